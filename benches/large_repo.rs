@@ -0,0 +1,101 @@
+//! Benchmark `pre-commit run --all-files` on a synthetic repo with a large number of files.
+//!
+//! The first run is reported separately, as it fills the file tags cache in the store.
+//!
+//! Run with `cargo bench --bench large_repo`, set `PRE_COMMIT_BENCH_FILES` to change the
+//! number of files (default 100,000).
+#![allow(clippy::print_stdout)]
+
+use std::path::Path;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+const DEFAULT_FILES: usize = 100_000;
+const ITERATIONS: u32 = 5;
+
+fn git(dir: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .status()
+        .expect("Failed to run git");
+    assert!(status.success(), "git {args:?} failed");
+}
+
+fn setup(dir: &Path, files: usize) {
+    git(dir, &["init", "--quiet"]);
+    for i in 0..files {
+        let subdir = dir.join(format!("dir{}", i % 100));
+        std::fs::create_dir_all(&subdir).expect("Failed to create directory");
+        let ext = ["py", "rs", "txt", "json"][i % 4];
+        std::fs::write(subdir.join(format!("file{i}.{ext}")), "content\n")
+            .expect("Failed to write file");
+    }
+    std::fs::write(
+        dir.join(".pre-commit-config.yaml"),
+        indoc::indoc! {r"
+            repos:
+              - repo: local
+                hooks:
+                  - id: python
+                    name: python
+                    language: system
+                    entry: 'true'
+                    types: [python]
+                  - id: json
+                    name: json
+                    language: system
+                    entry: 'true'
+                    files: \.json$
+                  - id: all
+                    name: all
+                    language: system
+                    entry: 'true'
+                    pass_filenames: false
+        "},
+    )
+    .expect("Failed to write config");
+    git(dir, &["add", "."]);
+}
+
+fn main() {
+    let files = std::env::var("PRE_COMMIT_BENCH_FILES")
+        .ok()
+        .and_then(|files| files.parse().ok())
+        .unwrap_or(DEFAULT_FILES);
+
+    let temp = tempfile::tempdir().expect("Failed to create temp dir");
+    let home = tempfile::tempdir().expect("Failed to create temp dir");
+    setup(temp.path(), files);
+
+    let run = || {
+        let start = Instant::now();
+        let output = Command::new(env!("CARGO_BIN_EXE_pre-commit"))
+            .args(["run", "--all-files"])
+            .current_dir(temp.path())
+            .env("PRE_COMMIT_HOME", home.path())
+            .output()
+            .expect("Failed to run pre-commit");
+        let elapsed = start.elapsed();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stdout)
+        );
+        elapsed
+    };
+
+    // The first run fills the file tags cache, later runs reuse it.
+    let cold = run();
+    let mut warm = Duration::ZERO;
+    for _ in 0..ITERATIONS {
+        warm += run();
+    }
+
+    println!("run --all-files with {files} files:");
+    println!("  first run (cold cache): {cold:?}");
+    println!(
+        "  later runs (warm cache): {:?} per iteration",
+        warm / ITERATIONS
+    );
+}
@@ -0,0 +1,103 @@
+//! GitHub Actions annotations for hook failures, see
+//! <https://docs.github.com/en/actions/reference/workflow-commands-for-github-actions>.
+
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::config::Language;
+use crate::fs::normalize_path;
+use crate::hook::Hook;
+
+/// Create `::error` annotations for a failed hook from its output.
+///
+/// `filenames` are the files the hook ran on, relative to the repo root. Only locations in
+/// these files are annotated, so unrelated `path:line` text in the output is ignored.
+pub fn github_annotations(hook: &Hook, filenames: &[&String], output: &[u8]) -> Vec<String> {
+    let title = escape_property(&hook.name);
+
+    // `fail` reports the files without locations, its entry is the message.
+    if hook.language.name() == Language::Fail {
+        let message = escape_data(hook.entry.trim());
+        return filenames
+            .iter()
+            .map(|file| {
+                format!(
+                    "::error file={},title={title}::{message}",
+                    escape_property(file)
+                )
+            })
+            .collect();
+    }
+
+    let filenames: HashSet<&str> = filenames.iter().map(|f| f.as_str()).collect();
+    let output = String::from_utf8_lossy(output);
+    output
+        .lines()
+        .filter_map(parse_location)
+        .filter_map(|(file, line, message)| {
+            // Paths in the output are relative to the hook's working directory.
+            let mut file = match hook.work_dir.as_deref() {
+                Some(work_dir) => Path::new(work_dir).join(file).to_string_lossy().to_string(),
+                None => file.to_string(),
+            };
+            normalize_path(&mut file);
+            filenames.contains(file.as_str()).then(|| {
+                format!(
+                    "::error file={},line={line},title={title}::{}",
+                    escape_property(&file),
+                    escape_data(message.trim())
+                )
+            })
+        })
+        .collect()
+}
+
+/// Parse a `path:line:message` or `path:line:column:message` line, e.g. from `pygrep` or
+/// most linters.
+fn parse_location(line: &str) -> Option<(&str, usize, &str)> {
+    let (file, rest) = line.split_once(':')?;
+    let (line_no, rest) = rest.split_once(':')?;
+    let line_no = line_no.trim().parse().ok()?;
+    // Skip the column, it isn't needed to show the annotation.
+    let message = match rest.split_once(':') {
+        Some((column, message)) if column.trim().parse::<usize>().is_ok() => message,
+        _ => rest,
+    };
+    Some((file, line_no, message))
+}
+
+fn escape_data(value: &str) -> String {
+    value
+        .replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn escape_property(value: &str) -> String {
+    escape_data(value).replace(':', "%3A").replace(',', "%2C")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_locations() {
+        assert_eq!(
+            parse_location("src/main.rs:12:    todo!()"),
+            Some(("src/main.rs", 12, "    todo!()"))
+        );
+        assert_eq!(
+            parse_location("src/main.rs:12:5: unused variable"),
+            Some(("src/main.rs", 12, " unused variable"))
+        );
+        assert_eq!(parse_location("Fixing src/main.rs"), None);
+        assert_eq!(parse_location("error: src/main.rs: failed"), None);
+    }
+
+    #[test]
+    fn escape() {
+        assert_eq!(escape_data("100%\nfailed"), "100%25%0Afailed");
+        assert_eq!(escape_property("a,b:c"), "a%2Cb%3Ac");
+    }
+}
@@ -0,0 +1,116 @@
+use std::fmt::Display;
+
+/// A CI provider, detected from the env vars it sets.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Provider {
+    GitHubActions,
+    GitLab,
+    CircleCi,
+    Travis,
+    AzurePipelines,
+    Jenkins,
+    Buildkite,
+    TeamCity,
+    Bitbucket,
+    /// `CI` is set, but the provider is unknown.
+    Other,
+}
+
+impl Provider {
+    /// The env var set by each provider.
+    const VARS: &'static [(&'static str, Provider)] = &[
+        ("GITHUB_ACTIONS", Provider::GitHubActions),
+        ("GITLAB_CI", Provider::GitLab),
+        ("CIRCLECI", Provider::CircleCi),
+        ("TRAVIS", Provider::Travis),
+        ("TF_BUILD", Provider::AzurePipelines),
+        ("JENKINS_URL", Provider::Jenkins),
+        ("BUILDKITE", Provider::Buildkite),
+        ("TEAMCITY_VERSION", Provider::TeamCity),
+        ("BITBUCKET_BUILD_NUMBER", Provider::Bitbucket),
+    ];
+
+    /// Detect the provider using the given env var lookup.
+    fn detect(var: impl Fn(&str) -> Option<String>) -> Option<Self> {
+        let ci = var("CI");
+        // Some users set `CI=false` to opt out.
+        if ci
+            .as_deref()
+            .is_some_and(|ci| matches!(ci, "" | "0" | "false" | "False" | "FALSE"))
+        {
+            return None;
+        }
+
+        Self::VARS
+            .iter()
+            .find(|(name, _)| var(name).is_some())
+            .map(|(_, provider)| *provider)
+            .or(ci.map(|_| Provider::Other))
+    }
+}
+
+impl Display for Provider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Provider::GitHubActions => "GitHub Actions",
+            Provider::GitLab => "GitLab CI",
+            Provider::CircleCi => "CircleCI",
+            Provider::Travis => "Travis CI",
+            Provider::AzurePipelines => "Azure Pipelines",
+            Provider::Jenkins => "Jenkins",
+            Provider::Buildkite => "Buildkite",
+            Provider::TeamCity => "TeamCity",
+            Provider::Bitbucket => "Bitbucket Pipelines",
+            Provider::Other => "CI",
+        };
+        f.write_str(name)
+    }
+}
+
+/// The CI provider we are running in, if any.
+pub fn provider() -> Option<Provider> {
+    Provider::detect(|name| std::env::var(name).ok())
+}
+
+/// Whether we are running in a CI environment.
+pub fn is_ci() -> bool {
+    provider().is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn detect(vars: &[(&str, &str)]) -> Option<Provider> {
+        let vars: HashMap<_, _> = vars.iter().copied().collect();
+        Provider::detect(|name| vars.get(name).map(ToString::to_string))
+    }
+
+    #[test]
+    fn detect_provider() {
+        assert_eq!(detect(&[]), None);
+
+        for &(name, provider) in Provider::VARS {
+            assert_eq!(detect(&[(name, "true")]), Some(provider), "{name}");
+            // Most providers set `CI` too.
+            assert_eq!(
+                detect(&[("CI", "true"), (name, "1")]),
+                Some(provider),
+                "{name}"
+            );
+        }
+
+        assert_eq!(detect(&[("CI", "true")]), Some(Provider::Other));
+        assert_eq!(detect(&[("CI", "1")]), Some(Provider::Other));
+    }
+
+    #[test]
+    fn ci_opt_out() {
+        for value in ["", "0", "false"] {
+            assert_eq!(detect(&[("CI", value)]), None);
+            assert_eq!(detect(&[("CI", value), ("GITHUB_ACTIONS", "true")]), None);
+        }
+    }
+}
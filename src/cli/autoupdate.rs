@@ -0,0 +1,175 @@
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use anyhow::Result;
+use fancy_regex::Regex;
+use futures::StreamExt;
+use owo_colors::OwoColorize;
+
+use crate::cli::ExitStatus;
+use crate::config::{read_config, ConfigRepo};
+use crate::fs::Simplified;
+use crate::git;
+use crate::hook::Project;
+use crate::printer::Printer;
+
+/// A `rev` line of a repo, the value is replaced while keeping indentation, quotes and comments.
+static REV_LINE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"^(\s+)rev:(\s*)(['"]?)([^\s#'"]+)(['"]?)(.*)$"#).expect("Invalid regex")
+});
+
+/// The rev a repo is updated to.
+struct RevInfo {
+    rev: String,
+    /// The tag of the rev, when frozen to a commit hash.
+    frozen: Option<String>,
+}
+
+pub(crate) async fn autoupdate(
+    config: Option<PathBuf>,
+    bleeding_edge: bool,
+    freeze: bool,
+    repos: Vec<String>,
+    jobs: usize,
+    dry_run: bool,
+    printer: Printer,
+) -> Result<ExitStatus> {
+    let config_path = Project::find_config_file(config)?;
+    let config = read_config(&config_path)?;
+
+    let remotes: Vec<_> = config
+        .repos
+        .iter()
+        .filter_map(|repo| match repo {
+            ConfigRepo::Remote(repo) => Some(repo),
+            _ => None,
+        })
+        .collect();
+
+    let tasks = remotes
+        .iter()
+        .enumerate()
+        .filter(|(_, remote)| {
+            repos.is_empty() || repos.iter().any(|repo| repo == remote.repo.as_str())
+        })
+        .map(|(idx, remote)| async move {
+            let result = resolve_rev(remote.repo.as_str(), bleeding_edge, freeze).await;
+            (idx, remote, result)
+        });
+    // Report in the order of the config.
+    let results: Vec<_> = futures::stream::iter(tasks)
+        .buffered(jobs.max(1))
+        .collect()
+        .await;
+
+    let mut status = ExitStatus::Success;
+    let mut updates: Vec<Option<RevInfo>> = remotes.iter().map(|_| None).collect();
+    for (idx, remote, result) in results {
+        match result {
+            Ok(info) if info.rev == remote.rev => {
+                writeln!(printer.stdout(), "{}: already up to date", remote.repo)?;
+            }
+            Ok(info) => {
+                writeln!(
+                    printer.stdout(),
+                    "{}: {} -> {}",
+                    remote.repo,
+                    remote.rev,
+                    info.rev.cyan()
+                )?;
+                updates[idx] = Some(info);
+            }
+            Err(err) => {
+                writeln!(
+                    printer.stderr(),
+                    "{}: Failed to update `{}`: {err:#}",
+                    "error".red().bold(),
+                    remote.repo
+                )?;
+                status = ExitStatus::Failure;
+            }
+        }
+    }
+
+    if dry_run || updates.iter().all(Option::is_none) {
+        return Ok(status);
+    }
+
+    let content = fs_err::read_to_string(&config_path)?;
+    let content = rewrite_revs(&content, &updates, &config_path)?;
+    fs_err::write(&config_path, content)?;
+
+    Ok(status)
+}
+
+/// Find the latest rev of a repo, a tag or with `bleeding_edge` the latest commit.
+async fn resolve_rev(url: &str, bleeding_edge: bool, freeze: bool) -> Result<RevInfo> {
+    let temp = tempfile::tempdir()?;
+    git::fetch_head(url, temp.path()).await?;
+
+    // With `bleeding_edge`, prefer a tag if the latest commit is tagged.
+    let rev = match git::describe_tag(temp.path(), "FETCH_HEAD", bleeding_edge).await? {
+        Some(tag) => tag,
+        None => git::rev_parse(temp.path(), "FETCH_HEAD").await?,
+    };
+
+    if freeze {
+        let exact = git::rev_parse(temp.path(), &rev).await?;
+        if exact != rev {
+            return Ok(RevInfo {
+                rev: exact,
+                frozen: Some(rev),
+            });
+        }
+    }
+    Ok(RevInfo { rev, frozen: None })
+}
+
+/// Rewrite the `rev` lines of the remote repos, line by line to keep the formatting.
+fn rewrite_revs(content: &str, updates: &[Option<RevInfo>], path: &Path) -> Result<String> {
+    let mut lines: Vec<String> = content
+        .split_inclusive('\n')
+        .map(ToString::to_string)
+        .collect();
+    let rev_lines: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| {
+            REV_LINE
+                .is_match(line.trim_end_matches(['\r', '\n']))
+                .unwrap_or(false)
+        })
+        .map(|(idx, _)| idx)
+        .collect();
+    if rev_lines.len() != updates.len() {
+        anyhow::bail!(
+            "Failed to find the `rev` line of each repo in `{}`, update it manually",
+            path.user_display()
+        );
+    }
+
+    for (idx, update) in rev_lines.into_iter().zip(updates) {
+        let Some(update) = update else {
+            continue;
+        };
+        let line = &lines[idx];
+        let body = line.trim_end_matches(['\r', '\n']);
+        let ending = &line[body.len()..];
+        let captures = REV_LINE.captures(body)?.expect("rev line matched before");
+
+        let comment = &captures[6];
+        // A stale frozen comment is replaced, or dropped when not frozen anymore.
+        let comment = match update.frozen {
+            Some(ref tag) => format!("  # frozen: {tag}"),
+            None if comment.trim_start().starts_with("# frozen:") => String::new(),
+            None => comment.to_string(),
+        };
+        lines[idx] = format!(
+            "{}rev:{}{}{}{}{comment}{ending}",
+            &captures[1], &captures[2], &captures[3], update.rev, &captures[5]
+        );
+    }
+
+    Ok(lines.concat())
+}
@@ -0,0 +1,15 @@
+use std::fmt::Write;
+
+use anyhow::Result;
+
+use crate::cli::ExitStatus;
+use crate::printer::Printer;
+use crate::store::Store;
+
+/// Print the store directory, e.g. for caching it in CI.
+pub(crate) fn cache_dir(printer: Printer) -> Result<ExitStatus> {
+    let store = Store::from_settings()?;
+    writeln!(printer.stdout(), "{}", store.path().display())?;
+
+    Ok(ExitStatus::Success)
+}
@@ -0,0 +1,268 @@
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::path::Path;
+
+use anyhow::Result;
+use owo_colors::OwoColorize;
+
+use crate::cli::install::{is_current_script, is_our_script, missing_hook_scripts};
+use crate::cli::ExitStatus;
+use crate::config::HookType;
+use crate::fs::Simplified;
+use crate::git::{self, GIT, MIN_GIT_VERSION};
+use crate::hook::Project;
+use crate::printer::Printer;
+use crate::store::{Store, CLONE_COMPLETE_MARKER, LOCAL_NAME};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Level {
+    Ok,
+    Warn,
+    Fail,
+}
+
+/// The result of a single check.
+struct Check {
+    name: &'static str,
+    level: Level,
+    message: String,
+}
+
+impl Check {
+    fn ok(name: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            name,
+            level: Level::Ok,
+            message: message.into(),
+        }
+    }
+
+    fn warn(name: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            name,
+            level: Level::Warn,
+            message: message.into(),
+        }
+    }
+
+    fn fail(name: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            name,
+            level: Level::Fail,
+            message: message.into(),
+        }
+    }
+}
+
+/// Check the health of the environment pre-commit runs in, for support triage.
+pub(crate) async fn doctor(printer: Printer) -> Result<ExitStatus> {
+    let store = Store::from_settings()?;
+
+    let mut checks = vec![check_git().await];
+    let store = match store.init() {
+        Ok(store) => {
+            checks.push(check_store_writable(&store));
+            Some(store)
+        }
+        Err(err) => {
+            checks.push(Check::fail(
+                "store",
+                format!("Failed to open the store: {err}"),
+            ));
+            None
+        }
+    };
+    checks.push(check_hooks_path().await);
+    checks.push(check_hook_scripts().await);
+    if let Some(store) = &store {
+        checks.push(check_cache(store)?);
+    }
+
+    for check in &checks {
+        let level = match check.level {
+            Level::Ok => "OK  ".green().to_string(),
+            Level::Warn => "WARN".yellow().to_string(),
+            Level::Fail => "FAIL".red().to_string(),
+        };
+        writeln!(
+            printer.stdout(),
+            "{level} {}: {}",
+            check.name.bold(),
+            check.message
+        )?;
+    }
+
+    if checks.iter().any(|check| check.level == Level::Fail) {
+        Ok(ExitStatus::Failure)
+    } else {
+        Ok(ExitStatus::Success)
+    }
+}
+
+async fn check_git() -> Check {
+    let path = match GIT.as_ref() {
+        Ok(path) => path,
+        Err(err) => return Check::fail("git", format!("git is not found: {err}")),
+    };
+    match git::git_version().await {
+        Ok(version) if version < MIN_GIT_VERSION => Check::fail(
+            "git",
+            format!("git {version} is not supported, upgrade git to >= {MIN_GIT_VERSION}"),
+        ),
+        Ok(version) => Check::ok("git", format!("{version} at {}", path.user_display())),
+        Err(err) => Check::fail("git", format!("Failed to get the git version: {err}")),
+    }
+}
+
+fn check_store_writable(store: &Store) -> Check {
+    match tempfile::tempfile_in(store.path()) {
+        Ok(_) => Check::ok(
+            "store",
+            format!("{} is writable", store.path().user_display()),
+        ),
+        Err(err) => Check::fail(
+            "store",
+            format!("{} is not writable: {err}", store.path().user_display()),
+        ),
+    }
+}
+
+async fn check_hooks_path() -> Check {
+    match git::has_hooks_path_set().await {
+        Ok(true) => Check::warn(
+            "core.hooksPath",
+            "set, git runs the hooks there instead of the scripts installed by pre-commit",
+        ),
+        Ok(false) => Check::ok("core.hooksPath", "not set"),
+        Err(err) => Check::warn("core.hooksPath", format!("Failed to check: {err}")),
+    }
+}
+
+async fn check_hook_scripts() -> Check {
+    match hook_scripts().await {
+        Ok(check) => check,
+        Err(err) => Check::warn("hook scripts", format!("Failed to check: {err:#}")),
+    }
+}
+
+/// Check the scripts installed by pre-commit are current, and that none the config expects
+/// is missing.
+async fn hook_scripts() -> Result<Check> {
+    let hooks_path = git::get_git_common_dir().await?.join("hooks");
+
+    let mut installed = Vec::new();
+    let mut outdated = Vec::new();
+    if hooks_path.try_exists()? {
+        let mut entries = fs_err::read_dir(&hooks_path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        entries.sort();
+        for path in entries {
+            if !path.is_file() || !is_our_script(&path)? {
+                continue;
+            }
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            if !is_current_script(&path)? {
+                outdated.push(name.clone());
+            }
+            installed.push(name);
+        }
+    }
+
+    let missing = match Project::from_config_file(None) {
+        Ok(project) => missing_hook_scripts(&project).await?,
+        Err(_) => vec![],
+    };
+
+    let mut problems = Vec::new();
+    if !outdated.is_empty() {
+        problems.push(format!("outdated: {}", outdated.join(", ")));
+    }
+    if !missing.is_empty() {
+        let missing: Vec<_> = missing.iter().map(HookType::as_str).collect();
+        problems.push(format!("missing: {}", missing.join(", ")));
+    }
+    if !problems.is_empty() {
+        return Ok(Check::warn(
+            "hook scripts",
+            format!(
+                "{}, run `pre-commit install` to fix this",
+                problems.join("; ")
+            ),
+        ));
+    }
+
+    if installed.is_empty() {
+        Ok(Check::ok("hook scripts", "none installed"))
+    } else {
+        Ok(Check::ok(
+            "hook scripts",
+            format!("{} up to date", installed.join(", ")),
+        ))
+    }
+}
+
+/// Check for cached repos that are missing, partially cloned, or left behind untracked.
+fn check_cache(store: &Store) -> Result<Check> {
+    let entries = store.repo_entries()?;
+
+    let mut missing = 0;
+    let mut partial = 0;
+    let mut tracked = HashSet::new();
+    for (name, _, path) in &entries {
+        let path = Path::new(path);
+        if let Some(file_name) = path.file_name() {
+            tracked.insert(file_name.to_os_string());
+        }
+        if !path.try_exists()? {
+            missing += 1;
+        } else if !is_local(name) && !path.join(".git").join(CLONE_COMPLETE_MARKER).try_exists()? {
+            partial += 1;
+        }
+    }
+
+    let mut orphaned = 0;
+    for entry in fs_err::read_dir(store.path())? {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        if entry.file_type()?.is_dir()
+            && file_name.to_string_lossy().starts_with("repo")
+            && !tracked.contains(&file_name)
+        {
+            orphaned += 1;
+        }
+    }
+
+    if missing + partial + orphaned == 0 {
+        return Ok(Check::ok(
+            "cache",
+            format!("{} repo(s) cached", entries.len()),
+        ));
+    }
+
+    let mut problems = Vec::new();
+    if missing > 0 {
+        problems.push(format!("{missing} missing"));
+    }
+    if partial > 0 {
+        problems.push(format!("{partial} partially cloned"));
+    }
+    if orphaned > 0 {
+        problems.push(format!("{orphaned} orphaned"));
+    }
+    Ok(Check::warn(
+        "cache",
+        format!(
+            "{} repo(s), run `pre-commit gc` or `pre-commit clean` to fix this",
+            problems.join(", ")
+        ),
+    ))
+}
+
+/// Local repos are created in place, they are never cloned.
+fn is_local(name: &str) -> bool {
+    name == LOCAL_NAME || name.starts_with(&format!("{LOCAL_NAME}:"))
+}
@@ -0,0 +1,193 @@
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::Result;
+use tracing::debug;
+
+use crate::cli::ExitStatus;
+use crate::config::{read_config_with_includes, ConfigRepo};
+use crate::fs::Simplified;
+use crate::printer::Printer;
+use crate::store::{Store, LOCAL_NAME, LOCAL_REV};
+
+/// Hook results that have not been used for this long are removed.
+const HOOK_RESULTS_MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Repos and environments referenced by the configs that have used the store.
+#[derive(Default)]
+struct References {
+    /// Remote repos as `(url, rev)`.
+    remotes: HashSet<(String, String)>,
+    /// Whether any config has local hooks.
+    local: bool,
+}
+
+impl References {
+    fn uses(&self, url: &str, rev: &str) -> bool {
+        self.remotes.contains(&(url.to_string(), rev.to_string()))
+    }
+}
+
+#[derive(Default)]
+struct Removed {
+    count: usize,
+    bytes: u64,
+}
+
+pub(crate) async fn gc(printer: Printer) -> Result<ExitStatus> {
+    let store = Store::from_settings()?.init()?;
+    // Hold the lock for the whole sweep, runs mark their config as used under the same lock
+    // before installing environments, so nothing they are about to use is removed.
+    let _lock = store.lock_async().await?;
+
+    let references = collect_references(&store)?;
+    let entries = store.repo_entries()?;
+
+    let mut repos = Removed::default();
+    let mut envs = Removed::default();
+
+    for (name, rev, path) in &entries {
+        let (is_env, used) = classify(name, rev, &entries, &references);
+
+        let path = Path::new(path);
+        if used && path.exists() {
+            continue;
+        }
+
+        debug!(name, rev, path = %path.display(), "Removing unused repo");
+        let bytes = dir_size(path);
+        store.remove_repo(name, rev, path)?;
+
+        let removed = if is_env { &mut envs } else { &mut repos };
+        removed.count += 1;
+        removed.bytes += bytes;
+    }
+
+    let file_tags = store.prune_file_tags()?;
+    let hook_results = store.prune_hook_results(HOOK_RESULTS_MAX_AGE)?;
+    debug!(file_tags, hook_results, "Pruned cached results");
+
+    writeln!(
+        printer.stdout(),
+        "{} repo(s) removed ({})",
+        repos.count,
+        human_readable_bytes(repos.bytes)
+    )?;
+    writeln!(
+        printer.stdout(),
+        "{} environment(s) removed ({})",
+        envs.count,
+        human_readable_bytes(envs.bytes)
+    )?;
+
+    Ok(ExitStatus::Success)
+}
+
+/// Collect the repos referenced by the configs, and forget configs that no longer exist.
+fn collect_references(store: &Store) -> Result<References> {
+    let mut references = References::default();
+
+    for config_path in store.configs()? {
+        let config = match read_config_with_includes(&config_path) {
+            Ok(config) => config,
+            Err(err) => {
+                debug!(path = %config_path.user_display(), %err, "Forgetting config");
+                store.remove_config(&config_path)?;
+                continue;
+            }
+        };
+
+        for repo in config.repos {
+            match repo {
+                ConfigRepo::Remote(repo) => {
+                    references
+                        .remotes
+                        .insert((repo.repo.as_str().to_string(), repo.rev));
+                }
+                ConfigRepo::Local(_) => references.local = true,
+                ConfigRepo::Meta(_) => {}
+            }
+        }
+    }
+
+    Ok(references)
+}
+
+/// Classify a stored repo, returns whether it is an environment and whether it is still used.
+fn classify(
+    name: &str,
+    rev: &str,
+    entries: &[(String, String, String)],
+    references: &References,
+) -> (bool, bool) {
+    // Environments of local hooks don't depend on a cloned repo.
+    if name == LOCAL_NAME || name.starts_with(&format!("{LOCAL_NAME}:")) {
+        return (true, references.local && rev == LOCAL_REV);
+    }
+
+    // An environment with additional dependencies, which is copied from the base repo.
+    if let Some(url) = base_repo(name, rev, entries) {
+        return (true, references.uses(url, rev));
+    }
+
+    // The base repo is no longer cached, the environment is orphaned.
+    if references
+        .remotes
+        .iter()
+        .any(|(url, r)| r == rev && name.starts_with(&format!("{url}:")))
+    {
+        return (true, false);
+    }
+
+    (false, references.uses(name, rev))
+}
+
+/// Find the cached base repo of an environment with additional dependencies.
+fn base_repo<'a>(
+    name: &str,
+    rev: &str,
+    entries: &'a [(String, String, String)],
+) -> Option<&'a str> {
+    entries
+        .iter()
+        .filter(|(base, base_rev, _)| base_rev == rev && base != name)
+        .find(|(base, _, _)| {
+            name.strip_prefix(base.as_str())
+                .is_some_and(|deps| deps.starts_with(':'))
+        })
+        .map(|(base, _, _)| base.as_str())
+}
+
+/// Calculate the size of a directory, symlinks are not followed.
+fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = fs_err::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .filter_map(Result::ok)
+        .map(|entry| match entry.path().symlink_metadata() {
+            Ok(metadata) if metadata.is_dir() => dir_size(&entry.path()),
+            Ok(metadata) => metadata.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn human_readable_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes}{}", UNITS[unit])
+    } else {
+        format!("{size:.1}{}", UNITS[unit])
+    }
+}
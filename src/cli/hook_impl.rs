@@ -42,24 +42,17 @@ pub(crate) async fn hook_impl(
 
     let run_args = to_run_args(hook_type, &args);
 
-    cli::run(
-        config,
-        run_args.hook_id,
-        Some(hook_type.into()),
-        run_args.from_ref,
-        run_args.to_ref,
-        run_args.all_files,
-        vec![],
-        false,
-        run_args.extra,
-        false,
-        printer,
-    )
-    .await
+    cli::run(config, run_args, false, printer).await
 }
 
 fn to_run_args(hook_type: HookType, args: &[OsString]) -> RunArgs {
-    let mut run_args = RunArgs::default();
+    let mut run_args = RunArgs {
+        hook_stage: vec![hook_type.into()],
+        no_show_diff_on_failure: true,
+        output_format: Some(cli::OutputFormat::Text),
+        max_output_lines: cli::DEFAULT_MAX_OUTPUT_LINES,
+        ..RunArgs::default()
+    };
 
     match hook_type {
         HookType::PrePush => {
@@ -85,7 +78,15 @@ fn to_run_args(hook_type: HookType, args: &[OsString]) -> RunArgs {
             run_args.to_ref = Some(args[1].to_string_lossy().into_owned());
             run_args.extra.checkout_type = Some(args[2].to_string_lossy().into_owned());
         }
-        HookType::PostMerge => run_args.extra.is_squash_merge = args[0] == "1",
+        HookType::PostMerge => {
+            run_args.extra.is_squash_merge = args[0] == "1";
+            // Run on the files changed by the merge, a squash merge doesn't commit,
+            // the changes are staged instead.
+            if !run_args.extra.is_squash_merge {
+                run_args.from_ref = Some("ORIG_HEAD".to_string());
+                run_args.to_ref = Some("HEAD".to_string());
+            }
+        }
         HookType::PostRewrite => {
             run_args.extra.rewrite_command = Some(args[0].to_string_lossy().into_owned());
         }
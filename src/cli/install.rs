@@ -3,16 +3,19 @@ use std::io::Write;
 use std::path::{Path, PathBuf};
 
 use anyhow::Result;
+use clap::ValueEnum;
 use indoc::indoc;
 use owo_colors::OwoColorize;
 
 use crate::cli::run;
 use crate::cli::{ExitStatus, HookType};
+use crate::config::{resolve_hook_types, CONFIG_FILE};
 use crate::fs::Simplified;
 use crate::git;
 use crate::hook::Project;
 use crate::printer::Printer;
 use crate::store::Store;
+use crate::warn_user;
 
 pub(crate) async fn install(
     config: Option<PathBuf>,
@@ -22,6 +25,14 @@ pub(crate) async fn install(
     allow_missing_config: bool,
     printer: Printer,
 ) -> Result<ExitStatus> {
+    let husky_hooks = husky_hooks()?;
+    if !husky_hooks.is_empty() {
+        warn_user!(
+            "Found husky hooks in `{HUSKY_DIR}`: {}, port them to `{CONFIG_FILE}` and remove husky so they don't run alongside pre-commit",
+            husky_hooks.join(", ")
+        );
+    }
+
     if git::has_hooks_path_set().await? {
         writeln!(
             printer.stderr(),
@@ -54,36 +65,47 @@ pub(crate) async fn install(
     if install_hooks {
         let mut project = project?;
         let store = Store::from_settings()?.init()?;
-        let _lock = store.lock_async().await?;
-
-        let hooks = project.init_hooks(&store, printer).await?;
+        let hooks = {
+            let _lock = store.lock_async().await?;
+            project.init_hooks(&store, printer).await?
+        };
         run::install_hooks(&hooks, printer).await?;
     }
 
     Ok(ExitStatus::Success)
 }
 
-fn get_hook_types(config_file: Option<PathBuf>, hook_types: Vec<HookType>) -> Vec<HookType> {
-    let project = Project::from_config_file(config_file);
+/// The directory husky keeps its hook scripts in.
+const HUSKY_DIR: &str = ".husky";
 
-    let mut hook_types = if hook_types.is_empty() {
-        if let Ok(ref project) = project {
-            project
-                .config()
-                .default_install_hook_types
-                .clone()
-                .unwrap_or_default()
-        } else {
-            vec![]
+/// The hook scripts managed by husky, husky's own files in `.husky/_` are not included.
+fn husky_hooks() -> Result<Vec<String>> {
+    let husky_dir = Path::new(HUSKY_DIR);
+    if !husky_dir.is_dir() {
+        return Ok(vec![]);
+    }
+
+    let mut hooks = Vec::new();
+    for entry in fs_err::read_dir(husky_dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().to_string();
+        if HookType::value_variants()
+            .iter()
+            .any(|hook_type| hook_type.as_str() == name)
+        {
+            hooks.push(name);
         }
-    } else {
-        hook_types
-    };
-    if hook_types.is_empty() {
-        hook_types = vec![HookType::PreCommit];
     }
+    hooks.sort_unstable();
+    Ok(hooks)
+}
 
-    hook_types
+fn get_hook_types(config_file: Option<PathBuf>, hook_types: Vec<HookType>) -> Vec<HookType> {
+    let project = Project::from_config_file(config_file);
+    resolve_hook_types(project.as_ref().ok().map(Project::config), hook_types)
 }
 
 fn install_hook_script(
@@ -103,6 +125,13 @@ fn install_hook_script(
                 "Overwriting existing hook at {}",
                 hook_path.user_display().cyan()
             )?;
+        } else if is_python_script(&hook_path)? {
+            // Keeping it as `.legacy` would run the hooks twice.
+            writeln!(
+                printer.stdout(),
+                "Replacing the hook installed by Python pre-commit at {}",
+                hook_path.user_display().cyan()
+            )?;
         } else {
             if !is_our_script(&hook_path)? {
                 let legacy_path = format!("{}.legacy", hook_path.display());
@@ -182,23 +211,100 @@ static PRIOR_HASHES: &[&str] = &[];
 // Use a different hash from `pre-commit` since our script is different.
 static CURRENT_HASH: &str = "182c10f181da4464a3eec51b83331688";
 
+/// The current and prior hashes of the scripts installed by the Python `pre-commit`.
+static PYTHON_HASHES: &[&str] = &[
+    "138fd403232d2ddd5efb44317e38bf03",
+    "4d9958c90bc262f47553e2c073f14cfe",
+    "d8ee923c46731b42cd95cc869add4062",
+    "49fd668cb42069aa1b6048464be5d395",
+    "79f09a650522a87b0da915d0d983b2de",
+    "e358c9dae00eac5d06b38dfdb1e33a8c",
+];
+
+/// The hook types the config expects to be installed, but whose scripts are missing,
+/// so hooks won't run on the corresponding git events.
+pub(crate) async fn missing_hook_scripts(project: &Project) -> Result<Vec<HookType>> {
+    // Hooks are managed by something else.
+    if git::has_hooks_path_set().await? {
+        return Ok(vec![]);
+    }
+
+    let hook_types = resolve_hook_types(Some(project.config()), vec![]);
+
+    let hooks_path = git::get_git_common_dir().await?.join("hooks");
+    let mut missing = Vec::new();
+    for hook_type in hook_types {
+        let hook_path = hooks_path.join(hook_type.as_str());
+        if !hook_path.try_exists()? || !is_our_script(&hook_path)? {
+            missing.push(hook_type);
+        }
+    }
+
+    Ok(missing)
+}
+
 /// Checks if the script contains any of the hashes that `pre-commit` has used in the past.
-fn is_our_script(hook_path: &Path) -> Result<bool> {
-    let content = fs_err::read_to_string(hook_path)?;
-    Ok(std::iter::once(CURRENT_HASH)
-        .chain(PRIOR_HASHES.iter().copied())
-        .any(|hash| content.contains(hash)))
+pub(crate) fn is_our_script(hook_path: &Path) -> Result<bool> {
+    contains_any(
+        hook_path,
+        std::iter::once(CURRENT_HASH).chain(PRIOR_HASHES.iter().copied()),
+    )
+}
+
+/// Checks if the script is the current version, calling the current executable.
+/// Older scripts still run, but miss fixes to the script or call another `pre-commit`.
+pub(crate) fn is_current_script(hook_path: &Path) -> Result<bool> {
+    let content = fs_err::read(hook_path)?;
+    let content = String::from_utf8_lossy(&content);
+    let pre_commit = std::env::current_exe()?;
+    let pre_commit = format!(r#"PRE_COMMIT="{}""#, pre_commit.simplified().display());
+    Ok(content.contains(CURRENT_HASH) && content.contains(&pre_commit))
+}
+
+/// Checks if the script was installed by the Python `pre-commit`, e.g. before migrating.
+fn is_python_script(hook_path: &Path) -> Result<bool> {
+    contains_any(hook_path, PYTHON_HASHES.iter().copied())
+}
+
+fn contains_any<'a>(hook_path: &Path, hashes: impl IntoIterator<Item = &'a str>) -> Result<bool> {
+    // Other hooks may be binaries.
+    let content = fs_err::read(hook_path)?;
+    let content = String::from_utf8_lossy(&content);
+    Ok(hashes.into_iter().any(|hash| content.contains(hash)))
 }
 
 pub(crate) async fn uninstall(
     config: Option<PathBuf>,
     hook_types: Vec<HookType>,
+    all: bool,
     printer: Printer,
 ) -> Result<ExitStatus> {
+    let hooks_path = git::get_git_common_dir().await?.join("hooks");
+
+    if all {
+        let mut names = Vec::new();
+        if hooks_path.try_exists()? {
+            for entry in fs_err::read_dir(&hooks_path)? {
+                let entry = entry?;
+                let name = entry.file_name().to_string_lossy().to_string();
+                // Backups are restored along with their hook.
+                if entry.file_type()?.is_file()
+                    && !name.ends_with(".legacy")
+                    && (is_our_script(&entry.path())? || is_python_script(&entry.path())?)
+                {
+                    names.push(name);
+                }
+            }
+        }
+        names.sort_unstable();
+        for name in names {
+            uninstall_hook_script(&hooks_path, &name, printer)?;
+        }
+        return Ok(ExitStatus::Success);
+    }
+
     for hook_type in get_hook_types(config, hook_types) {
-        let hooks_path = git::get_git_common_dir().await?.join("hooks");
         let hook_path = hooks_path.join(hook_type.as_str());
-        let legacy_path = hooks_path.join(format!("{}.legacy", hook_type.as_str()));
 
         if !hook_path.try_exists()? {
             writeln!(
@@ -206,30 +312,36 @@ pub(crate) async fn uninstall(
                 "{} does not exist, skipping.",
                 hook_path.user_display().cyan()
             )?;
-        } else if !is_our_script(&hook_path)? {
+        } else if !is_our_script(&hook_path)? && !is_python_script(&hook_path)? {
             writeln!(
                 printer.stderr(),
                 "{} is not managed by pre-commit, skipping.",
                 hook_path.user_display().cyan()
             )?;
         } else {
-            fs_err::remove_file(&hook_path)?;
-            writeln!(
-                printer.stdout(),
-                "Uninstalled {}",
-                hook_type.as_str().cyan()
-            )?;
-
-            if legacy_path.try_exists()? {
-                fs_err::rename(&legacy_path, &hook_path)?;
-                writeln!(
-                    printer.stdout(),
-                    "Restored previous hook to {}",
-                    hook_path.user_display().cyan()
-                )?;
-            }
+            uninstall_hook_script(&hooks_path, hook_type.as_str(), printer)?;
         }
     }
 
     Ok(ExitStatus::Success)
 }
+
+/// Remove our hook script `name`, restoring the previous hook if it was moved to `.legacy`.
+fn uninstall_hook_script(hooks_path: &Path, name: &str, printer: Printer) -> Result<()> {
+    let hook_path = hooks_path.join(name);
+    let legacy_path = hooks_path.join(format!("{name}.legacy"));
+
+    fs_err::remove_file(&hook_path)?;
+    writeln!(printer.stdout(), "Uninstalled {}", name.cyan())?;
+
+    if legacy_path.try_exists()? {
+        fs_err::rename(&legacy_path, &hook_path)?;
+        writeln!(
+            printer.stdout(),
+            "Restored previous hook to {}",
+            hook_path.user_display().cyan()
+        )?;
+    }
+
+    Ok(())
+}
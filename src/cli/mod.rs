@@ -1,4 +1,5 @@
 use std::ffi::OsString;
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
 use std::process::ExitCode;
 
@@ -6,7 +7,11 @@ use clap::{ArgAction, Args, Parser, Subcommand};
 
 use crate::config::{HookType, Stage};
 
+mod autoupdate;
+mod cache_dir;
 mod clean;
+mod doctor;
+mod gc;
 mod hook_impl;
 mod install;
 mod run;
@@ -14,10 +19,14 @@ mod sample_config;
 mod self_update;
 mod validate;
 
+pub(crate) use autoupdate::autoupdate;
+pub(crate) use cache_dir::cache_dir;
 pub(crate) use clean::clean;
+pub(crate) use doctor::doctor;
+pub(crate) use gc::gc;
 pub(crate) use hook_impl::hook_impl;
 pub(crate) use install::{install, uninstall};
-pub(crate) use run::run;
+pub(crate) use run::{list_stages, print_env_path, run};
 pub(crate) use sample_config::sample_config;
 pub(crate) use self_update::self_update;
 pub(crate) use validate::{validate_configs, validate_manifest};
@@ -74,6 +83,40 @@ impl From<ColorChoice> for anstream::ColorChoice {
     }
 }
 
+/// Which changes to show with `--show-diff-on-failure`.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum DiffScope {
+    /// Show the changes of all files.
+    #[default]
+    All,
+
+    /// Only show the changes of the files the failed hooks ran on.
+    Touched,
+}
+
+/// How the files changed between `--from-ref` and `--to-ref` are selected.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum RefRange {
+    /// The changes on `to_ref` since it diverged from `from_ref`, from their merge base,
+    /// like `git diff from_ref...to_ref`. Changes made on `from_ref` since are not included.
+    #[default]
+    ThreeDot,
+
+    /// All differences between the two refs, like `git diff from_ref..to_ref`.
+    TwoDot,
+}
+
+/// The format of hook results.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable output.
+    Text,
+
+    /// Human-readable output, plus GitHub Actions annotations for the locations reported by
+    /// failed hooks.
+    Github,
+}
+
 #[derive(Parser)]
 #[command(
     name = "pre-commit",
@@ -117,6 +160,12 @@ pub(crate) struct GlobalArgs {
     )]
     pub(crate) color: ColorChoice,
 
+    /// Disable colors in output, a shorthand for `--color never`.
+    ///
+    /// Colors are also disabled when `NO_COLOR` is set, unless `--color` is given.
+    #[arg(global = true, long)]
+    pub(crate) no_color: bool,
+
     /// Display the concise help for this command.
     #[arg(global = true, short, long, action = clap::ArgAction::HelpShort)]
     help: Option<bool>,
@@ -127,9 +176,12 @@ pub(crate) struct GlobalArgs {
     #[arg(global = true, long)]
     pub no_progress: bool,
 
-    /// Do not print any output.
-    #[arg(global = true, long, short, conflicts_with = "verbose")]
-    pub quiet: bool,
+    /// Use quiet output.
+    ///
+    /// Only hook failures are printed. Repeating this option, e.g., `-qq`, will enable a silent
+    /// mode in which pre-commit will write no output. `--verbose` takes precedence over this option.
+    #[arg(global = true, long, short, action = ArgAction::Count)]
+    pub quiet: u8,
 
     /// Use verbose output.
     #[arg(global = true, short, long, action = ArgAction::Count)]
@@ -146,6 +198,22 @@ pub(crate) struct GlobalArgs {
     pub show_settings: bool,
 }
 
+impl GlobalArgs {
+    /// The color choice for all output, including the hooks run.
+    pub(crate) fn color_choice(&self) -> ColorChoice {
+        if self.no_color {
+            return ColorChoice::Never;
+        }
+        // An explicit `--color` takes precedence, see https://no-color.org.
+        if matches!(self.color, ColorChoice::Auto)
+            && std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty())
+        {
+            return ColorChoice::Never;
+        }
+        self.color
+    }
+}
+
 #[derive(Debug, Subcommand)]
 pub(crate) enum Command {
     /// Install the git pre-commit hook.
@@ -166,10 +234,16 @@ pub(crate) enum Command {
     /// Auto-update pre-commit config to the latest repos' versions.
     #[command(name = "autoupdate")]
     AutoUpdate(AutoUpdateArgs),
-    /// Clean unused cached repos.
+    /// Clean unused cached repos and environments.
     GC,
     /// Clean out pre-commit files.
     Clean,
+    /// Show the pre-commit cache directory.
+    CacheDir,
+    /// Check the health of the environment: git, the store, installed hook scripts and the cache.
+    ///
+    /// Exits with a non-zero status if any check fails.
+    Doctor,
     /// Install hook script in a directory intended for use with `git config init.templateDir`.
     #[command(name = "init-templatedir")]
     InitTemplateDir,
@@ -199,6 +273,9 @@ pub(crate) struct InstallArgs {
     #[arg(long)]
     pub(crate) install_hooks: bool,
 
+    /// The git hook types to install, only the client-side hooks run by git are accepted.
+    ///
+    /// Defaults to `default_install_hook_types` of the config, then `pre-commit`.
     #[arg(short = 't', long = "hook-type", value_name = "HOOK_TYPE", value_enum)]
     pub(crate) hook_types: Vec<HookType>,
 
@@ -211,6 +288,10 @@ pub(crate) struct InstallArgs {
 pub(crate) struct UninstallArgs {
     #[arg(short = 't', long = "hook-type", value_name = "HOOK_TYPE", value_enum)]
     pub(crate) hook_types: Vec<HookType>,
+
+    /// Remove every hook script installed by pre-commit, whatever the configured hook types.
+    #[arg(long, conflicts_with = "hook_types")]
+    pub(crate) all: bool,
 }
 
 #[derive(Debug, Clone, Default, Args)]
@@ -241,7 +322,21 @@ pub(crate) struct RunExtraArgs {
     pub(crate) rewrite_command: Option<String>,
 }
 
+/// The default maximum number of output lines to show for each hook.
+pub(crate) const DEFAULT_MAX_OUTPUT_LINES: usize = 10_000;
+
+/// Resolve a `--foo`/`--no-foo` flag pair, `None` if neither is given.
+pub(crate) fn flag(yes: bool, no: bool) -> Option<bool> {
+    match (yes, no) {
+        (true, false) => Some(true),
+        (false, true) => Some(false),
+        (false, false) => None,
+        (true, true) => unreachable!("`overrides_with` makes the flags mutually exclusive"),
+    }
+}
+
 #[derive(Debug, Clone, Default, Args)]
+#[allow(clippy::struct_excessive_bools)]
 pub(crate) struct RunArgs {
     /// The hook ID to run.
     #[arg(value_name = "HOOK")]
@@ -249,23 +344,126 @@ pub(crate) struct RunArgs {
     /// Run on all files in the repo.
     #[arg(short, long, conflicts_with_all = ["files", "from_ref", "to_ref"])]
     pub(crate) all_files: bool,
+    /// With `--all-files`, also run on untracked files that are not ignored.
+    #[arg(long, requires = "all_files")]
+    pub(crate) include_untracked: bool,
     /// Specific filenames to run hooks on.
+    ///
+    /// `@<path>` reads the filenames from a file, one per line.
     #[arg(long, conflicts_with_all = ["all_files", "from_ref", "to_ref"])]
     pub(crate) files: Vec<PathBuf>,
+    /// Specific filenames to run hooks on, given after `--`, e.g. `pre-commit run <HOOK> -- <FILES>...`.
+    ///
+    /// Everything after `--` is a filename, even if it looks like a hook id or an option.
+    #[arg(
+        last = true,
+        value_name = "FILES",
+        conflicts_with_all = ["all_files", "from_ref", "to_ref"]
+    )]
+    pub(crate) trailing_files: Vec<PathBuf>,
     /// The original ref in a `from_ref...to_ref` diff expression.
     /// Files changed in this diff will be run through the hooks.
+    ///
+    /// `--source` is accepted as a deprecated alias, for compatibility with older pre-commit.
     #[arg(short = 's', long, alias = "source", requires = "to_ref")]
     pub(crate) from_ref: Option<String>,
     /// The destination ref in a `from_ref...to_ref` diff expression.
     /// Files changed in this diff will be run through the hooks.
+    ///
+    /// `--origin` is accepted as a deprecated alias, for compatibility with older pre-commit.
     #[arg(short = 'o', long, alias = "origin", requires = "from_ref")]
     pub(crate) to_ref: Option<String>,
+    /// How the files changed between `--from-ref` and `--to-ref` are selected.
+    #[arg(long, value_enum, default_value_t = RefRange::ThreeDot, requires = "from_ref")]
+    pub(crate) ref_range: RefRange,
     /// The stage during which the hook is fired.
+    /// Defaults to `pre-commit`, or the stage implied by the git hook arguments, e.g. `commit-msg`
+    /// when `--commit-msg-filename` is given.
+    ///
+    /// Can be repeated to run the hooks of several stages at once, each hook runs at most once.
+    /// The files to check are selected for the first stage.
     #[arg(long)]
-    pub(crate) hook_stage: Option<Stage>,
+    pub(crate) hook_stage: Vec<Stage>,
     /// When hooks fail, run `git diff` directly afterward.
-    #[arg(long)]
+    /// Enabled by default in CI.
+    #[arg(long, overrides_with = "no_show_diff_on_failure")]
     pub(crate) show_diff_on_failure: bool,
+    /// Don't run `git diff` when hooks fail, even in CI.
+    #[arg(long, overrides_with = "show_diff_on_failure")]
+    pub(crate) no_show_diff_on_failure: bool,
+    /// Which changes to show with `--show-diff-on-failure`.
+    #[arg(long, value_enum, default_value_t = DiffScope::All)]
+    pub(crate) diff_scope: DiffScope,
+    /// When hooks fail, leave the working tree as the hooks left it instead of re-applying
+    /// the non-staged changes.
+    ///
+    /// The non-staged changes are kept in a patch file, which is printed. To restore them,
+    /// run `git checkout -- .` to discard the hook modifications, then `git apply <patch>`.
+    /// By default the changes are re-applied, if that conflicts with the hook modifications,
+    /// the modifications are discarded.
+    #[arg(long)]
+    pub(crate) no_restore_on_failure: bool,
+    /// The format of hook results.
+    /// Defaults to `github` when running in GitHub Actions, `text` otherwise.
+    #[arg(long, value_enum)]
+    pub(crate) output_format: Option<OutputFormat>,
+    /// The maximum number of output lines to show for each hook, `0` means no limit.
+    #[arg(long, value_name = "LINES", default_value_t = DEFAULT_MAX_OUTPUT_LINES)]
+    pub(crate) max_output_lines: usize,
+    /// Load the configuration file as it existed at the given git rev,
+    /// instead of the working tree version.
+    #[arg(long, value_name = "REV")]
+    pub(crate) config_rev: Option<String>,
+    /// The maximum number of hook processes to run concurrently.
+    /// Defaults to `PRE_COMMIT_JOBS`, then `jobs` of the config, then the number of CPUs.
+    ///
+    /// A value of 0 is treated as 1.
+    #[arg(short, long, value_name = "N")]
+    pub(crate) jobs: Option<usize>,
+    /// The maximum number of repos to clone or install concurrently.
+    /// Defaults to the number of CPUs, capped at 8 since these operations are mostly network-bound.
+    ///
+    /// Repos are prepared before any hook runs, so this does not share a budget with `--jobs`.
+    #[arg(long, value_name = "N")]
+    pub(crate) parallel_repos: Option<NonZeroUsize>,
+    /// The maximum number of files passed to each invocation of a hook.
+    /// Defaults to as many as fit in the command line, spread over `--jobs`.
+    ///
+    /// Smaller batches help memory-heavy hooks, larger ones help hooks that are slow to start.
+    /// Batches are still split to fit in the command line.
+    #[arg(long, value_name = "N")]
+    pub(crate) batch_size: Option<NonZeroUsize>,
+    /// Re-run the hooks whenever the tracked files change, until interrupted.
+    ///
+    /// Hook environments are reused between runs, and the screen is cleared before each re-run.
+    #[arg(long, conflicts_with_all = ["from_ref", "to_ref"])]
+    pub(crate) watch: bool,
+    /// Run as in CI, skipping the hooks listed in `ci.skip` of the config, like pre-commit.ci.
+    #[arg(long, env = "PRE_COMMIT_CI", value_parser = clap::builder::FalseyValueParser::new())]
+    pub(crate) ci: bool,
+    /// Run every hook, instead of reusing the results of previous passing runs.
+    ///
+    /// A hook passed on the same files with the same content and configuration is reported as
+    /// `Passed (cached)` without running it again. Only hooks which are passed filenames and
+    /// don't set `always_run` or `verbose` are cached.
+    #[arg(long)]
+    pub(crate) no_cache: bool,
+    /// Run all hooks even if one fails, ignoring `fail_fast` of the config and of the hooks.
+    ///
+    /// All hooks run by default, so this only matters when `fail_fast` is set.
+    /// Either way, the exit code is non-zero if any hook failed.
+    #[arg(long)]
+    pub(crate) keep_going: bool,
+    /// Print the environment directory of a hook and exit, without running any hook.
+    ///
+    /// Remote repos are cloned if needed, but the environment is not installed.
+    #[arg(long, value_name = "HOOK", conflicts_with_all = ["hook_id", "watch"])]
+    pub(crate) print_env_path: Option<String>,
+    /// Print the hooks configured for each stage and exit, without running any hook.
+    ///
+    /// Hooks without `stages` are listed under every stage, unless `default_stages` is set.
+    #[arg(long, conflicts_with_all = ["hook_id", "watch", "print_env_path"])]
+    pub(crate) list_stages: bool,
 
     #[command(flatten)]
     pub(crate) extra: RunExtraArgs,
@@ -276,6 +474,12 @@ pub(crate) struct ValidateConfigArgs {
     /// The path to the configuration file.
     #[arg(value_name = "CONFIG")]
     pub(crate) configs: Vec<PathBuf>,
+    /// Check that the `rev` of each remote repo exists, using `git ls-remote`.
+    #[arg(long)]
+    pub(crate) check_revs: bool,
+    /// Treat a hook id listed more than once in a repo as an error, rather than a warning.
+    #[arg(long)]
+    pub(crate) strict: bool,
 }
 
 #[derive(Debug, Args)]
@@ -287,14 +491,21 @@ pub(crate) struct ValidateManifestArgs {
 
 #[derive(Debug, Args)]
 pub(crate) struct AutoUpdateArgs {
-    #[arg(long, default_value_t = true)]
+    /// Update to the latest commit of the default branch, rather than the latest tag.
+    #[arg(long)]
     pub(crate) bleeding_edge: bool,
+    /// Store the commit hash in `rev`, with the tag in a `# frozen:` comment.
     #[arg(long)]
     pub(crate) freeze: bool,
+    /// Only update this repo, can be given multiple times.
     #[arg(long)]
-    pub(crate) repo: Option<String>,
+    pub(crate) repo: Vec<String>,
+    /// Number of repos to update concurrently.
     #[arg(short, long, default_value_t = 1)]
     pub(crate) jobs: usize,
+    /// Print the rev changes without updating the configuration file.
+    #[arg(long)]
+    pub(crate) dry_run: bool,
 }
 
 #[derive(Debug, Args)]
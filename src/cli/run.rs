@@ -1,41 +1,78 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Write;
+use std::io::IsTerminal;
 use std::path::{Path, PathBuf};
 
 use anyhow::Result;
-use futures::stream::FuturesUnordered;
+use clap::ValueEnum;
 use futures::StreamExt;
 use itertools::Itertools;
 use owo_colors::OwoColorize;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
-use tracing::{debug, trace};
+use tracing::{debug, trace, warn};
 
-use crate::cli::{ExitStatus, RunExtraArgs};
-use crate::config::Stage;
-use crate::fs::{normalize_path, Simplified};
+use crate::cleanup::add_cleanup;
+use crate::cli::install::missing_hook_scripts;
+use crate::cli::{flag, ExitStatus, OutputFormat, RefRange, RunArgs, RunExtraArgs};
+use crate::config::{resolve_hook_types, ConfigWire, HookType, Stage};
+use crate::fs::{normalize_path, LockedFile, Simplified};
 use crate::git;
-use crate::hook::{Hook, Project};
+use crate::hook::{Hook, Project, INSTALL_STATE_FILE};
 use crate::printer::Printer;
-use crate::run::{run_hooks, FilenameFilter, WorkTreeKeeper};
-use crate::store::Store;
+use crate::run::{run_hooks, Concurrency, FilenameFilter, WorkTreeKeeper};
+use crate::store::{report_cache, Store};
+use crate::warn_user;
 
-#[allow(clippy::too_many_arguments)]
 pub(crate) async fn run(
     config: Option<PathBuf>,
-    hook_id: Option<String>,
-    hook_stage: Option<Stage>,
-    from_ref: Option<String>,
-    to_ref: Option<String>,
-    all_files: bool,
-    files: Vec<PathBuf>,
-    show_diff_on_failure: bool,
-    extra_args: RunExtraArgs,
+    args: RunArgs,
     verbose: bool,
     printer: Printer,
 ) -> Result<ExitStatus> {
+    let show_diff_on_failure = flag(args.show_diff_on_failure, args.no_show_diff_on_failure)
+        .unwrap_or_else(crate::ci::is_ci);
+    let output_format = args.output_format.unwrap_or_else(|| {
+        if crate::ci::provider() == Some(crate::ci::Provider::GitHubActions) {
+            OutputFormat::Github
+        } else {
+            OutputFormat::Text
+        }
+    });
+    let RunArgs {
+        hook_id,
+        hook_stage: hook_stages,
+        from_ref,
+        to_ref,
+        ref_range,
+        all_files,
+        include_untracked,
+        files,
+        diff_scope,
+        no_restore_on_failure,
+        max_output_lines,
+        config_rev,
+        jobs,
+        parallel_repos,
+        batch_size,
+        watch,
+        ci,
+        no_cache,
+        keep_going,
+        extra: extra_args,
+        ..
+    } = args;
+
+    let stages = if hook_stages.is_empty() {
+        vec![detect_stage(&extra_args)]
+    } else {
+        hook_stages.clone()
+    };
+    debug!(?stages, "Running hooks for stages");
+    // Files and env vars are selected for the first stage.
+    let stage = stages[0];
+
     // Prevent recursive post-checkout hooks.
-    if matches!(hook_stage, Some(Stage::PostCheckout))
-        && std::env::var_os("_PRE_COMMIT_SKIP_POST_CHECKOUT").is_some()
+    if stage == Stage::PostCheckout && std::env::var_os("_PRE_COMMIT_SKIP_POST_CHECKOUT").is_some()
     {
         return Ok(ExitStatus::Success);
     }
@@ -43,130 +80,295 @@ pub(crate) async fn run(
     let should_stash = !all_files && files.is_empty();
 
     // Check if we have unresolved merge conflict files and fail fast.
-    if should_stash && git::has_unmerged_paths().await? {
-        writeln!(
-            printer.stderr(),
-            "You have unmerged paths. Resolve them before running pre-commit."
-        )?;
-        return Ok(ExitStatus::Failure);
+    if should_stash {
+        let conflicted = git::get_conflicted_files().await?;
+        if !conflicted.is_empty() {
+            writeln!(
+                printer.stderr(),
+                "You have unmerged paths. Resolve them before running pre-commit."
+            )?;
+            for file in &conflicted {
+                writeln!(printer.stderr(), "  {}", file.cyan())?;
+            }
+            return Ok(ExitStatus::Failure);
+        }
     }
 
-    let config_file = Project::find_config_file(config)?;
-    if should_stash && config_not_staged(&config_file).await? {
-        writeln!(
-            printer.stderr(),
-            "Your pre-commit configuration is unstaged.\n`git add {}` to fix this.",
-            &config_file.user_display()
-        )?;
-        return Ok(ExitStatus::Failure);
-    }
+    let mut project = if let Some(rev) = config_rev {
+        Project::from_rev(config, &rev).await?
+    } else {
+        let config_file = Project::find_config_file(config)?;
+        if should_stash && config_not_staged(&config_file).await? {
+            writeln!(
+                printer.stderr(),
+                "Your pre-commit configuration is unstaged.\n`git add {}` to fix this.",
+                &config_file.user_display()
+            )?;
+            return Ok(ExitStatus::Failure);
+        }
+
+        warn_config_drift(&config_file).await?;
+
+        let project = Project::new(config_file)?;
+        // Only nudge interactive users, CI usually runs `pre-commit run` without installing.
+        if std::io::stderr().is_terminal() {
+            warn_missing_hook_scripts(&project).await?;
+        }
+        project
+    };
+
+    Concurrency::new(jobs, project.config().jobs, parallel_repos, batch_size).init();
 
     // Set env vars for hooks.
-    let env_vars = fill_envs(from_ref.as_ref(), to_ref.as_ref(), &extra_args);
+    let env_vars = fill_envs(stage, from_ref.as_ref(), to_ref.as_ref(), &extra_args);
 
-    let mut project = Project::new(config_file)?;
     let store = Store::from_settings()?.init()?;
+    // Tags of unchanged files are reused across runs, see `identify::tags_from_path_cached`.
+    let root = std::env::current_dir()?;
+    store.load_file_tags(&root)?;
+
+    loop {
+        let status = 'run: {
+            let lock = store.lock_async().await?;
+            let hooks = project.init_hooks(&store, printer).await?;
+
+            let hooks: Vec<_> = hooks
+                .into_iter()
+                .filter(|h| {
+                    if let Some(ref hook) = hook_id {
+                        &h.id == hook || &h.alias == hook
+                    } else {
+                        true
+                    }
+                })
+                // `--verbose` applies to every hook, so languages can report in more detail.
+                .map(|mut h| {
+                    h.verbose |= verbose;
+                    h.fail_fast &= !keep_going;
+                    h
+                })
+                .collect();
+
+            // An empty config is valid, there is just nothing to do.
+            if hooks.is_empty() && hook_id.is_none() {
+                writeln!(printer.stderr(), "No hooks to run")?;
+                break 'run ExitStatus::Success;
+            }
 
-    let lock = store.lock_async().await?;
-    let hooks = project.init_hooks(&store, printer).await?;
+            // Hooks of other stages are kept, to be reported in verbose mode.
+            let in_stage = |h: &Hook| stages.iter().any(|stage| h.stages.contains(stage));
+            if !hooks.iter().any(in_stage) && hook_id.is_some() {
+                if hook_stages.is_empty() {
+                    writeln!(
+                        printer.stderr(),
+                        "No hook found for id `{}`",
+                        hook_id.as_ref().unwrap().cyan()
+                    )?;
+                } else {
+                    writeln!(
+                        printer.stderr(),
+                        "No hook found for id `{}` and stage `{}`",
+                        hook_id.as_ref().unwrap().cyan(),
+                        stages.iter().map(|stage| stage.cyan()).join("`, `")
+                    )?;
+                }
+                break 'run ExitStatus::Failure;
+            }
 
-    let hooks: Vec<_> = hooks
-        .into_iter()
-        .filter(|h| {
-            if let Some(ref hook) = hook_id {
-                &h.id == hook || &h.alias == hook
-            } else {
-                true
+            let mut skips = get_skips();
+            if ci {
+                if let Some(ref ci) = project.config().ci {
+                    skips.extend(ci.skip.iter().cloned());
+                }
             }
-        })
-        .filter(|h| {
-            if let Some(stage) = hook_stage {
-                h.stages.contains(&stage)
-            } else {
-                true
+            let to_run = hooks
+                .iter()
+                .filter(|&h| in_stage(h))
+                .filter(|h| !skips.contains(&h.id) && !skips.contains(&h.alias))
+                .cloned()
+                .collect::<Vec<_>>();
+
+            if to_run.is_empty() && hook_stages.is_empty() {
+                warn_other_hook_types(project.config(), stage);
             }
-        })
-        .collect();
 
-    if hooks.is_empty() && hook_id.is_some() {
-        if let Some(hook_stage) = hook_stage {
-            writeln!(
-                printer.stderr(),
-                "No hook found for id `{}` and stage `{}`",
-                hook_id.unwrap().cyan(),
-                hook_stage.cyan()
-            )?;
-        } else {
-            writeln!(
-                printer.stderr(),
-                "No hook found for id `{}`",
-                hook_id.unwrap().cyan()
+            debug!(
+                "Hooks going to run: {:?}",
+                to_run.iter().map(|h| &h.id).collect::<Vec<_>>()
+            );
+            // Environments are locked one by one, so other processes can install different ones.
+            drop(lock);
+            install_hooks(&to_run, printer).await?;
+
+            // Clear any unstaged changes from the git working directory.
+            let mut guard = None;
+            if should_stash {
+                guard = Some(WorkTreeKeeper::clean(&store).await?);
+            }
+
+            // Without stashing, the non-staged changes stay in the working tree, so it's
+            // snapshotted to only show the changes made by hooks on failure.
+            let baseline = match &guard {
+                Some(guard) => Some(guard.tree().to_string()),
+                None if show_diff_on_failure => Some(git::write_working_tree().await?),
+                None => None,
+            };
+
+            let mut filenames = all_filenames(
+                stage,
+                from_ref.clone(),
+                to_ref.clone(),
+                ref_range,
+                all_files,
+                include_untracked,
+                files.clone(),
+                extra_args.commit_msg_filename.as_ref(),
+            )
+            .await?;
+            for filename in &mut filenames {
+                normalize_path(filename);
+            }
+
+            // Submodules are not files, skip them unless some hook selects directories.
+            if !to_run.iter().any(Hook::selects_directories) {
+                let submodules: HashSet<_> =
+                    git::get_submodule_paths().await?.into_iter().collect();
+                if !submodules.is_empty() {
+                    filenames.retain(|filename| !submodules.contains(filename));
+                }
+            }
+
+            let filter = FilenameFilter::new(
+                project.config().files.as_deref(),
+                project.config().exclude.as_deref(),
             )?;
+            let filenames = filenames
+                .into_par_iter()
+                .filter(|filename| filter.filter(filename))
+                .filter(|filename| {
+                    // Ignore not existing files, symlinks and directories (submodules) are kept
+                    // so hooks can select them by type.
+                    std::fs::symlink_metadata(filename).is_ok()
+                })
+                .collect::<Vec<_>>();
+
+            trace!("Files after filtered: {}", filenames.len());
+
+            let status = run_hooks(
+                &hooks,
+                &stages,
+                &skips,
+                filenames,
+                env_vars.clone(),
+                project.config().fail_fast.unwrap_or(false) && !keep_going,
+                show_diff_on_failure,
+                diff_scope,
+                baseline.as_deref(),
+                output_format,
+                max_output_lines,
+                // Runs on all files take long, show how much each hook processed.
+                all_files,
+                (!no_cache).then_some(&store),
+                verbose,
+                printer,
+            )
+            .await?;
+
+            // The cache is only an optimization, don't fail the run.
+            let lock = store.lock_async().await?;
+            if let Err(err) = store.save_file_tags(&root) {
+                warn!(%err, "Failed to save file tags");
+            }
+            drop(lock);
+
+            if no_restore_on_failure && matches!(status, ExitStatus::Failure) {
+                if let Some(guard) = guard {
+                    guard.keep_working_tree();
+                }
+            }
+
+            status
+        };
+
+        if !watch {
+            return Ok(status);
         }
-        return Ok(ExitStatus::Failure);
+        crate::watch::wait_for_changes().await?;
+        // Clear the screen, the escape codes are stripped if color is disabled.
+        write!(printer.stdout(), "\x1b[2J\x1b[H")?;
     }
+}
 
-    let skips = get_skips();
-    let to_run = hooks
+/// Print the environment directory of a hook, without installing or running it.
+pub(crate) async fn print_env_path(
+    config: Option<PathBuf>,
+    hook_id: &str,
+    printer: Printer,
+) -> Result<ExitStatus> {
+    let mut project = Project::new(Project::find_config_file(config)?)?;
+    let store = Store::from_settings()?.init()?;
+
+    let hooks = {
+        let _lock = store.lock_async().await?;
+        // Keep stdout for the path only, so it can be captured by scripts.
+        project.init_hooks(&store, Printer::Quiet).await?
+    };
+
+    let Some(hook) = hooks
         .iter()
-        .filter(|h| !skips.contains(&h.id) && !skips.contains(&h.alias))
-        .cloned()
-        .collect::<Vec<_>>();
+        .find(|hook| hook.id == hook_id || hook.alias == hook_id)
+    else {
+        writeln!(
+            printer.stderr(),
+            "No hook found for id `{}`",
+            hook_id.cyan()
+        )?;
+        return Ok(ExitStatus::Failure);
+    };
 
-    debug!(
-        "Hooks going to run: {:?}",
-        to_run.iter().map(|h| &h.id).collect::<Vec<_>>()
-    );
-    install_hooks(&to_run, printer).await?;
-    drop(lock);
+    let Some(env_dir) = hook.environment_dir() else {
+        writeln!(
+            printer.stderr(),
+            "Hook `{}` uses language `{}`, which has no environment",
+            hook_id.cyan(),
+            hook.language.name()
+        )?;
+        return Ok(ExitStatus::Failure);
+    };
 
-    // Clear any unstaged changes from the git working directory.
-    let mut _guard = None;
-    if should_stash {
-        _guard = Some(WorkTreeKeeper::clean(&store).await?);
+    if !hook.installed() {
+        warn_user!("Hook `{hook_id}` is not installed yet, run it once to install it");
     }
+    writeln!(printer.stdout(), "{}", env_dir.display())?;
 
-    let mut filenames = all_filenames(
-        hook_stage,
-        from_ref,
-        to_ref,
-        all_files,
-        files,
-        extra_args.commit_msg_filename.as_ref(),
-    )
-    .await?;
-    for filename in &mut filenames {
-        normalize_path(filename);
+    Ok(ExitStatus::Success)
+}
+
+/// Print the hooks configured for each stage, without installing or running them.
+pub(crate) async fn list_stages(config: Option<PathBuf>, printer: Printer) -> Result<ExitStatus> {
+    let mut project = Project::new(Project::find_config_file(config)?)?;
+    let store = Store::from_settings()?.init()?;
+
+    let hooks = {
+        let _lock = store.lock_async().await?;
+        project.init_hooks(&store, Printer::Quiet).await?
+    };
+
+    for stage in Stage::value_variants() {
+        let mut in_stage = hooks
+            .iter()
+            .filter(|hook| hook.stages.contains(stage))
+            .peekable();
+        if in_stage.peek().is_none() {
+            continue;
+        }
+        writeln!(printer.stdout(), "{}:", stage.cyan())?;
+        for hook in in_stage {
+            writeln!(printer.stdout(), "  {}", hook.id)?;
+        }
     }
 
-    let filter = FilenameFilter::new(
-        project.config().files.as_deref(),
-        project.config().exclude.as_deref(),
-    )?;
-    let filenames = filenames
-        .into_par_iter()
-        .filter(|filename| filter.filter(filename))
-        .filter(|filename| {
-            // Ignore not existing files.
-            std::fs::symlink_metadata(filename)
-                .map(|m| m.file_type().is_file())
-                .unwrap_or(false)
-        })
-        .collect::<Vec<_>>();
-
-    trace!("Files after filtered: {}", filenames.len());
-
-    run_hooks(
-        &hooks,
-        &skips,
-        filenames,
-        env_vars,
-        project.config().fail_fast.unwrap_or(false),
-        show_diff_on_failure,
-        verbose,
-        printer,
-    )
-    .await
+    Ok(ExitStatus::Success)
 }
 
 async fn config_not_staged(config: &Path) -> Result<bool> {
@@ -184,7 +386,52 @@ async fn config_not_staged(config: &Path) -> Result<bool> {
     Ok(!status.success())
 }
 
+/// Warn if the config file is not committed, hooks may behave differently on other machines.
+async fn warn_config_drift(config: &Path) -> Result<()> {
+    if !git::is_tracked(config).await? {
+        warn_user!(
+            "The configuration file `{}` is not tracked by git",
+            config.user_display()
+        );
+    } else if git::has_uncommitted_changes(config).await? {
+        warn_user!(
+            "The configuration file `{}` has uncommitted changes",
+            config.user_display()
+        );
+    }
+    Ok(())
+}
+
+/// Warn if the hook scripts are not installed, so hooks won't run on `git commit`.
+async fn warn_missing_hook_scripts(project: &Project) -> Result<()> {
+    for hook_type in missing_hook_scripts(project).await? {
+        warn_user!(
+            "The `{}` hook is not installed, run `pre-commit install` to run hooks automatically",
+            hook_type.as_str()
+        );
+    }
+    Ok(())
+}
+
+/// Warn if nothing runs for the default stage, but hooks are installed for other hook types,
+/// the user probably wants to run one of those stages.
+fn warn_other_hook_types(config: &ConfigWire, stage: Stage) {
+    let hook_types = resolve_hook_types(Some(config), vec![]);
+    if hook_types
+        .iter()
+        .any(|&hook_type| Stage::from(hook_type) == stage)
+    {
+        return;
+    }
+    warn_user!(
+        "No hooks to run for stage `{}`, hooks are installed for `{}`, select a stage with `--hook-stage`",
+        stage.as_str(),
+        hook_types.iter().map(HookType::as_str).join("`, `")
+    );
+}
+
 fn fill_envs(
+    stage: Stage,
     from_ref: Option<&String>,
     to_ref: Option<&String>,
     args: &RunExtraArgs,
@@ -227,16 +474,52 @@ fn fill_envs(
     if let Some(ref checkout) = args.checkout_type {
         env.insert("PRE_COMMIT_CHECKOUT_TYPE", checkout.clone());
     }
-    if args.is_squash_merge {
-        env.insert("PRE_COMMIT_SQUASH_MERGE", "1".into());
+    if stage == Stage::PostMerge {
+        let squash = if args.is_squash_merge { "1" } else { "0" };
+        env.insert("PRE_COMMIT_IS_SQUASH_MERGE", squash.into());
     }
     if let Some(ref command) = args.rewrite_command {
         env.insert("PRE_COMMIT_REWRITE_COMMAND", command.clone());
     }
 
+    // The hook output is captured, so most tools only colorize it if forced to.
+    match anstream::ColorChoice::global() {
+        anstream::ColorChoice::Always | anstream::ColorChoice::AlwaysAnsi => {
+            env.insert("FORCE_COLOR", "1".into());
+            env.insert("CLICOLOR_FORCE", "1".into());
+            env.insert("PY_COLORS", "1".into());
+        }
+        anstream::ColorChoice::Never => {
+            env.insert("NO_COLOR", "1".into());
+        }
+        anstream::ColorChoice::Auto => {}
+    }
+
     env
 }
 
+/// Detect the stage from the git hook arguments, so `hook-impl` and manual runs with the same
+/// arguments behave the same. Defaults to `pre-commit`.
+fn detect_stage(args: &RunExtraArgs) -> Stage {
+    if args.prepare_commit_message_source.is_some() || args.commit_object_name.is_some() {
+        Stage::PrepareCommitMsg
+    } else if args.commit_msg_filename.is_some() {
+        Stage::CommitMsg
+    } else if args.remote_name.is_some() || args.remote_url.is_some() {
+        Stage::PrePush
+    } else if args.checkout_type.is_some() {
+        Stage::PostCheckout
+    } else if args.rewrite_command.is_some() {
+        Stage::PostRewrite
+    } else if args.pre_rebase_upstream.is_some() {
+        Stage::PreRebase
+    } else if args.is_squash_merge {
+        Stage::PostMerge
+    } else {
+        Stage::PreCommit
+    }
+}
+
 fn get_skips() -> Vec<String> {
     match std::env::var_os("SKIP") {
         Some(s) if !s.is_empty() => s
@@ -252,44 +535,66 @@ fn get_skips() -> Vec<String> {
 /// Get all filenames to run hooks on.
 #[allow(clippy::too_many_arguments)]
 async fn all_filenames(
-    hook_stage: Option<Stage>,
+    hook_stage: Stage,
     from_ref: Option<String>,
     to_ref: Option<String>,
+    ref_range: RefRange,
     all_files: bool,
+    include_untracked: bool,
     files: Vec<PathBuf>,
     commit_msg_filename: Option<&PathBuf>,
 ) -> Result<Vec<String>> {
-    if hook_stage.is_some_and(|stage| !stage.operate_on_files()) {
+    if !hook_stage.operate_on_files() {
         return Ok(vec![]);
     }
-    if hook_stage.is_some_and(|stage| matches!(stage, Stage::PrepareCommitMsg | Stage::CommitMsg)) {
+    if matches!(hook_stage, Stage::PrepareCommitMsg | Stage::CommitMsg) {
         return Ok(vec![commit_msg_filename
             .unwrap()
             .to_string_lossy()
             .to_string()]);
     }
     if let (Some(from_ref), Some(to_ref)) = (from_ref, to_ref) {
-        let files = git::get_changed_files(&from_ref, &to_ref).await?;
+        // Files changed on the base branch since the branches diverged are not wanted, e.g. in PRs.
+        let base = match ref_range {
+            RefRange::ThreeDot => git::merge_base(&from_ref, &to_ref).await?,
+            RefRange::TwoDot => from_ref.clone(),
+        };
+        let files = git::get_changed_files(&base, &to_ref).await?;
         debug!(
-            "Files changed between {} and {}: {}",
+            "Files changed between {} and {} ({:?}): {}",
             from_ref,
             to_ref,
+            ref_range,
             files.len()
         );
         return Ok(files);
     }
 
     if !files.is_empty() {
-        let files: Vec<_> = files
-            .into_iter()
-            .map(|f| f.to_string_lossy().to_string())
-            .collect();
-        debug!("Files passed as arguments: {}", files.len());
-        return Ok(files);
+        // Directories are expanded to the tracked files under them.
+        let mut expanded = Vec::with_capacity(files.len());
+        for file in files {
+            if file.is_dir() {
+                expanded.extend(git::ls_files_under(&file).await?);
+            } else {
+                expanded.push(file.to_string_lossy().to_string());
+            }
+        }
+        // Make the file list canonical, so the same files always run the same way.
+        expanded.sort_unstable();
+        expanded.dedup();
+        debug!("Files passed as arguments: {}", expanded.len());
+        return Ok(expanded);
     }
     if all_files {
-        let files = git::get_all_files().await?;
+        let mut files = git::get_all_files().await?;
         debug!("All files in the repo: {}", files.len());
+        if include_untracked {
+            let untracked = git::get_untracked_files().await?;
+            debug!("Untracked files: {}", untracked.len());
+            files.extend(untracked);
+            files.sort_unstable();
+        }
         return Ok(files);
     }
     // TODO: implement merge conflict
@@ -302,6 +607,17 @@ async fn all_filenames(
 }
 
 async fn install_hook(hook: &Hook, env_dir: PathBuf, printer: Printer) -> Result<()> {
+    let _lock = LockedFile::acquire(
+        env_lock_path(&env_dir),
+        format!("environment {}", env_dir.user_display()),
+    )
+    .await?;
+    // Another process may have installed it while we were waiting for the lock.
+    if hook.installed() {
+        debug!(%hook, "Environment installed by another process");
+        return Ok(());
+    }
+
     writeln!(
         printer.stdout(),
         "Installing environment for {}",
@@ -317,25 +633,64 @@ async fn install_hook(hook: &Hook, env_dir: PathBuf, printer: Printer) -> Result
         fs_err::remove_dir_all(&env_dir)?;
     }
 
-    hook.language.install(hook).await?;
+    // Environments can't be installed to a temporary directory and renamed into place like repos,
+    // because virtualenvs embed their absolute path. Instead, the environment is only marked
+    // installed on success, and partial environments are removed on failure or interruption.
+    let partial = env_dir.clone();
+    add_cleanup(move || {
+        if !partial.join(INSTALL_STATE_FILE).exists() {
+            let _ = fs_err::remove_dir_all(&partial);
+        }
+    });
+    if let Err(err) = hook.language.install(hook).await {
+        // Keep the partial environment with `--verbose`, to inspect what went wrong.
+        if printer == Printer::Verbose {
+            return Err(err.context(format!(
+                "Failed to install environment for {}, the partial environment is kept at `{}`",
+                hook.repo(),
+                env_dir.user_display()
+            )));
+        }
+        if env_dir.try_exists()? {
+            fs_err::remove_dir_all(&env_dir)?;
+        }
+        return Err(err.context(format!("Failed to install environment for {}", hook.repo())));
+    }
     hook.mark_installed()?;
 
     Ok(())
 }
 
+/// The lock file of an environment, next to its directory so it's removed with the repo.
+fn env_lock_path(env_dir: &Path) -> PathBuf {
+    let mut path = env_dir.as_os_str().to_owned();
+    path.push(".lock");
+    PathBuf::from(path)
+}
+
 // TODO: progress bar
 pub async fn install_hooks(hooks: &[Hook], printer: Printer) -> Result<()> {
-    let to_install = hooks
-        .iter()
-        .filter(|&hook| !hook.installed())
-        .unique_by(|&hook| hook.install_key());
-
-    let mut tasks = FuturesUnordered::new();
-    for hook in to_install {
-        if let Some(env_dir) = hook.environment_dir() {
-            tasks.push(async move { install_hook(hook, env_dir, printer).await });
+    let mut to_install = Vec::new();
+    for hook in hooks.iter().unique_by(|&hook| hook.install_key()) {
+        let Some(env_dir) = hook.environment_dir() else {
+            continue;
+        };
+        let installed = hook.installed();
+        report_cache(
+            printer,
+            installed,
+            "environment",
+            &format!("{} for {}", hook.language.name(), hook.repo()),
+        )?;
+        if !installed {
+            to_install.push((hook, env_dir));
         }
     }
+
+    let tasks = to_install
+        .into_iter()
+        .map(|(hook, env_dir)| async move { install_hook(hook, env_dir, printer).await });
+    let mut tasks = futures::stream::iter(tasks).buffer_unordered(Concurrency::get().repos);
     while let Some(result) = tasks.next().await {
         result?;
     }
@@ -3,27 +3,140 @@ use std::iter;
 use std::path::PathBuf;
 
 use anstream::eprintln;
+use futures::stream::FuturesUnordered;
+use futures::StreamExt;
+use itertools::Itertools;
 use owo_colors::OwoColorize;
 
 use crate::cli::ExitStatus;
-use crate::config::{read_config, read_manifest};
+use crate::config::{
+    read_config_with_includes, read_manifest, ConfigRemoteRepo, ConfigRepo, ConfigWire,
+};
+use crate::fs::Simplified;
+use crate::git;
+use crate::warn_user;
 
-pub(crate) fn validate_configs(configs: Vec<PathBuf>) -> ExitStatus {
+pub(crate) async fn validate_configs(
+    configs: Vec<PathBuf>,
+    check_revs: bool,
+    strict: bool,
+) -> ExitStatus {
     let mut status = ExitStatus::Success;
+    let mut remotes = Vec::new();
 
-    for config in configs {
-        if let Err(err) = read_config(&config) {
-            eprintln!("{}: {}", "error".red().bold(), err);
-            for source in iter::successors(err.source(), |&err| err.source()) {
-                eprintln!("  {}: {}", "caused by".red().bold(), source);
+    for path in configs {
+        match read_config_with_includes(&path) {
+            Ok(config) => {
+                for (repo, id) in duplicate_hook_ids(&config) {
+                    let message = format!(
+                        "Hook `{id}` is listed more than once in repo `{repo}` of `{}`",
+                        path.user_display()
+                    );
+                    if strict {
+                        eprintln!("{}: {message}", "error".red().bold());
+                        status = ExitStatus::Failure;
+                    } else {
+                        warn_user!("{message}");
+                    }
+                }
+                remotes.extend(config.repos.into_iter().filter_map(|repo| match repo {
+                    ConfigRepo::Remote(repo) => Some(repo),
+                    _ => None,
+                }));
+            }
+            Err(err) => {
+                eprintln!("{}: {}", "error".red().bold(), err);
+                for source in iter::successors(err.source(), |&err| err.source()) {
+                    eprintln!("  {}: {}", "caused by".red().bold(), source);
+                }
+                status = ExitStatus::Failure;
             }
-            status = ExitStatus::Failure;
         }
     }
 
+    if check_revs && !check_remote_revs(&remotes).await {
+        status = ExitStatus::Failure;
+    }
+
     status
 }
 
+/// The hook ids listed more than once within a single repo, usually a copy-paste mistake.
+/// Upstream allows them, e.g. to run a hook twice with different `args`.
+fn duplicate_hook_ids(config: &ConfigWire) -> Vec<(String, String)> {
+    let mut duplicates = Vec::new();
+    for repo in &config.repos {
+        let (name, ids): (String, Vec<&str>) = match repo {
+            ConfigRepo::Remote(repo) => (
+                repo.repo.to_string(),
+                repo.hooks.iter().map(|hook| hook.id.as_str()).collect(),
+            ),
+            ConfigRepo::Local(repo) => (
+                repo.repo.clone(),
+                repo.hooks.iter().map(|hook| hook.id.as_str()).collect(),
+            ),
+            ConfigRepo::Meta(repo) => (
+                repo.repo.clone(),
+                repo.hooks.iter().map(|hook| hook.id.as_str()).collect(),
+            ),
+        };
+        duplicates.extend(
+            ids.into_iter()
+                .duplicates()
+                .map(|id| (name.clone(), id.to_string())),
+        );
+    }
+    duplicates
+}
+
+/// Check that the revs of all remote repos exist, reporting all bad references at once.
+async fn check_remote_revs(repos: &[ConfigRemoteRepo]) -> bool {
+    let mut tasks = repos
+        .iter()
+        .unique_by(|repo| (repo.repo.as_str(), repo.rev.as_str()))
+        .enumerate()
+        .map(|(idx, repo)| async move {
+            let result = git::ls_remote(repo.repo.as_str(), &repo.rev).await;
+            (idx, repo, result)
+        })
+        .collect::<FuturesUnordered<_>>();
+
+    let mut results = Vec::new();
+    while let Some(result) = tasks.next().await {
+        results.push(result);
+    }
+    // Report in the order of the config.
+    results.sort_unstable_by_key(|(idx, _, _)| *idx);
+
+    let mut valid = true;
+    for (_, repo, result) in results {
+        match result {
+            Ok(true) => {}
+            Ok(false) => {
+                eprintln!(
+                    "{}: Rev `{}` not found in `{}`",
+                    "error".red().bold(),
+                    repo.rev,
+                    repo.repo
+                );
+                valid = false;
+            }
+            Err(err) => {
+                eprintln!(
+                    "{}: Failed to check rev `{}` of `{}`",
+                    "error".red().bold(),
+                    repo.rev,
+                    repo.repo
+                );
+                eprintln!("  {}: {}", "caused by".red().bold(), err);
+                valid = false;
+            }
+        }
+    }
+
+    valid
+}
+
 pub(crate) fn validate_manifest(configs: Vec<PathBuf>) -> ExitStatus {
     let mut status = ExitStatus::Success;
 
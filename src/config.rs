@@ -1,7 +1,7 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Display;
 use std::ops::RangeInclusive;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 use anyhow::Result;
@@ -137,10 +137,13 @@ pub enum Stage {
     PostMerge,
     PostRewrite,
     #[serde(alias = "commit")]
+    #[value(alias = "commit")]
     PreCommit,
     #[serde(alias = "merge-commit")]
+    #[value(alias = "merge-commit")]
     PreMergeCommit,
     #[serde(alias = "push")]
+    #[value(alias = "push")]
     PrePush,
     PreRebase,
     PrepareCommitMsg,
@@ -195,6 +198,7 @@ impl Stage {
                 | Stage::CommitMsg
                 | Stage::PreCommit
                 | Stage::PreMergeCommit
+                | Stage::PostMerge
                 | Stage::PrePush
                 | Stage::PrepareCommitMsg
         )
@@ -218,15 +222,46 @@ pub struct ConfigWire {
     /// Default to all stages.
     pub default_stages: Option<Vec<Stage>>,
     /// Global file include pattern.
+    /// Missing or empty matches all files.
     pub files: Option<String>,
     /// Global file exclude pattern.
+    /// Missing or empty excludes no files.
     pub exclude: Option<String>,
     /// Set to true to have pre-commit stop running hooks after the first failure.
     /// Default is false.
     pub fail_fast: Option<bool>,
+    /// The maximum number of hook processes to run concurrently, overridden by `--jobs` and
+    /// `PRE_COMMIT_JOBS`. Default is the number of CPUs.
+    pub jobs: Option<usize>,
     pub minimum_pre_commit_version: Option<String>,
     /// Configuration for pre-commit.ci service.
-    pub ci: Option<HashMap<String, serde_yaml::Value>>,
+    pub ci: Option<CiConfig>,
+    /// Other configuration files, relative to this one, whose `repos` are merged into this
+    /// configuration, see [`resolve_includes`].
+    pub include: Option<Vec<String>>,
+}
+
+/// The hook types to act on: the given ones, otherwise `default_install_hook_types` of the
+/// config, otherwise `[pre-commit]`.
+pub fn resolve_hook_types(config: Option<&ConfigWire>, hook_types: Vec<HookType>) -> Vec<HookType> {
+    if !hook_types.is_empty() {
+        return hook_types;
+    }
+    config
+        .and_then(|config| config.default_install_hook_types.clone())
+        .filter(|hook_types| !hook_types.is_empty())
+        .unwrap_or_else(|| vec![HookType::PreCommit])
+}
+
+/// Configuration for pre-commit.ci service.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct CiConfig {
+    /// Hook ids to skip on pre-commit.ci, also skipped by `pre-commit run --ci`.
+    #[serde(default)]
+    pub skip: Vec<String>,
+    /// Other settings of the service, e.g. `autofix_prs`, which are not used by pre-commit.
+    #[serde(flatten)]
+    pub other: HashMap<String, serde_yaml::Value>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -320,7 +355,7 @@ pub struct ConfigRemoteHook {
     /// Default is `default`.
     /// See <https://pre-commit.com/#overriding-language-version>.
     pub language_version: Option<String>,
-    /// Write the output of the hook to a file when the hook fails or verbose is enabled.
+    /// Write the output of the hook to a file instead of the terminal, replaced on each run.
     pub log_file: Option<String>,
     /// This hook will execute using a single process instead of in parallel.
     /// Default is false.
@@ -332,6 +367,10 @@ pub struct ConfigRemoteHook {
     /// Print the output of the hook even if it passes.
     /// Default is false.
     pub verbose: Option<bool>,
+    /// The directory to run the hook in, relative to the repository root.
+    /// Filenames passed to the hook are relative to this directory.
+    /// Default is the repository root.
+    pub work_dir: Option<String>,
     pub minimum_pre_commit_version: Option<String>,
 }
 
@@ -345,7 +384,7 @@ pub type ConfigLocalHook = ManifestHook;
 pub enum MetaHookID {
     CheckHooksApply,
     CheckUselessExcludes,
-    Identify,
+    Identity,
 }
 
 impl MetaHookID {
@@ -353,7 +392,7 @@ impl MetaHookID {
         match self {
             MetaHookID::CheckHooksApply => "check-hooks-apply",
             MetaHookID::CheckUselessExcludes => "check-useless-excludes",
-            MetaHookID::Identify => "identify",
+            MetaHookID::Identity => "identity",
         }
     }
 }
@@ -372,6 +411,19 @@ pub struct ConfigMetaHook {
     // only "system" is allowed
     pub language: Option<Language>,
     // TODO: entry is not allowed
+    /// Override the name of the hook.
+    pub name: Option<String>,
+    /// Allows the hook to be referenced using an additional id when using pre-commit run <hookid>
+    pub alias: Option<String>,
+    /// Override the pattern of files to run on.
+    pub files: Option<String>,
+    /// Override the pattern of files to exclude.
+    pub exclude: Option<String>,
+    /// Select which git hook(s) to run for.
+    /// Default is `default_stages`, or all stages.
+    pub stages: Option<Vec<Stage>>,
+    /// Print the output of the hook even if it passes.
+    pub verbose: Option<bool>,
 }
 
 #[derive(Debug, Clone)]
@@ -413,6 +465,8 @@ impl<'de> Deserialize<'de> for ConfigRepo {
         }
 
         let RepoWire { repo, rest } = RepoWire::deserialize(deserializer)?;
+        check_rev(&repo, &rest)
+            .map_err(|e| serde::de::Error::custom(format!("Invalid {repo} repo: {e}")))?;
 
         match repo {
             RepoLocation::Remote(url) => {
@@ -421,6 +475,8 @@ impl<'de> Deserialize<'de> for ConfigRepo {
                     rev: String,
                     hooks: Vec<ConfigRemoteHook>,
                 }
+                check_hook_args(&rest)
+                    .map_err(|e| serde::de::Error::custom(format!("Invalid remote repo: {e}")))?;
                 let RemoteRepo { rev, hooks } = RemoteRepo::deserialize(rest)
                     .map_err(|e| serde::de::Error::custom(format!("Invalid remote repo: {e}")))?;
 
@@ -436,6 +492,8 @@ impl<'de> Deserialize<'de> for ConfigRepo {
                 struct LocalRepo {
                     hooks: Vec<ConfigLocalHook>,
                 }
+                check_hook_args(&rest)
+                    .map_err(|e| serde::de::Error::custom(format!("Invalid local repo: {e}")))?;
                 let LocalRepo { hooks } = LocalRepo::deserialize(rest)
                     .map_err(|e| serde::de::Error::custom(format!("Invalid local repo: {e}")))?;
                 Ok(ConfigRepo::Local(ConfigLocalRepo {
@@ -460,6 +518,47 @@ impl<'de> Deserialize<'de> for ConfigRepo {
     }
 }
 
+/// Remote repos are pinned to a `rev`, `local` and `meta` hooks are not versioned,
+/// so a `rev` there is a mistake rather than something to ignore.
+fn check_rev(repo: &RepoLocation, rest: &serde_yaml::Value) -> Result<(), String> {
+    let has_rev = rest.get("rev").is_some();
+    match repo {
+        RepoLocation::Remote(url) if !has_rev => Err(format!("`repo: {url}` must set `rev`")),
+        RepoLocation::Local | RepoLocation::Meta if has_rev => {
+            Err(format!("`repo: {repo}` must not set `rev`"))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Reject `args` written as a single string, which is a common mistake.
+/// The generic serde error doesn't tell which hook is wrong or how to fix it.
+fn check_hook_args(repo: &serde_yaml::Value) -> Result<(), String> {
+    let Some(hooks) = repo.get("hooks").and_then(serde_yaml::Value::as_sequence) else {
+        return Ok(());
+    };
+    for hook in hooks {
+        let Some(args) = hook.get("args").and_then(serde_yaml::Value::as_str) else {
+            continue;
+        };
+        let id = hook
+            .get("id")
+            .and_then(serde_yaml::Value::as_str)
+            .unwrap_or_default();
+        let suggestion = shlex::split(args)
+            .unwrap_or_else(|| vec![args.to_string()])
+            .iter()
+            .map(|arg| format!("{arg:?}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        return Err(format!(
+            "hook `{id}`: expected a list of strings for `args`, found a string; \
+             wrap each arg separately, e.g. `args: [{suggestion}]`"
+        ));
+    }
+    Ok(())
+}
+
 impl ConfigRepo {
     pub fn hook_ids(&self) -> Vec<&str> {
         match self {
@@ -468,6 +567,24 @@ impl ConfigRepo {
             ConfigRepo::Meta(repo) => repo.hooks.iter().map(|h| h.id.as_str()).collect(),
         }
     }
+
+    /// The `repo` key of the repo: the URL, `local` or `meta`.
+    pub fn location(&self) -> String {
+        match self {
+            ConfigRepo::Remote(repo) => repo.repo.to_string(),
+            ConfigRepo::Local(repo) => repo.repo.clone(),
+            ConfigRepo::Meta(repo) => repo.repo.clone(),
+        }
+    }
+
+    /// Keep only the hooks whose id matches the predicate.
+    pub fn retain_hooks(&mut self, mut f: impl FnMut(&str) -> bool) {
+        match self {
+            ConfigRepo::Remote(repo) => repo.hooks.retain(|h| f(&h.id)),
+            ConfigRepo::Local(repo) => repo.hooks.retain(|h| f(&h.id)),
+            ConfigRepo::Meta(repo) => repo.hooks.retain(|h| f(h.id.as_str())),
+        }
+    }
 }
 
 // TODO: check minimum_pre_commit_version
@@ -486,9 +603,10 @@ pub struct ManifestHook {
     /// Not documented in the official docs.
     pub alias: Option<String>,
     /// The pattern of files to run on.
+    /// Missing or empty matches all files.
     pub files: Option<String>,
     /// Exclude files that were matched by `files`.
-    /// Default is `$^`, which matches nothing.
+    /// Default is `$^`, which matches nothing. An empty pattern also excludes nothing.
     pub exclude: Option<String>,
     /// List of file types to run on (AND).
     /// Default is `[file]`, which matches all files.
@@ -518,7 +636,7 @@ pub struct ManifestHook {
     /// Default is `default`.
     /// See <https://pre-commit.com/#overriding-language-version>.
     pub language_version: Option<String>,
-    /// Write the output of the hook to a file when the hook fails or verbose is enabled.
+    /// Write the output of the hook to a file instead of the terminal, replaced on each run.
     pub log_file: Option<String>,
     /// This hook will execute using a single process instead of in parallel.
     /// Default is false.
@@ -530,6 +648,10 @@ pub struct ManifestHook {
     /// Print the output of the hook even if it passes.
     /// Default is false.
     pub verbose: Option<bool>,
+    /// The directory to run the hook in, relative to the repository root.
+    /// Filenames passed to the hook are relative to this directory.
+    /// Default is the repository root.
+    pub work_dir: Option<String>,
     pub minimum_pre_commit_version: Option<String>,
 }
 
@@ -553,29 +675,182 @@ pub enum Error {
 
     #[error("Invalid repo URL: {0}")]
     RepoUrl(#[from] url::ParseError),
+
+    #[error("`{0}` is too large ({1} bytes), the limit is {} bytes", MAX_FILE_SIZE)]
+    TooLarge(String, usize),
+
+    #[error(
+        "`{0}` is nested too deeply, the limit is {} levels",
+        MAX_NESTING_DEPTH
+    )]
+    TooDeep(String),
+
+    #[error("`{0}` contains multiple YAML documents, only a single document is allowed")]
+    MultipleDocuments(String),
+
+    #[error("Configuration files include each other: {0}")]
+    IncludeCycle(String),
 }
 
+/// The maximum size of a configuration or manifest file, real files are a few KiB.
+const MAX_FILE_SIZE: usize = 1024 * 1024;
+/// The maximum nesting depth of a configuration or manifest file, real files are less than
+/// 10 levels deep.
+const MAX_NESTING_DEPTH: usize = 32;
+
 /// Read the configuration file from the given path.
 pub fn read_config(path: &Path) -> Result<ConfigWire, Error> {
-    let content = match fs_err::read_to_string(path) {
-        Ok(content) => content,
+    let content = read_config_content(path)?;
+    parse_config(&content, &path.user_display().to_string())
+}
+
+/// Read the configuration file from the given path, with the repos of the files it includes.
+pub fn read_config_with_includes(path: &Path) -> Result<ConfigWire, Error> {
+    let mut config = read_config(path)?;
+    resolve_includes(&mut config, path)?;
+    Ok(config)
+}
+
+/// Merge the `repos` of the configuration files listed in `include` into the configuration,
+/// recursively. Other settings of the included files are ignored.
+///
+/// Included repos come first, and the configuration's own hooks take precedence: an included
+/// hook is dropped if a hook with the same id is in the same repo of the configuration.
+pub fn resolve_includes(config: &mut ConfigWire, path: &Path) -> Result<(), Error> {
+    let mut chain = vec![(canonicalize(path)?, path.user_display().to_string())];
+    resolve_includes_inner(config, path, &mut chain)
+}
+
+fn resolve_includes_inner(
+    config: &mut ConfigWire,
+    path: &Path,
+    chain: &mut Vec<(PathBuf, String)>,
+) -> Result<(), Error> {
+    let Some(includes) = config.include.take() else {
+        return Ok(());
+    };
+
+    let base = path.parent().unwrap_or(Path::new(""));
+    let mut repos = Vec::new();
+    for include in includes {
+        let include_path = base.join(include);
+        let canonical = canonicalize(&include_path)?;
+        let name = include_path.user_display().to_string();
+        if chain.iter().any(|(path, _)| *path == canonical) {
+            let cycle = chain
+                .iter()
+                .map(|(_, name)| format!("`{name}`"))
+                .chain(std::iter::once(format!("`{name}`")))
+                .collect::<Vec<_>>()
+                .join(" -> ");
+            return Err(Error::IncludeCycle(cycle));
+        }
+
+        let mut included = read_config(&include_path)?;
+        chain.push((canonical, name));
+        resolve_includes_inner(&mut included, &include_path, chain)?;
+        chain.pop();
+        repos.extend(included.repos);
+    }
+
+    let own: HashSet<(String, String)> = config
+        .repos
+        .iter()
+        .flat_map(|repo| {
+            repo.hook_ids()
+                .into_iter()
+                .map(|id| (repo.location(), id.to_string()))
+        })
+        .collect();
+    repos.retain_mut(|repo| {
+        let location = repo.location();
+        repo.retain_hooks(|id| !own.contains(&(location.clone(), id.to_string())));
+        !repo.hook_ids().is_empty()
+    });
+
+    repos.append(&mut config.repos);
+    config.repos = repos;
+    Ok(())
+}
+
+fn canonicalize(path: &Path) -> Result<PathBuf, Error> {
+    match fs_err::canonicalize(path) {
+        Ok(path) => Ok(path),
         Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
-            return Err(Error::NotFound(path.user_display().to_string()));
+            Err(Error::NotFound(path.user_display().to_string()))
         }
-        Err(e) => return Err(e.into()),
-    };
-    let config = serde_yaml::from_str(&content)
-        .map_err(|e| Error::Yaml(path.user_display().to_string(), e))?;
-    Ok(config)
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Read the raw content of the configuration file.
+pub fn read_config_content(path: &Path) -> Result<String, Error> {
+    match fs_err::read_to_string(path) {
+        Ok(content) => Ok(content),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            Err(Error::NotFound(path.user_display().to_string()))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Parse the configuration from the given content, `name` is used in error messages.
+pub fn parse_config(content: &str, name: &str) -> Result<ConfigWire, Error> {
+    parse_yaml(content, name)
+}
+
+/// Parse YAML from a possibly untrusted repo, rejecting oversized or deeply nested documents
+/// before deserializing.
+fn parse_yaml<T: serde::de::DeserializeOwned>(content: &str, name: &str) -> Result<T, Error> {
+    if content.len() > MAX_FILE_SIZE {
+        return Err(Error::TooLarge(name.to_string(), content.len()));
+    }
+
+    let value: serde_yaml::Value =
+        serde_yaml::from_str(content).map_err(|e| yaml_error(name, e))?;
+    if nesting_depth(&value) > MAX_NESTING_DEPTH {
+        return Err(Error::TooDeep(name.to_string()));
+    }
+
+    // Deserialize from the source, `from_value` would lose the location of errors.
+    serde_yaml::from_str(content).map_err(|e| Error::Yaml(name.to_string(), e))
+}
+
+/// Give the errors `serde_yaml` only has a generic message for a clear one.
+fn yaml_error(name: &str, err: serde_yaml::Error) -> Error {
+    let message = err.to_string();
+    // `serde_yaml` has its own (higher) recursion limit.
+    if message.starts_with("recursion limit exceeded") {
+        Error::TooDeep(name.to_string())
+    // E.g. a stray `---` separator.
+    } else if message.starts_with("deserializing from YAML containing more than one document") {
+        Error::MultipleDocuments(name.to_string())
+    } else {
+        Error::Yaml(name.to_string(), err)
+    }
+}
+
+/// The nesting depth of a YAML value, scalars have a depth of 0.
+fn nesting_depth(value: &serde_yaml::Value) -> usize {
+    match value {
+        serde_yaml::Value::Sequence(seq) => 1 + seq.iter().map(nesting_depth).max().unwrap_or(0),
+        serde_yaml::Value::Mapping(map) => {
+            1 + map
+                .iter()
+                .map(|(k, v)| nesting_depth(k).max(nesting_depth(v)))
+                .max()
+                .unwrap_or(0)
+        }
+        serde_yaml::Value::Tagged(tagged) => nesting_depth(&tagged.value),
+        _ => 0,
+    }
 }
 
 // TODO: check id duplication?
 /// Read the manifest file from the given path.
 pub fn read_manifest(path: &Path) -> Result<ManifestWire, Error> {
     let content = fs_err::read_to_string(path)?;
-    let manifest = serde_yaml::from_str(&content)
-        .map_err(|e| Error::Yaml(path.user_display().to_string(), e))?;
-    Ok(manifest)
+    parse_yaml(&content, &path.user_display().to_string())
 }
 
 #[cfg(test)]
@@ -625,6 +900,7 @@ mod tests {
                                     require_serial: None,
                                     stages: None,
                                     verbose: None,
+                                    work_dir: None,
                                     minimum_pre_commit_version: None,
                                 },
                             ],
@@ -637,8 +913,10 @@ mod tests {
                 files: None,
                 exclude: None,
                 fail_fast: None,
+                jobs: None,
                 minimum_pre_commit_version: None,
                 ci: None,
+                include: None,
             },
         )
         "###);
@@ -656,7 +934,21 @@ mod tests {
         let result = serde_yaml::from_str::<ConfigWire>(yaml);
         insta::assert_debug_snapshot!(result, @r###"
         Err(
-            Error("repos: Invalid local repo: unknown field `rev`, expected `hooks`", line: 2, column: 3),
+            Error("repos: Invalid local repo: `repo: local` must not set `rev`", line: 2, column: 3),
+        )
+        "###);
+
+        let yaml = indoc::indoc! {r"
+            repos:
+              - repo: meta
+                rev: v1.0.0
+                hooks:
+                  - id: identity
+        "};
+        let result = serde_yaml::from_str::<ConfigWire>(yaml);
+        insta::assert_debug_snapshot!(result, @r###"
+        Err(
+            Error("repos: Invalid meta repo: `repo: meta` must not set `rev`", line: 2, column: 3),
         )
         "###);
 
@@ -714,6 +1006,7 @@ mod tests {
                                     require_serial: None,
                                     stages: None,
                                     verbose: None,
+                                    work_dir: None,
                                     minimum_pre_commit_version: None,
                                 },
                             ],
@@ -726,8 +1019,10 @@ mod tests {
                 files: None,
                 exclude: None,
                 fail_fast: None,
+                jobs: None,
                 minimum_pre_commit_version: None,
                 ci: None,
+                include: None,
             },
         )
         "###);
@@ -741,7 +1036,7 @@ mod tests {
         let result = serde_yaml::from_str::<ConfigWire>(yaml);
         insta::assert_debug_snapshot!(result, @r###"
         Err(
-            Error("repos: Invalid remote repo: missing field `rev`", line: 2, column: 3),
+            Error("repos: Invalid remote repo: `repo: https://github.com/crate-ci/typos` must set `rev`", line: 2, column: 3),
         )
         "###);
     }
@@ -822,6 +1117,7 @@ mod tests {
                                     require_serial: None,
                                     stages: None,
                                     verbose: None,
+                                    work_dir: None,
                                     minimum_pre_commit_version: None,
                                 },
                             ],
@@ -834,8 +1130,10 @@ mod tests {
                 files: None,
                 exclude: None,
                 fail_fast: None,
+                jobs: None,
                 minimum_pre_commit_version: None,
                 ci: None,
+                include: None,
             },
         )
         "###);
@@ -854,4 +1152,73 @@ mod tests {
         insta::assert_debug_snapshot!(manifest);
         Ok(())
     }
+
+    #[test]
+    fn nesting_too_deep() {
+        // Within `serde_yaml`'s own recursion limit, but above ours.
+        let yaml = format!("repos: {}{}", "[".repeat(40), "]".repeat(40));
+        let err = parse_config(&yaml, "config.yaml").unwrap_err();
+        assert!(matches!(err, Error::TooDeep(_)));
+
+        // Beyond `serde_yaml`'s recursion limit.
+        let yaml = format!("repos: {}{}", "[".repeat(10_000), "]".repeat(10_000));
+        let err = parse_config(&yaml, "config.yaml").unwrap_err();
+        insta::assert_snapshot!(err, @"`config.yaml` is nested too deeply, the limit is 32 levels");
+    }
+
+    #[test]
+    fn multiple_documents() {
+        let yaml = indoc::indoc! {r"
+            repos: []
+            ---
+            repos:
+              - repo: local
+                hooks: []
+        "};
+        let err = parse_config(yaml, "config.yaml").unwrap_err();
+        insta::assert_snapshot!(err, @"`config.yaml` contains multiple YAML documents, only a single document is allowed");
+
+        // A leading document marker is still a single document.
+        parse_config("---\nrepos: []\n", "config.yaml").unwrap();
+
+        // A syntax error is not reported as multiple documents.
+        let err = parse_config("repos: [", "config.yaml").unwrap_err();
+        assert!(matches!(err, Error::Yaml(_, _)));
+    }
+
+    #[test]
+    fn config_too_large() {
+        let yaml = format!("repos: []\n#{}", "a".repeat(MAX_FILE_SIZE));
+        let err = parse_config(&yaml, "config.yaml").unwrap_err();
+        assert!(matches!(err, Error::TooLarge(_, _)));
+    }
+
+    #[test]
+    fn default_hook_types() -> Result<()> {
+        let config = parse_config("repos: []\n", "config.yaml")?;
+        assert!(matches!(
+            resolve_hook_types(Some(&config), vec![])[..],
+            [HookType::PreCommit]
+        ));
+        assert!(matches!(
+            resolve_hook_types(None, vec![])[..],
+            [HookType::PreCommit]
+        ));
+
+        let config = parse_config(
+            "repos: []\ndefault_install_hook_types: [pre-push, commit-msg]\n",
+            "config.yaml",
+        )?;
+        assert!(matches!(
+            resolve_hook_types(Some(&config), vec![])[..],
+            [HookType::PrePush, HookType::CommitMsg]
+        ));
+        // Explicit hook types take precedence.
+        assert!(matches!(
+            resolve_hook_types(Some(&config), vec![HookType::PostCommit])[..],
+            [HookType::PostCommit]
+        ));
+
+        Ok(())
+    }
 }
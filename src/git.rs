@@ -1,7 +1,10 @@
+use std::fmt::Display;
 use std::path::{Path, PathBuf};
+use std::process::Stdio;
 use std::sync::LazyLock;
 
 use anyhow::Result;
+use tokio::io::{AsyncBufReadExt, BufReader};
 use tracing::warn;
 
 use crate::process;
@@ -13,12 +16,17 @@ pub enum Error {
     Command(#[from] process::Error),
     #[error("Failed to find git: {0}")]
     GitNotFound(#[from] which::Error),
+    #[error("Failed to parse git version from `{0}`")]
+    InvalidVersion(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
 }
 
 pub static GIT: LazyLock<Result<PathBuf, which::Error>> = LazyLock::new(|| which::which("git"));
 
-static GIT_ENV: LazyLock<Vec<(String, String)>> = LazyLock::new(|| {
-    let keep = &[
+/// Whether a `GIT_*` env var should be passed on to git commands and hooks.
+fn keep_git_env(key: &str) -> bool {
+    const KEEP: &[&str] = &[
         "GIT_EXEC_PATH",
         "GIT_SSH",
         "GIT_SSH_COMMAND",
@@ -30,16 +38,100 @@ static GIT_ENV: LazyLock<Vec<(String, String)>> = LazyLock::new(|| {
         "GIT_ASKPASS",
     ];
 
+    !key.starts_with("GIT_")
+        || key.starts_with("GIT_CONFIG_KEY_")
+        || key.starts_with("GIT_CONFIG_VALUE_")
+        || KEEP.contains(&key)
+}
+
+/// The env vars passed to git commands.
+///
+/// `GIT_INDEX_FILE` is set by git when it runs the `pre-commit` hook, e.g. to a temporary
+/// index for `git commit <paths>`, it is the index to check, so it's kept. A relative path
+/// is resolved against the original working directory, so this must be initialized before
+/// changing the working directory.
+pub static GIT_ENV: LazyLock<Vec<(String, String)>> = LazyLock::new(|| {
     std::env::vars()
-        .filter(|(k, _)| {
-            !k.starts_with("GIT_")
-                || k.starts_with("GIT_CONFIG_KEY_")
-                || k.starts_with("GIT_CONFIG_VALUE_")
-                || keep.contains(&k.as_str())
+        .filter_map(|(k, v)| {
+            if k == "GIT_INDEX_FILE" {
+                let path = std::path::absolute(&v).ok()?;
+                Some((k, path.to_string_lossy().to_string()))
+            } else if keep_git_env(&k) {
+                Some((k, v))
+            } else {
+                None
+            }
         })
         .collect()
 });
 
+/// The `GIT_*` env vars that git sets when running hooks, e.g. `GIT_INDEX_FILE`.
+/// They break nested git commands in hooks, especially when the hook runs in another directory.
+pub static HOOK_GIT_ENV_REMOVE: LazyLock<Vec<String>> = LazyLock::new(|| {
+    std::env::vars()
+        .map(|(k, _)| k)
+        .filter(|k| !keep_git_env(k))
+        .collect()
+});
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct GitVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl GitVersion {
+    /// Parse the output of `git --version`, e.g. `git version 2.39.3 (Apple Git-146)`
+    /// or `git version 2.45.1.windows.1`.
+    fn parse(output: &str) -> Option<Self> {
+        let version = output.trim().strip_prefix("git version ")?;
+        let version = version.split_whitespace().next()?;
+        let mut parts = version.split('.').map(str::parse::<u32>);
+        let major = parts.next()?.ok()?;
+        let minor = parts.next()?.ok()?;
+        // Release candidates, e.g. `2.47.0-rc0`, have no numeric patch version.
+        let patch = parts.next().and_then(Result::ok).unwrap_or(0);
+        Some(Self {
+            major,
+            minor,
+            patch,
+        })
+    }
+}
+
+impl Display for GitVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// The minimum supported git version, `protocol.version=2` for fetching hook repos needs 2.18.
+pub const MIN_GIT_VERSION: GitVersion = GitVersion {
+    major: 2,
+    minor: 18,
+    patch: 0,
+};
+
+static GIT_VERSION: tokio::sync::OnceCell<GitVersion> = tokio::sync::OnceCell::const_new();
+
+/// Get the version of git, it is only queried once per process.
+pub async fn git_version() -> Result<GitVersion, Error> {
+    let version = GIT_VERSION
+        .get_or_try_init(|| async {
+            let output = git_cmd("get git version")?
+                .arg("--version")
+                .check(true)
+                .output()
+                .await?;
+            let output = String::from_utf8_lossy(&output.stdout);
+            GitVersion::parse(&output)
+                .ok_or_else(|| Error::InvalidVersion(output.trim().to_string()))
+        })
+        .await?;
+    Ok(*version)
+}
+
 pub fn git_cmd(summary: &str) -> Result<Cmd, Error> {
     let mut cmd = Cmd::new(GIT.as_ref().map_err(|&e| Error::GitNotFound(e))?, summary);
     cmd.arg("-c").arg("core.useBuiltinFSMonitor=false");
@@ -48,6 +140,21 @@ pub fn git_cmd(summary: &str) -> Result<Cmd, Error> {
     Ok(cmd)
 }
 
+/// Create a git command to operate on a repo in the store.
+///
+/// Git is stopped from discovering a repo above `path`, so if `path` is not a repo the command
+/// fails, instead of operating on the user's repo, e.g. adding a remote to it.
+fn store_git_cmd(summary: &str, path: &Path) -> Result<Cmd, Error> {
+    // `GIT_CEILING_DIRECTORIES` ignores relative paths.
+    let path = std::path::absolute(path)?;
+    let mut cmd = git_cmd(summary)?;
+    cmd.current_dir(&path);
+    if let Some(parent) = path.parent() {
+        cmd.env("GIT_CEILING_DIRECTORIES", parent);
+    }
+    Ok(cmd)
+}
+
 fn zsplit(s: &[u8]) -> Vec<String> {
     let s = String::from_utf8_lossy(s);
     let s = s.trim_end_matches('\0');
@@ -74,6 +181,11 @@ pub async fn intent_to_add_files() -> Result<Vec<String>, Error> {
     Ok(zsplit(&output.stdout))
 }
 
+/// Get the files changed between `old` and `new`, compared directly as in `git diff old new`.
+/// Use [`merge_base`] for `old` to only get the changes made on `new` since it diverged.
+///
+/// A renamed file is always reported by its new path, whether git detects it as a rename or as
+/// a deletion and an addition, so the rename similarity threshold makes no difference here.
 pub async fn get_changed_files(old: &str, new: &str) -> Result<Vec<String>, Error> {
     let output = git_cmd("get changed files")?
         .arg("diff")
@@ -81,23 +193,101 @@ pub async fn get_changed_files(old: &str, new: &str) -> Result<Vec<String>, Erro
         .arg("--diff-filter=ACMRT")
         .arg("--no-ext-diff") // Disable external diff drivers
         .arg("-z") // Use NUL as line terminator
-        .arg(format!("{old}...{new}"))
+        .arg(old)
+        .arg(new)
         .check(true)
         .output()
         .await?;
     Ok(zsplit(&output.stdout))
 }
 
+/// Get the best common ancestor of `a` and `b`, as with `git merge-base a b`.
+pub async fn merge_base(a: &str, b: &str) -> Result<String, Error> {
+    let output = git_cmd("git merge-base")?
+        .arg("merge-base")
+        .arg(a)
+        .arg(b)
+        .check(true)
+        .output()
+        .await?;
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Get all tracked files.
+///
+/// The NUL-separated output of `git ls-files -z` is parsed as it is read, instead of buffering
+/// the whole output first. The file list itself is still collected in full.
 pub async fn get_all_files() -> Result<Vec<String>, Error> {
-    let output = git_cmd("get git all files")?
+    let mut cmd = git_cmd("get git all files")?;
+    let mut child = cmd
+        .arg("ls-files")
+        .arg("-z")
+        .stdout(Stdio::piped())
+        .spawn()?;
+
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let mut reader = BufReader::new(stdout);
+    let mut files = Vec::new();
+    let mut buf = Vec::new();
+    while reader.read_until(b'\0', &mut buf).await? > 0 {
+        if buf.last() == Some(&b'\0') {
+            buf.pop();
+        }
+        files.push(String::from_utf8_lossy(&buf).into_owned());
+        buf.clear();
+    }
+
+    let status = child.wait().await?;
+    cmd.check_status(status)?;
+    Ok(files)
+}
+
+/// Get the untracked files, ignored files are excluded.
+pub async fn get_untracked_files() -> Result<Vec<String>, Error> {
+    let output = git_cmd("get untracked files")?
+        .arg("ls-files")
+        .arg("-z")
+        .arg("--others")
+        .arg("--exclude-standard")
+        .check(true)
+        .output()
+        .await?;
+    Ok(zsplit(&output.stdout))
+}
+
+/// Get the tracked files under the given directory.
+pub async fn ls_files_under(path: &Path) -> Result<Vec<String>, Error> {
+    let output = git_cmd("get git files under path")?
         .arg("ls-files")
         .arg("-z")
+        .arg("--")
+        .arg(path)
         .check(true)
         .output()
         .await?;
     Ok(zsplit(&output.stdout))
 }
 
+/// Get the paths of the submodules, i.e. the gitlink entries (mode `160000`) in the index.
+pub async fn get_submodule_paths() -> Result<Vec<String>, Error> {
+    let output = git_cmd("get git submodules")?
+        .arg("ls-files")
+        .arg("--stage")
+        .arg("-z")
+        .check(true)
+        .output()
+        .await?;
+    Ok(zsplit(&output.stdout)
+        .into_iter()
+        .filter_map(|entry| {
+            // `<mode> <object> <stage>\t<path>`
+            let (info, path) = entry.split_once('\t')?;
+            info.starts_with("160000 ").then(|| path.to_string())
+        })
+        .collect())
+}
+
+/// The git directory of the current worktree, e.g. `.git/worktrees/<name>` in a linked worktree.
 pub async fn get_git_dir() -> Result<PathBuf, Error> {
     let output = git_cmd("get git dir")?
         .arg("rev-parse")
@@ -110,6 +300,9 @@ pub async fn get_git_dir() -> Result<PathBuf, Error> {
     ))
 }
 
+/// The git directory shared by all worktrees of the repository, which holds the hooks.
+///
+/// Older git versions don't support `--git-common-dir`, fall back to the git directory.
 pub async fn get_git_common_dir() -> Result<PathBuf, Error> {
     let output = git_cmd("get git common dir")?
         .arg("rev-parse")
@@ -141,13 +334,26 @@ pub async fn get_staged_files() -> Result<Vec<String>, Error> {
 }
 
 pub async fn has_unmerged_paths() -> Result<bool, Error> {
-    let output = git_cmd("check has unmerged paths")?
+    Ok(!get_conflicted_files().await?.is_empty())
+}
+
+/// Get the unmerged paths in the index, each path is listed once.
+pub async fn get_conflicted_files() -> Result<Vec<String>, Error> {
+    let output = git_cmd("get conflicted files")?
         .arg("ls-files")
         .arg("--unmerged")
+        .arg("-z")
         .check(true)
         .output()
         .await?;
-    Ok(!String::from_utf8_lossy(&output.stdout).trim().is_empty())
+
+    // Each entry is `<mode> <object> <stage>\t<path>`, with an entry per stage.
+    let mut files: Vec<String> = zsplit(&output.stdout)
+        .into_iter()
+        .filter_map(|entry| entry.split_once('\t').map(|(_, path)| path.to_string()))
+        .collect();
+    files.dedup();
+    Ok(files)
 }
 
 pub async fn get_diff() -> Result<Vec<u8>, Error> {
@@ -175,6 +381,42 @@ pub async fn write_tree() -> Result<String, Error> {
     Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
 }
 
+/// Write a tree of the working tree as it is, with the non-staged changes of tracked files.
+///
+/// A copy of the index is used, so the real index is left untouched.
+pub async fn write_working_tree() -> Result<String, Error> {
+    let temp = tempfile::tempdir()?;
+    let index = temp.path().join("index");
+
+    let output = git_cmd("get index path")?
+        .arg("rev-parse")
+        .arg("--git-path")
+        .arg("index")
+        .check(true)
+        .output()
+        .await?;
+    let current = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+    // A new repo has no index until something is added.
+    if current.try_exists()? {
+        fs_err::copy(&current, &index)?;
+    }
+
+    git_cmd("git add")?
+        .arg("add")
+        .arg("--update")
+        .env("GIT_INDEX_FILE", &index)
+        .check(true)
+        .output()
+        .await?;
+    let output = git_cmd("git write-tree")?
+        .arg("write-tree")
+        .env("GIT_INDEX_FILE", &index)
+        .check(true)
+        .output()
+        .await?;
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
 /// Get the path of the top-level directory of the working tree.
 pub async fn get_root() -> Result<PathBuf, Error> {
     let output = git_cmd("get git root")?
@@ -207,6 +449,76 @@ pub async fn is_dirty(path: &Path) -> Result<bool, Error> {
     }
 }
 
+/// Check if the file is tracked by git.
+///
+/// Files outside the repository are treated as tracked, since there is nothing to compare with.
+pub async fn is_tracked(path: &Path) -> Result<bool, Error> {
+    let output = git_cmd("check file is tracked")?
+        .arg("ls-files")
+        .arg("--error-unmatch")
+        .arg("--")
+        .arg(path)
+        .check(false)
+        .output()
+        .await?;
+    Ok(output.status.code() != Some(1))
+}
+
+/// Check if the file differs from the version in `HEAD`, including staged changes.
+///
+/// Returns `false` if the repository has no commits yet.
+pub async fn has_uncommitted_changes(path: &Path) -> Result<bool, Error> {
+    let output = git_cmd("check uncommitted changes")?
+        .arg("diff")
+        .arg("--quiet") // Implies `--exit-code`
+        .arg("--no-ext-diff") // Disable external diff drivers
+        .arg("HEAD")
+        .arg("--")
+        .arg(path)
+        .check(false)
+        .output()
+        .await?;
+    Ok(output.status.code() == Some(1))
+}
+
+/// Check if the rev exists in the remote repository.
+///
+/// `git ls-remote` can only resolve refs, so revs that look like a commit SHA are assumed to exist.
+pub async fn ls_remote(url: &str, rev: &str) -> Result<bool, Error> {
+    if (7..=40).contains(&rev.len()) && rev.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Ok(true);
+    }
+
+    let mut cmd = git_cmd("git ls-remote")?;
+    let output = cmd
+        .arg("ls-remote")
+        .arg("--exit-code") // Exit with status 2 if no matching refs are found
+        .arg(url)
+        .arg(rev)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .check(false)
+        .output()
+        .await?;
+    if output.status.success() {
+        Ok(true)
+    } else if output.status.code() == Some(2) {
+        Ok(false)
+    } else {
+        Err(cmd.check_status(output.status).unwrap_err().into())
+    }
+}
+
+/// Read the content of the file at the given rev, `path` is relative to the repository root.
+pub async fn show_blob(rev: &str, path: &Path) -> Result<String, Error> {
+    let output = git_cmd("git show")?
+        .arg("show")
+        .arg(format!("{rev}:{}", path.to_string_lossy()))
+        .check(true)
+        .output()
+        .await?;
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
 async fn init_repo(url: &str, path: &Path) -> Result<(), Error> {
     git_cmd("init git repo")?
         .arg("init")
@@ -216,8 +528,7 @@ async fn init_repo(url: &str, path: &Path) -> Result<(), Error> {
         .output()
         .await?;
 
-    git_cmd("add git remote")?
-        .current_dir(path)
+    store_git_cmd("add git remote", path)?
         .arg("remote")
         .arg("add")
         .arg("origin")
@@ -230,8 +541,7 @@ async fn init_repo(url: &str, path: &Path) -> Result<(), Error> {
 }
 
 async fn shallow_clone(rev: &str, path: &Path) -> Result<(), Error> {
-    git_cmd("git shallow clone")?
-        .current_dir(path)
+    store_git_cmd("git shallow clone", path)?
         .arg("-c")
         .arg("protocol.version=2")
         .arg("fetch")
@@ -242,16 +552,14 @@ async fn shallow_clone(rev: &str, path: &Path) -> Result<(), Error> {
         .output()
         .await?;
 
-    git_cmd("git checkout")?
-        .current_dir(path)
+    store_git_cmd("git checkout", path)?
         .arg("checkout")
         .arg("FETCH_HEAD")
         .check(true)
         .output()
         .await?;
 
-    git_cmd("update git submodules")?
-        .current_dir(path)
+    store_git_cmd("update git submodules", path)?
         .arg("-c")
         .arg("protocol.version=2")
         .arg("submodule")
@@ -267,8 +575,7 @@ async fn shallow_clone(rev: &str, path: &Path) -> Result<(), Error> {
 }
 
 async fn full_clone(rev: &str, path: &Path) -> Result<(), Error> {
-    git_cmd("git full clone")?
-        .current_dir(path)
+    store_git_cmd("git full clone", path)?
         .arg("fetch")
         .arg("origin")
         .arg("--tags")
@@ -276,16 +583,14 @@ async fn full_clone(rev: &str, path: &Path) -> Result<(), Error> {
         .output()
         .await?;
 
-    git_cmd("git checkout")?
-        .current_dir(path)
+    store_git_cmd("git checkout", path)?
         .arg("checkout")
         .arg(rev)
         .check(true)
         .output()
         .await?;
 
-    git_cmd("update git submodules")?
-        .current_dir(path)
+    store_git_cmd("update git submodules", path)?
         .arg("submodule")
         .arg("update")
         .arg("--init")
@@ -297,6 +602,54 @@ async fn full_clone(rev: &str, path: &Path) -> Result<(), Error> {
     Ok(())
 }
 
+/// Fetch the default branch and tags of `url` into a new repo at `path`, as `FETCH_HEAD`.
+pub async fn fetch_head(url: &str, path: &Path) -> Result<(), Error> {
+    init_repo(url, path).await?;
+
+    store_git_cmd("git fetch", path)?
+        .arg("fetch")
+        .arg("origin")
+        .arg("HEAD")
+        .arg("--tags")
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .check(true)
+        .output()
+        .await?;
+
+    Ok(())
+}
+
+/// Describe `rev` of the repo at `path` with a tag, the latest tag reachable from it,
+/// or only a tag pointing at it with `exact`. `None` if there is no such tag.
+pub async fn describe_tag(path: &Path, rev: &str, exact: bool) -> Result<Option<String>, Error> {
+    let mut cmd = store_git_cmd("git describe", path)?;
+    cmd.arg("describe").arg(rev).arg("--tags");
+    if exact {
+        cmd.arg("--exact-match");
+    } else {
+        cmd.arg("--abbrev=0");
+    }
+    let output = cmd.check(false).output().await?;
+    if output.status.success() {
+        Ok(Some(
+            String::from_utf8_lossy(&output.stdout).trim().to_string(),
+        ))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Resolve `rev` of the repo at `path` to an object name.
+pub async fn rev_parse(path: &Path, rev: &str) -> Result<String, Error> {
+    let output = store_git_cmd("git rev-parse", path)?
+        .arg("rev-parse")
+        .arg(rev)
+        .check(true)
+        .output()
+        .await?;
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
 pub async fn clone_repo(url: &str, rev: &str, path: &Path) -> Result<(), Error> {
     init_repo(url, path).await?;
 
@@ -322,3 +675,78 @@ pub async fn has_hooks_path_set() -> Result<bool> {
         Ok(false)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_git_version() {
+        let version = |major, minor, patch| GitVersion {
+            major,
+            minor,
+            patch,
+        };
+        assert_eq!(
+            GitVersion::parse("git version 2.47.1\n"),
+            Some(version(2, 47, 1))
+        );
+        assert_eq!(
+            GitVersion::parse("git version 2.39.3 (Apple Git-146)"),
+            Some(version(2, 39, 3))
+        );
+        assert_eq!(
+            GitVersion::parse("git version 2.45.1.windows.1"),
+            Some(version(2, 45, 1))
+        );
+        assert_eq!(
+            GitVersion::parse("git version 2.47.0-rc0"),
+            Some(version(2, 47, 0))
+        );
+        assert_eq!(GitVersion::parse("not git"), None);
+
+        assert!(version(2, 17, 9) < MIN_GIT_VERSION);
+        assert!(version(2, 18, 0) >= MIN_GIT_VERSION);
+    }
+
+    #[test]
+    fn store_git_cmd_outside_repo() -> anyhow::Result<()> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let user_repo = tempfile::tempdir()?;
+        let store_repo = user_repo.path().join("store").join("repo");
+        std::fs::create_dir_all(&store_repo)?;
+
+        runtime.block_on(async {
+            git_cmd("init user repo")?
+                .arg("init")
+                .arg(user_repo.path())
+                .check(true)
+                .output()
+                .await?;
+
+            // Without a repo at `store_repo`, the user's repo must not be found.
+            let result = store_git_cmd("get git dir", &store_repo)?
+                .arg("rev-parse")
+                .arg("--git-dir")
+                .check(true)
+                .output()
+                .await;
+            assert!(result.is_err());
+
+            init_repo("https://example.com/repo.git", &store_repo).await?;
+            let output = store_git_cmd("get git dir", &store_repo)?
+                .arg("rev-parse")
+                .arg("--absolute-git-dir")
+                .check(true)
+                .output()
+                .await?;
+            assert_eq!(
+                Path::new(String::from_utf8_lossy(&output.stdout).trim()),
+                std::fs::canonicalize(store_repo.join(".git"))?
+            );
+            anyhow::Ok(())
+        })
+    }
+}
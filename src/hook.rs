@@ -1,10 +1,10 @@
 use std::fmt::Display;
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
 use anyhow::Result;
 use clap::ValueEnum;
-use futures::stream::FuturesUnordered;
 use futures::StreamExt;
 use itertools::zip_eq;
 use thiserror::Error;
@@ -12,15 +12,23 @@ use tracing::{debug, error};
 use url::Url;
 
 use crate::config::{
-    self, read_config, read_manifest, ConfigLocalHook, ConfigRemoteHook, ConfigRepo, ConfigWire,
-    ManifestHook, Stage, CONFIG_FILE, MANIFEST_FILE,
+    self, parse_config, read_config_content, read_manifest, resolve_includes, ConfigLocalHook,
+    ConfigMetaHook, ConfigRemoteHook, ConfigRepo, ConfigWire, ManifestHook, Stage, CONFIG_FILE,
+    MANIFEST_FILE,
 };
 use crate::fs::{Simplified, CWD};
+use crate::git;
+use crate::identify::tags;
 use crate::languages::{Language, DEFAULT_VERSION};
+use crate::meta_hooks;
 use crate::printer::Printer;
-use crate::store::Store;
+use crate::run::Concurrency;
+use crate::store::{Store, LOCAL_NAME};
 use crate::warn_user;
 
+/// The file marking a hook environment as completely installed.
+pub const INSTALL_STATE_FILE: &str = ".install_state_v2";
+
 #[derive(Debug, Error)]
 pub enum Error {
     #[error("Failed to parse URL: {0}")]
@@ -32,6 +40,23 @@ pub enum Error {
     #[error(transparent)]
     Store(#[from] Box<crate::store::Error>),
     #[error(transparent)]
+    Git(#[from] git::Error),
+    #[error("Hook `{hook}` uses language `{language}`, which is not supported yet (supported languages: {supported})")]
+    UnsupportedLanguage {
+        hook: String,
+        language: config::Language,
+        supported: String,
+    },
+    #[error("Hook `{hook}` specifies `additional_dependencies`, but language `{language}` does not install an environment to add them to")]
+    AdditionalDependenciesNotSupported {
+        hook: String,
+        language: config::Language,
+    },
+    #[error("`{0}` includes other configuration files, which is not supported when reading the configuration from a rev")]
+    IncludeAtRev(String),
+    #[error("Failed to parse entry of hook `{hook}`: `{entry}`")]
+    InvalidEntry { hook: String, entry: String },
+    #[error(transparent)]
     Io(#[from] std::io::Error),
 }
 
@@ -47,7 +72,9 @@ pub enum Repo {
     Local {
         hooks: Vec<ManifestHook>,
     },
-    Meta,
+    Meta {
+        hooks: Vec<ManifestHook>,
+    },
 }
 
 impl Repo {
@@ -72,16 +99,18 @@ impl Repo {
         Self::Local { hooks }
     }
 
-    pub fn meta() -> Result<Self, Error> {
-        todo!()
+    /// Construct the meta repo, its hooks are built in.
+    pub fn meta() -> Self {
+        Self::Meta {
+            hooks: meta_hooks::manifest(),
+        }
     }
 
     /// Get a hook by id.
     pub fn get_hook(&self, id: &str) -> Option<&ManifestHook> {
         let hooks = match self {
             Repo::Remote { ref hooks, .. } => hooks,
-            Repo::Local { ref hooks } => hooks,
-            Repo::Meta => return None,
+            Repo::Local { ref hooks } | Repo::Meta { ref hooks } => hooks,
         };
         hooks.iter().find(|hook| hook.id == id)
     }
@@ -89,8 +118,7 @@ impl Repo {
     pub fn path(&self) -> &Path {
         match self {
             Repo::Remote { ref path, .. } => path,
-            Repo::Local { .. } => &CWD,
-            Repo::Meta => todo!(),
+            Repo::Local { .. } | Repo::Meta { .. } => &CWD,
         }
     }
 }
@@ -100,15 +128,19 @@ impl Display for Repo {
         match self {
             Repo::Remote { url, rev, .. } => write!(f, "{url}@{rev}"),
             Repo::Local { .. } => write!(f, "local"),
-            Repo::Meta => write!(f, "meta"),
+            Repo::Meta { .. } => write!(f, "meta"),
         }
     }
 }
 
 pub struct Project {
     config_path: PathBuf,
+    /// Hash of the configuration file content, `None` if the configuration is not from a file.
+    config_hash: Option<u64>,
     config: ConfigWire,
     repos: Vec<Rc<Repo>>,
+    /// Hooks resolved by the last `init_hooks`, with the store they were resolved in.
+    cached_hooks: Option<(PathBuf, Vec<Hook>)>,
 }
 
 impl Project {
@@ -134,13 +166,50 @@ impl Project {
             path = %config_path.display(),
             "Loading project configuration"
         );
-        let config = read_config(&config_path)?;
+        let content = read_config_content(&config_path)?;
+        let mut config = parse_config(&content, &config_path.user_display().to_string())?;
+        resolve_includes(&mut config, &config_path)?;
+        let mut project = Self::with_config(config, config_path);
+        project.config_hash = Some(hash_content(&content));
+        Ok(project)
+    }
+
+    /// Initialize a new project from the configuration content, which is not read from a file.
+    ///
+    /// `config_path` is where the configuration is considered to be, it is used in messages.
+    pub fn from_config_str(content: &str, config_path: PathBuf) -> Result<Self, Error> {
+        let config = parse_config(content, &config_path.user_display().to_string())?;
+        Ok(Self::with_config(config, config_path))
+    }
+
+    /// Initialize a new project from the configuration file as it existed at the given rev.
+    pub async fn from_rev(config: Option<PathBuf>, rev: &str) -> Result<Self, Error> {
+        let config_path = config.unwrap_or_else(|| PathBuf::from(CONFIG_FILE));
+        let path = config_path.strip_prefix(&*CWD).unwrap_or(&config_path);
+        debug!(
+            path = %path.display(),
+            rev,
+            "Loading project configuration from git"
+        );
+        let content = git::show_blob(rev, path).await?;
+        let name = format!("{rev}:{}", path.user_display());
+        let config = parse_config(&content, &name)?;
+        // Included files would have to be read at the rev too.
+        if config.include.is_some() {
+            return Err(Error::IncludeAtRev(name));
+        }
+        Ok(Self::with_config(config, config_path))
+    }
+
+    fn with_config(config: ConfigWire, config_path: PathBuf) -> Self {
         let size = config.repos.len();
-        Ok(Self {
+        Self {
             config,
             config_path,
+            config_hash: None,
             repos: Vec::with_capacity(size),
-        })
+            cached_hooks: None,
+        }
     }
 
     pub fn config(&self) -> &ConfigWire {
@@ -155,7 +224,7 @@ impl Project {
         let mut repos = Vec::with_capacity(self.config.repos.len());
 
         // TODO: progress bar
-        let mut tasks = FuturesUnordered::new();
+        let mut tasks = Vec::new();
         for (idx, repo) in self.config.repos.iter().enumerate() {
             match repo {
                 ConfigRepo::Remote(repo) => {
@@ -169,11 +238,12 @@ impl Project {
                     repos.push((idx, Rc::new(repo)));
                 }
                 ConfigRepo::Meta(_) => {
-                    todo!()
+                    repos.push((idx, Rc::new(Repo::meta())));
                 }
             }
         }
 
+        let mut tasks = futures::stream::iter(tasks).buffer_unordered(Concurrency::get().repos);
         while let Some((idx, repo_path)) = tasks.next().await {
             let repo_path = repo_path.map_err(Box::new)?;
             let ConfigRepo::Remote(repo_config) = &self.config.repos[idx] else {
@@ -194,11 +264,66 @@ impl Project {
     }
 
     /// Load and prepare hooks for the project.
+    ///
+    /// The hooks are cached, so repeated calls (e.g. in watch mode) skip cloning repos and reading
+    /// manifests, until the configuration file changes.
     pub async fn init_hooks(
         &mut self,
         store: &Store,
         printer: Printer,
     ) -> Result<Vec<Hook>, Error> {
+        self.reload_if_changed()?;
+        if let Some(hooks) = self.get_cached_hooks(store) {
+            debug!("Using cached hooks");
+            return Ok(hooks);
+        }
+
+        let hooks = self.resolve_hooks(store, printer).await?;
+        self.cached_hooks = Some((store.path().to_path_buf(), hooks.clone()));
+        Ok(hooks)
+    }
+
+    /// Reload the configuration if the file has changed since it was read.
+    fn reload_if_changed(&mut self) -> Result<(), Error> {
+        let Some(hash) = self.config_hash else {
+            return Ok(());
+        };
+        let content = read_config_content(&self.config_path)?;
+        let new_hash = hash_content(&content);
+        if new_hash != hash {
+            debug!(
+                path = %self.config_path.display(),
+                "Configuration changed, reloading"
+            );
+            let mut config = parse_config(&content, &self.config_path.user_display().to_string())?;
+            resolve_includes(&mut config, &self.config_path)?;
+            self.config = config;
+            self.config_hash = Some(new_hash);
+            self.cached_hooks = None;
+        }
+        Ok(())
+    }
+
+    /// Get the cached hooks, if resolved in the same store and their environments still exist.
+    fn get_cached_hooks(&self, store: &Store) -> Option<Vec<Hook>> {
+        let (store_path, hooks) = self.cached_hooks.as_ref()?;
+        if store_path != store.path() {
+            return None;
+        }
+        // The store may have been cleaned in the meantime.
+        if !hooks
+            .iter()
+            .all(|hook| hook.path().try_exists().unwrap_or(false))
+        {
+            return None;
+        }
+        Some(hooks.clone())
+    }
+
+    async fn resolve_hooks(&mut self, store: &Store, printer: Printer) -> Result<Vec<Hook>, Error> {
+        store
+            .mark_config_used(&self.config_path)
+            .map_err(Box::new)?;
         self.init_repos(store, printer).await?;
 
         let mut hooks = Vec::new();
@@ -219,8 +344,9 @@ impl Project {
                         let repo = Rc::clone(repo);
                         let mut builder = HookBuilder::new(repo, hook.clone());
                         builder.update(hook_config);
+                        builder.check_language()?;
                         builder.combine(&self.config);
-                        let mut hook = builder.build();
+                        let mut hook = builder.build()?;
 
                         if hook.additional_dependencies.is_empty() {
                             // Use the shared repo environment.
@@ -247,8 +373,9 @@ impl Project {
                     for hook_config in &repo_config.hooks {
                         let repo = Rc::clone(repo);
                         let mut builder = HookBuilder::new(repo, hook_config.clone());
+                        builder.check_language()?;
                         builder.combine(&self.config);
-                        let mut hook = builder.build();
+                        let mut hook = builder.build()?;
 
                         // If the hook doesn't need an environment, don't do any preparation.
                         if hook.language.environment_dir().is_some() {
@@ -265,8 +392,21 @@ impl Project {
                         hooks.push(hook);
                     }
                 }
-                ConfigRepo::Meta(_) => {
-                    todo!()
+                ConfigRepo::Meta(repo_config) => {
+                    for hook_config in &repo_config.hooks {
+                        let hook = repo
+                            .get_hook(hook_config.id.as_str())
+                            .expect("meta hook not found");
+
+                        let repo = Rc::clone(repo);
+                        let mut builder = HookBuilder::new(repo, hook.clone());
+                        builder.update_meta(hook_config);
+                        builder.combine(&self.config);
+                        let hook = builder.build()?;
+
+                        let path = hook.repo.path().to_path_buf();
+                        hooks.push(hook.with_path(path));
+                    }
                 }
             }
         }
@@ -275,6 +415,12 @@ impl Project {
     }
 }
 
+fn hash_content(content: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
+}
+
 struct HookBuilder {
     repo: Rc<Repo>,
     config: ManifestHook,
@@ -314,6 +460,7 @@ impl HookBuilder {
             require_serial,
             stages,
             verbose,
+            work_dir,
             minimum_pre_commit_version,
         );
 
@@ -330,6 +477,43 @@ impl HookBuilder {
         self
     }
 
+    /// Update the meta hook from the project level hook configuration.
+    fn update_meta(&mut self, config: &ConfigMetaHook) -> &mut Self {
+        macro_rules! update_if_some {
+            ($($field:ident),* $(,)?) => {
+                $(
+                if config.$field.is_some() {
+                    self.config.$field.clone_from(&config.$field);
+                }
+                )*
+            };
+        }
+        update_if_some!(alias, files, exclude, stages, verbose);
+
+        if let Some(name) = &config.name {
+            self.config.name.clone_from(name);
+        }
+
+        self
+    }
+
+    /// Check the hook language is supported, before anything relies on it.
+    fn check_language(&self) -> Result<(), Error> {
+        let language = self.config.language;
+        if Language::is_supported(language) {
+            return Ok(());
+        }
+        Err(Error::UnsupportedLanguage {
+            hook: self.config.id.clone(),
+            language,
+            supported: Language::SUPPORTED
+                .iter()
+                .map(config::Language::as_str)
+                .collect::<Vec<_>>()
+                .join(", "),
+        })
+    }
+
     /// Combine the hook configuration with the project level hook configuration.
     fn combine(&mut self, config: &ConfigWire) {
         let language = self.config.language;
@@ -373,9 +557,8 @@ impl HookBuilder {
     }
 
     /// Check the hook configuration.
-    fn check(&self) {
+    fn check(&self) -> Result<(), Error> {
         let language = Language::from(self.config.language);
-        // TODO: check ENVIRONMENT_DIR with language_version and additional_dependencies
         if language.environment_dir().is_none() {
             if self.config.language_version != Some(DEFAULT_VERSION.to_string()) {
                 warn_user!(
@@ -384,21 +567,28 @@ impl HookBuilder {
                 );
             }
 
-            if self.config.additional_dependencies.is_some() {
-                warn_user!(
-                    "Language {} does not need environment, but additional_dependencies is set",
-                    language
-                );
+            // There is no environment to install them in, so the hook would fail to find them.
+            if self
+                .config
+                .additional_dependencies
+                .as_ref()
+                .is_some_and(|deps| !deps.is_empty())
+            {
+                return Err(Error::AdditionalDependenciesNotSupported {
+                    hook: self.config.id.clone(),
+                    language: language.name(),
+                });
             }
         }
+        Ok(())
     }
 
     /// Build the hook.
-    fn build(mut self) -> Hook {
-        self.check();
+    fn build(mut self) -> Result<Hook, Error> {
+        self.check()?;
         self.fill_in_defaults();
 
-        Hook {
+        Ok(Hook {
             repo: self.repo,
             path: None,
             id: self.config.id,
@@ -428,8 +618,9 @@ impl HookBuilder {
             require_serial: self.config.require_serial.expect("require_serial not set"),
             stages: self.config.stages.expect("stages not set"),
             verbose: self.config.verbose.expect("verbose not set"),
+            work_dir: self.config.work_dir,
             minimum_pre_commit_version: self.config.minimum_pre_commit_version,
-        }
+        })
     }
 }
 
@@ -460,6 +651,7 @@ pub struct Hook {
     pub require_serial: bool,
     pub stages: Vec<Stage>,
     pub verbose: bool,
+    pub work_dir: Option<String>,
     pub minimum_pre_commit_version: Option<String>,
 }
 
@@ -498,12 +690,50 @@ impl Hook {
         self.path.as_deref().unwrap_or_else(|| self.repo.path())
     }
 
+    /// Whether the hook explicitly selects directories, e.g. to check submodules.
+    pub fn selects_directories(&self) -> bool {
+        self.types
+            .iter()
+            .chain(&self.types_or)
+            .any(|t| t == tags::DIRECTORY)
+    }
+
+    /// Get the directory to run the hook in, defaults to the repository root.
+    pub fn work_dir(&self) -> &Path {
+        self.work_dir.as_deref().map_or(CWD.as_path(), Path::new)
+    }
+
+    /// Split the entry into the command and its arguments, with shell-like word splitting,
+    /// e.g. `python -m "my tool"` becomes `["python", "-m", "my tool"]`.
+    pub fn entry_argv(&self) -> Result<Vec<String>, Error> {
+        match shlex::split(&self.entry) {
+            Some(argv) if !argv.is_empty() => Ok(argv),
+            _ => Err(Error::InvalidEntry {
+                hook: self.id.clone(),
+                entry: self.entry.clone(),
+            }),
+        }
+    }
+
     /// Get the environment directory that the hook will be installed to.
     pub fn environment_dir(&self) -> Option<PathBuf> {
         let env_dir = self.language.environment_dir()?;
-        Some(
-            self.path()
-                .join(format!("{}-{}", env_dir, &self.language_version)),
+        Some(self.path().join(format!("{env_dir}-{}", self.env_key())))
+    }
+
+    /// The cache key of the hook environment, see [`env_key`].
+    pub fn env_key(&self) -> String {
+        let (url, rev) = match &*self.repo {
+            Repo::Remote { url, rev, .. } => (url.as_str(), rev.as_str()),
+            Repo::Local { .. } => (LOCAL_NAME, ""),
+            Repo::Meta { .. } => ("meta", ""),
+        };
+        env_key(
+            self.language,
+            &self.language_version,
+            url,
+            rev,
+            &self.additional_dependencies,
         )
     }
 
@@ -524,19 +754,193 @@ impl Hook {
             return true;
         };
 
-        let state_file_v2 = env.join(".install_state_v2");
-        state_file_v2.exists()
+        env.join(INSTALL_STATE_FILE).exists()
         // Drop support for state file v1.
     }
 
     /// Write a state file to mark the hook as installed.
+    ///
+    /// The state file is written to a staging path first and renamed into place,
+    /// so an interrupted write never marks a partial environment as installed.
     pub fn mark_installed(&self) -> Result<(), Error> {
         let env = self.environment_dir().unwrap();
-        let state_file_v2 = env.join(".install_state_v2");
-        fs_err::OpenOptions::new()
-            .write(true)
-            .create(true)
-            .open(&state_file_v2)?;
+        let staging = env.join(format!("{INSTALL_STATE_FILE}staging"));
+        fs_err::write(&staging, b"")?;
+        fs_err::rename(&staging, env.join(INSTALL_STATE_FILE))?;
         Ok(())
     }
 }
+
+/// Compute the cache key of a hook environment, used to name its directory.
+///
+/// The key is the hex MD5 digest of `language`, `language_version`, `repo_url`, `rev` and the
+/// sorted additional dependencies, each followed by a NUL byte. Local repos use `local` as url
+/// and an empty rev. The scheme must not change, so the same inputs map to the same directory
+/// across machines and versions, e.g. for caches shared in CI. MD5 is only used for naming here,
+/// it is not meant to resist collisions crafted on purpose.
+pub fn env_key(
+    language: Language,
+    language_version: &str,
+    repo_url: &str,
+    rev: &str,
+    deps: &[String],
+) -> String {
+    let mut deps: Vec<_> = deps.iter().map(String::as_str).collect();
+    deps.sort_unstable();
+
+    let name = language.name();
+    let mut input = String::new();
+    for field in [name.as_str(), language_version, repo_url, rev]
+        .into_iter()
+        .chain(deps)
+    {
+        input.push_str(field);
+        input.push('\0');
+    }
+    format!("{:x}", md5::compute(input))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_config_str() {
+        let project = Project::from_config_str(
+            indoc::indoc! {r"
+                fail_fast: true
+                repos:
+                  - repo: https://github.com/pre-commit/pre-commit-hooks
+                    rev: v5.0.0
+                    hooks:
+                      - id: trailing-whitespace
+                      - id: check-json
+                        args: [--autofix]
+                  - repo: local
+                    hooks:
+                      - id: cargo-fmt
+                        name: cargo fmt
+                        entry: cargo fmt --
+                        language: system
+            "},
+            PathBuf::from(CONFIG_FILE),
+        )
+        .unwrap();
+
+        assert_eq!(project.config_file(), Path::new(CONFIG_FILE));
+        assert_eq!(project.config().fail_fast, Some(true));
+        let hook_ids: Vec<_> = project
+            .config()
+            .repos
+            .iter()
+            .map(ConfigRepo::hook_ids)
+            .collect();
+        assert_eq!(
+            hook_ids,
+            vec![vec!["trailing-whitespace", "check-json"], vec!["cargo-fmt"]]
+        );
+
+        // An empty list of repos is valid.
+        let project = Project::from_config_str("repos: []", PathBuf::from(CONFIG_FILE)).unwrap();
+        assert!(project.config().repos.is_empty());
+    }
+
+    #[test]
+    fn from_config_str_error() {
+        let errors = [
+            // Not YAML.
+            "repos: [",
+            // Missing `repos`.
+            "fail_fast: true",
+            // Remote repo without `rev`.
+            indoc::indoc! {r"
+                repos:
+                  - repo: https://github.com/pre-commit/pre-commit-hooks
+                    hooks:
+                      - id: trailing-whitespace
+            "},
+            // Local hook without `entry`.
+            indoc::indoc! {r"
+                repos:
+                  - repo: local
+                    hooks:
+                      - id: cargo-fmt
+                        name: cargo fmt
+                        language: system
+            "},
+        ];
+        for content in errors {
+            let Err(err) = Project::from_config_str(content, PathBuf::from("config.yaml")) else {
+                panic!("Expected an error for:\n{content}");
+            };
+            assert_eq!(err.to_string(), "Failed to parse `config.yaml`");
+        }
+    }
+
+    #[test]
+    fn init_hooks_cached() -> anyhow::Result<()> {
+        let config = |id: &str| {
+            format!(
+                "repos:\n  - repo: local\n    hooks:\n      - {{ id: {id}, name: {id}, entry: echo, language: system }}\n"
+            )
+        };
+
+        let dir = tempfile::tempdir()?;
+        let config_path = dir.path().join(CONFIG_FILE);
+        fs_err::write(&config_path, config("first"))?;
+        let store = Store::from_path(dir.path().join("store")).init()?;
+        let mut project = Project::new(config_path.clone())?;
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        runtime.block_on(async {
+            let first = project.init_hooks(&store, Printer::Quiet).await?;
+            let second = project.init_hooks(&store, Printer::Quiet).await?;
+            // A cache hit reuses the resolved repos.
+            assert!(Rc::ptr_eq(&first[0].repo, &second[0].repo));
+
+            // Changing the config invalidates the cache.
+            fs_err::write(&config_path, config("changed"))?;
+            let third = project.init_hooks(&store, Printer::Quiet).await?;
+            assert!(!Rc::ptr_eq(&first[0].repo, &third[0].repo));
+            assert_eq!(third[0].id, "changed");
+
+            anyhow::Ok(())
+        })
+    }
+
+    #[test]
+    fn env_key_stable() {
+        let python = Language::from(crate::config::Language::Python);
+        let url = "https://github.com/psf/black";
+
+        // The key is part of the store layout, it must never change for the same inputs.
+        assert_eq!(
+            env_key(python, "default", url, "v24.1.0", &[]),
+            "a95298144bb3dcd7586bfc217be9583e"
+        );
+        let deps = ["click==8.1.7".to_string(), "attrs".to_string()];
+        assert_eq!(
+            env_key(python, "default", url, "v24.1.0", &deps),
+            "0860a0042539c66e9482c85e61f4f01d"
+        );
+
+        // The order of dependencies doesn't matter.
+        let reordered = ["attrs".to_string(), "click==8.1.7".to_string()];
+        assert_eq!(
+            env_key(python, "default", url, "v24.1.0", &deps),
+            env_key(python, "default", url, "v24.1.0", &reordered)
+        );
+
+        // Fields don't run into each other.
+        assert_ne!(
+            env_key(python, "default", url, "v1", &["0".to_string()]),
+            env_key(python, "default", url, "v10", &[])
+        );
+        assert_ne!(
+            env_key(python, "3.12", url, "v24.1.0", &[]),
+            env_key(python, "default", url, "v24.1.0", &[])
+        );
+    }
+}
@@ -6,12 +6,13 @@ use std::os::unix::fs::FileTypeExt;
 #[cfg(unix)]
 use std::os::unix::fs::PermissionsExt;
 use std::path::Path;
-use std::sync::OnceLock;
+use std::sync::{LazyLock, Mutex, OnceLock};
+use std::time::UNIX_EPOCH;
 use std::vec;
 
 use anyhow::Result;
 
-mod tags {
+pub(crate) mod tags {
     pub const DIRECTORY: &str = "directory";
     pub const SYMLINK: &str = "symlink";
     pub const SOCKET: &str = "socket";
@@ -635,8 +636,9 @@ fn is_encoding_tag(tag: &str) -> bool {
     matches!(tag, tags::TEXT | tags::BINARY)
 }
 
-pub fn tags_from_path(path: &Path) -> Result<Vec<&str>> {
-    let metadata = std::fs::metadata(path)?;
+pub fn tags_from_path(path: &Path) -> Result<Vec<&'static str>> {
+    // Don't follow symlinks, so they can be selected by the `symlink` tag.
+    let metadata = std::fs::symlink_metadata(path)?;
     if metadata.is_dir() {
         return Ok(vec![tags::DIRECTORY]);
     } else if metadata.is_symlink() {
@@ -673,8 +675,11 @@ pub fn tags_from_path(path: &Path) -> Result<Vec<&str>> {
         tags.insert(tags::NON_EXECUTABLE);
     }
 
-    tags.extend(tags_from_filename(path));
-    if executable {
+    let filename_tags = tags_from_filename(path);
+    // Scripts without a known extension are identified by their shebang.
+    let check_shebang = executable || filename_tags.is_empty();
+    tags.extend(filename_tags);
+    if check_shebang {
         if let Ok(shebang) = parse_shebang(path) {
             tags.extend(tags_from_interpreter(&shebang));
         }
@@ -691,7 +696,132 @@ pub fn tags_from_path(path: &Path) -> Result<Vec<&str>> {
     Ok(tags.into_iter().collect())
 }
 
-fn tags_from_filename(filename: &Path) -> Vec<&str> {
+/// The tags of a file from a previous run, valid while its modification time, size and mode
+/// are unchanged.
+#[derive(Debug, Clone)]
+pub struct CachedTags {
+    /// Modification time in nanoseconds since the Unix epoch.
+    pub mtime: i64,
+    pub size: u64,
+    /// Unix permission bits, `0` on other platforms.
+    pub mode: u32,
+    pub tags: Vec<&'static str>,
+}
+
+#[derive(Debug, Default)]
+struct TagsCache {
+    entries: HashMap<String, CachedTags>,
+    /// Paths added or updated since the cache was loaded.
+    updated: HashSet<String>,
+}
+
+static TAGS_CACHE: LazyLock<Mutex<TagsCache>> = LazyLock::new(Mutex::default);
+
+/// Load the tags cache, e.g. from the store. Entries with unknown tags are dropped.
+pub fn load_tags_cache(entries: impl IntoIterator<Item = (String, i64, u64, u32, String)>) {
+    let entries = entries
+        .into_iter()
+        .filter_map(|(path, mtime, size, mode, tags)| {
+            let tags = tags.split(',').map(intern_tag).collect::<Option<_>>()?;
+            Some((
+                path,
+                CachedTags {
+                    mtime,
+                    size,
+                    mode,
+                    tags,
+                },
+            ))
+        })
+        .collect();
+
+    let mut cache = TAGS_CACHE.lock().unwrap();
+    cache.entries = entries;
+    cache.updated.clear();
+}
+
+/// Take the entries added or updated since the cache was loaded, to be saved.
+pub fn take_updated_tags() -> Vec<(String, CachedTags)> {
+    let mut cache = TAGS_CACHE.lock().unwrap();
+    let updated = std::mem::take(&mut cache.updated);
+    updated
+        .into_iter()
+        .filter_map(|path| {
+            let cached = cache.entries.get(&path)?.clone();
+            Some((path, cached))
+        })
+        .collect()
+}
+
+/// Get the static tag equal to `tag`, for tags read back from the cache.
+fn intern_tag(tag: &str) -> Option<&'static str> {
+    static ALL: OnceLock<HashSet<&'static str>> = OnceLock::new();
+    ALL.get_or_init(|| {
+        let mut all: HashSet<&'static str> = [
+            tags::DIRECTORY,
+            tags::SYMLINK,
+            tags::SOCKET,
+            tags::FIFO,
+            tags::BLOCK_DEVICE,
+            tags::CHARACTER_DEVICE,
+            tags::FILE,
+            tags::EXECUTABLE,
+            tags::NON_EXECUTABLE,
+            tags::TEXT,
+            tags::BINARY,
+        ]
+        .into_iter()
+        .collect();
+        all.extend(by_extension().values().flatten().copied());
+        all.extend(by_filename().values().flat_map(|tags| tags.iter()).copied());
+        all.extend(by_interpreter().values().flatten().copied());
+        all
+    })
+    .get(tag)
+    .copied()
+}
+
+/// Like [`tags_from_path`], but the tags of regular files are cached by path, modification time,
+/// size and mode, so file contents are not read again for shebang and binary detection.
+pub fn tags_from_path_cached(path: &Path) -> Result<Vec<&'static str>> {
+    let metadata = std::fs::symlink_metadata(path)?;
+    let mtime = metadata
+        .modified()
+        .ok()
+        .and_then(|mtime| mtime.duration_since(UNIX_EPOCH).ok())
+        .and_then(|mtime| i64::try_from(mtime.as_nanos()).ok());
+    let (true, Some(mtime)) = (metadata.is_file(), mtime) else {
+        return tags_from_path(path);
+    };
+    let size = metadata.len();
+    #[cfg(unix)]
+    let mode = metadata.permissions().mode();
+    #[cfg(not(unix))]
+    let mode = 0;
+
+    let key = path.to_string_lossy();
+    if let Some(cached) = TAGS_CACHE.lock().unwrap().entries.get(key.as_ref()) {
+        if cached.mtime == mtime && cached.size == size && cached.mode == mode {
+            return Ok(cached.tags.clone());
+        }
+    }
+
+    let tags = tags_from_path(path)?;
+    let mut cache = TAGS_CACHE.lock().unwrap();
+    cache.entries.insert(
+        key.to_string(),
+        CachedTags {
+            mtime,
+            size,
+            mode,
+            tags: tags.clone(),
+        },
+    );
+    cache.updated.insert(key.into_owned());
+    Ok(tags)
+}
+
+fn tags_from_filename(filename: &Path) -> Vec<&'static str> {
     let ext = filename.extension().and_then(|ext| ext.to_str());
     let filename = filename
         .file_name()
@@ -721,8 +851,23 @@ fn tags_from_filename(filename: &Path) -> Vec<&str> {
     result.into_iter().collect()
 }
 
-fn tags_from_interpreter(_interpreter: &[String]) -> Vec<&'static str> {
-    todo!()
+fn tags_from_interpreter(interpreter: &[String]) -> Vec<&'static str> {
+    let Some(interpreter) = interpreter.first() else {
+        return vec![];
+    };
+    let mut name = interpreter
+        .rsplit_once('/')
+        .map_or(interpreter.as_str(), |(_, name)| name);
+
+    // Try "python3.5.2" => "python3.5" => "python3" until one matches.
+    while !name.is_empty() {
+        if let Some(tags) = by_interpreter().get(name) {
+            return tags.clone();
+        }
+        name = name.rsplit_once('.').map_or("", |(name, _)| name);
+    }
+
+    vec![]
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -741,12 +886,16 @@ enum ShebangError {
 
 fn parse_shebang(path: &Path) -> Result<Vec<String>, ShebangError> {
     let file = std::fs::File::open(path)?;
-    let mut reader = std::io::BufReader::new(file);
-    let mut line = String::new();
-    reader.read_line(&mut line)?;
-    if !line.starts_with("#!") {
+    // Only look at the first KB, binary files may not have a line break at all.
+    let mut reader = std::io::BufReader::new(file.take(1024));
+    let mut magic = [0; 2];
+    if reader.read_exact(&mut magic).is_err() || magic != *b"#!" {
         return Err(ShebangError::NoShebang);
     }
+    let mut line = String::from("#!");
+    // Fails on invalid UTF-8, e.g. binary files.
+    reader.read_line(&mut line)?;
+    let line = line.trim_end_matches(['\r', '\n']);
 
     // Require only printable ASCII
     if line.bytes().any(|b| !(0x20..=0x7E).contains(&b)) {
@@ -804,6 +953,12 @@ fn is_text_file(path: &Path) -> bool {
 mod tests {
     use std::path::Path;
 
+    fn sorted_tags(path: &Path) -> anyhow::Result<Vec<&str>> {
+        let mut tags = super::tags_from_path(path)?;
+        tags.sort_unstable();
+        Ok(tags)
+    }
+
     #[test]
     fn tags_from_filename() {
         let tags = super::tags_from_filename(Path::new("test.py"));
@@ -811,4 +966,114 @@ mod tests {
         let tags = super::tags_from_filename(Path::new("data.json"));
         assert_eq!(tags, vec!["json", "text"]);
     }
+
+    #[test]
+    #[cfg(unix)]
+    fn tags_from_path() -> anyhow::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir()?;
+
+        let script = dir.path().join("script.sh");
+        fs_err::write(&script, "echo hello\n")?;
+        fs_err::set_permissions(&script, std::fs::Permissions::from_mode(0o755))?;
+        assert_eq!(
+            sorted_tags(&script)?,
+            vec!["executable", "file", "shell", "text"]
+        );
+
+        let link = dir.path().join("link.sh");
+        std::os::unix::fs::symlink(&script, &link)?;
+        assert_eq!(sorted_tags(&link)?, vec!["symlink"]);
+
+        // A submodule is checked out as a directory.
+        let submodule = dir.path().join("submodule");
+        fs_err::create_dir(&submodule)?;
+        assert_eq!(sorted_tags(&submodule)?, vec!["directory"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn tags_from_shebang() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+
+        let python = dir.path().join("python-script");
+        fs_err::write(&python, "#!/usr/bin/env python3\nprint('hello')\n")?;
+        assert_eq!(
+            sorted_tags(&python)?,
+            vec!["file", "non-executable", "python", "python3", "text"]
+        );
+
+        let bash = dir.path().join("bash-script");
+        fs_err::write(&bash, "#!/bin/bash\necho hello\n")?;
+        assert_eq!(
+            sorted_tags(&bash)?,
+            vec!["bash", "file", "non-executable", "shell", "text"]
+        );
+
+        let ruby = dir.path().join("ruby-script");
+        fs_err::write(&ruby, "#!/usr/bin/env ruby\nputs 'hello'\n")?;
+        assert_eq!(
+            sorted_tags(&ruby)?,
+            vec!["file", "non-executable", "ruby", "text"]
+        );
+
+        let binary = dir.path().join("binary");
+        fs_err::write(&binary, b"#!\xff\xfe\x00\x01")?;
+        assert_eq!(
+            sorted_tags(&binary)?,
+            vec!["binary", "file", "non-executable"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn tags_from_path_cached() -> anyhow::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let cached_tags = |path: &Path| -> anyhow::Result<Vec<&'static str>> {
+            let mut tags = super::tags_from_path_cached(path)?;
+            tags.sort_unstable();
+            Ok(tags)
+        };
+
+        let dir = tempfile::tempdir()?;
+        let script = dir.path().join("script");
+        fs_err::write(&script, "#!/usr/bin/env python3\nprint('hello')\n")?;
+        assert_eq!(
+            cached_tags(&script)?,
+            vec!["file", "non-executable", "python", "python3", "text"]
+        );
+        // Served from the cache.
+        assert_eq!(cached_tags(&script)?, sorted_tags(&script)?);
+
+        // A change of content or mode invalidates the entry.
+        fs_err::write(&script, "#!/bin/bash\necho hello\n")?;
+        assert_eq!(
+            cached_tags(&script)?,
+            vec!["bash", "file", "non-executable", "shell", "text"]
+        );
+        fs_err::set_permissions(&script, std::fs::Permissions::from_mode(0o755))?;
+        assert_eq!(
+            cached_tags(&script)?,
+            vec!["bash", "executable", "file", "shell", "text"]
+        );
+
+        // Updated entries round-trip through the store format.
+        let updated = super::take_updated_tags();
+        let (path, cached) = updated
+            .into_iter()
+            .find(|(path, _)| Path::new(path) == script)
+            .expect("script is updated");
+        let mut tags = cached.tags.clone();
+        tags.sort_unstable();
+        super::load_tags_cache([(path, cached.mtime, cached.size, cached.mode, tags.join(","))]);
+        assert_eq!(cached_tags(&script)?, tags);
+        assert!(super::take_updated_tags().is_empty());
+
+        Ok(())
+    }
 }
@@ -127,7 +127,7 @@ impl Docker {
         Ok(Cow::Borrowed(path))
     }
 
-    async fn docker_cmd() -> Result<Cmd> {
+    async fn docker_cmd(work_dir: Option<&str>) -> Result<Cmd> {
         let mut command = Cmd::new("docker", "run container");
         command.arg("run").arg("--rm");
 
@@ -155,7 +155,7 @@ impl Docker {
                 Self::get_docker_path(&CWD.to_string_lossy()).await?
             ))
             .arg("--workdir")
-            .arg("/src");
+            .arg(work_dir.map_or_else(|| "/src".to_string(), |dir| format!("/src/{dir}")));
 
         Ok(command)
     }
@@ -196,20 +196,22 @@ impl LanguageImpl for Docker {
 
         let docker_tag = Docker::docker_tag(hook).expect("Failed to get docker tag");
 
-        let cmds = shlex::split(&hook.entry).ok_or(anyhow::anyhow!("Failed to parse entry"))?;
+        let cmds = hook.entry_argv()?;
 
         let cmds = Arc::new(cmds);
         let hook_args = Arc::new(hook.args.clone());
+        let work_dir = Arc::new(hook.work_dir.clone());
 
         let run = move |batch: Vec<String>| {
             let cmds = cmds.clone();
             let docker_tag = docker_tag.clone();
             let hook_args = hook_args.clone();
+            let work_dir = work_dir.clone();
             let env_vars = env_vars.clone();
 
             async move {
                 // docker run [OPTIONS] IMAGE [COMMAND] [ARG...]
-                let mut cmd = Docker::docker_cmd().await?;
+                let mut cmd = Docker::docker_cmd(work_dir.as_deref()).await?;
                 let cmd = cmd
                     .arg("--entrypoint")
                     .arg(&cmds[0])
@@ -217,11 +219,11 @@ impl LanguageImpl for Docker {
                     .args(&cmds[1..])
                     .args(hook_args.as_ref())
                     .args(batch)
-                    .stderr(std::process::Stdio::inherit())
-                    .envs(env_vars.as_ref());
+                    .envs(env_vars.as_ref())
+                    .check(false)
+                    .merge_stderr();
 
-                let mut output = cmd.output().await?;
-                output.stdout.extend(output.stderr);
+                let output = cmd.output().await?;
                 let code = output.status.code().unwrap_or(1);
                 anyhow::Ok((code, output.stdout))
             }
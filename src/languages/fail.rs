@@ -34,13 +34,29 @@ impl LanguageImpl for Fail {
         filenames: &[&String],
         _env_vars: Arc<HashMap<&'static str, String>>,
     ) -> anyhow::Result<(i32, Vec<u8>)> {
-        let mut out = hook.entry.as_bytes().to_vec();
-        out.extend(b"\n\n");
-        for f in filenames {
-            out.extend(f.as_bytes());
+        // Only fail for matched files, e.g. when run with `always_run`.
+        if filenames.is_empty() {
+            return Ok((0, Vec::new()));
+        }
+
+        let mut out = Vec::new();
+        if hook.verbose {
+            // Report the reason for each file.
+            for f in filenames {
+                out.extend(f.as_bytes());
+                out.extend(b": ");
+                out.extend(hook.entry.as_bytes());
+                out.push(b'\n');
+            }
+        } else {
+            out.extend(hook.entry.as_bytes());
+            out.extend(b"\n\n");
+            for f in filenames {
+                out.extend(f.as_bytes());
+                out.push(b'\n');
+            }
             out.push(b'\n');
         }
-        out.push(b'\n');
 
         Ok((1, out))
     }
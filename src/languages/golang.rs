@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::config;
+use crate::hook::Hook;
+use crate::languages::{hook_cmd, LanguageImpl, DEFAULT_VERSION};
+use crate::process::Cmd;
+use crate::run::run_by_batch;
+
+#[derive(Debug, Copy, Clone)]
+pub struct Golang;
+
+impl LanguageImpl for Golang {
+    fn name(&self) -> config::Language {
+        config::Language::Golang
+    }
+
+    fn default_version(&self) -> &str {
+        DEFAULT_VERSION
+    }
+
+    fn environment_dir(&self) -> Option<&str> {
+        Some("golangenv")
+    }
+
+    async fn install(&self, hook: &Hook) -> anyhow::Result<()> {
+        let env = hook.environment_dir().expect("No environment dir found");
+        fs_err::create_dir_all(&env)?;
+
+        let toolchain = toolchain(hook);
+        if hook.path().join("go.mod").try_exists()? {
+            go(&env, &toolchain, "install go module")
+                .arg("install")
+                .arg("./...")
+                .current_dir(hook.path())
+                .check(true)
+                .output()
+                .await?;
+        }
+        for dep in &hook.additional_dependencies {
+            go(&env, &toolchain, "install go dependency")
+                .arg("install")
+                .arg(dep)
+                .current_dir(hook.path())
+                .check(true)
+                .output()
+                .await?;
+        }
+
+        // Only the binaries are needed, remove the module and build cache.
+        let pkg = env.join("pkg");
+        if pkg.try_exists()? {
+            fs_err::remove_dir_all(pkg)?;
+        }
+
+        Ok(())
+    }
+
+    async fn check_health(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn run(
+        &self,
+        hook: &Hook,
+        filenames: &[&String],
+        env_vars: Arc<HashMap<&'static str, String>>,
+    ) -> anyhow::Result<(i32, Vec<u8>)> {
+        let env_dir = hook.environment_dir().expect("No environment dir for Go");
+
+        let cmds = hook.entry_argv()?;
+
+        // Binaries are installed to `$GOBIN`.
+        let new_path = std::env::join_paths(
+            std::iter::once(env_dir.join("bin")).chain(
+                std::env::var_os("PATH")
+                    .as_ref()
+                    .iter()
+                    .flat_map(std::env::split_paths),
+            ),
+        )?;
+
+        let cmds = Arc::new(cmds);
+        let hook_args = Arc::new(hook.args.clone());
+        let new_path = Arc::new(new_path);
+        let work_dir = Arc::new(hook.work_dir().to_path_buf());
+
+        let run = move |batch: Vec<String>| {
+            let cmds = cmds.clone();
+            let hook_args = hook_args.clone();
+            let new_path = new_path.clone();
+            let work_dir = work_dir.clone();
+            let env_vars = env_vars.clone();
+
+            async move {
+                let output = hook_cmd(&cmds[0], "run go command")
+                    .args(&cmds[1..])
+                    .env("PATH", new_path.as_ref())
+                    .envs(env_vars.as_ref())
+                    .args(hook_args.as_slice())
+                    .args(batch)
+                    .current_dir(work_dir.as_ref())
+                    .check(false)
+                    .merge_stderr()
+                    .output()
+                    .await?;
+
+                let code = output.status.code().unwrap_or(1);
+                anyhow::Ok((code, output.stdout))
+            }
+        };
+
+        let results = run_by_batch(hook, filenames, run).await?;
+
+        // Collect results
+        let mut combined_status = 0;
+        let mut combined_output = Vec::new();
+
+        for (code, output) in results {
+            combined_status |= code;
+            combined_output.extend(output);
+        }
+
+        Ok((combined_status, combined_output))
+    }
+}
+
+/// The value of `GOTOOLCHAIN`, `local` means the system `go`.
+/// Other versions are downloaded by `go` itself (requires go 1.21+).
+fn toolchain(hook: &Hook) -> String {
+    match hook.language_version.as_str() {
+        DEFAULT_VERSION | "system" => "local".to_string(),
+        version if version.starts_with("go") => version.to_string(),
+        version => format!("go{version}"),
+    }
+}
+
+/// A `go` command that builds into an isolated `GOPATH` in the environment.
+fn go(env: &Path, toolchain: &str, summary: &str) -> Cmd {
+    let mut cmd = Cmd::new("go", summary);
+    cmd.env("GOPATH", env)
+        .env("GOBIN", env.join("bin"))
+        .env("GOTOOLCHAIN", toolchain)
+        // Make the module cache writable, so it can be removed after installing.
+        .env("GOFLAGS", "-modcacherw");
+    cmd
+}
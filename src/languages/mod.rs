@@ -1,16 +1,25 @@
 use std::collections::HashMap;
+use std::ffi::OsStr;
 use std::fmt::{Debug, Display};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use anyhow::Result;
 
 use crate::config;
+use crate::git::HOOK_GIT_ENV_REMOVE;
 use crate::hook::Hook;
+use crate::process::{self, Cmd};
 
 mod docker;
 mod fail;
+mod golang;
 mod node;
+mod pygrep;
 mod python;
+mod ruby;
+mod rust;
+mod script;
 mod system;
 
 pub const DEFAULT_VERSION: &str = "default";
@@ -29,6 +38,46 @@ trait LanguageImpl {
     ) -> Result<(i32, Vec<u8>)>;
 }
 
+/// Create a command to run a hook entry, without the `GIT_*` env vars set by git for hooks.
+fn hook_cmd(program: impl AsRef<OsStr>, summary: &str) -> Cmd {
+    let mut cmd = Cmd::new(program, summary);
+    for key in HOOK_GIT_ENV_REMOVE.iter() {
+        cmd.env_remove(key);
+    }
+    cmd
+}
+
+/// Turn a hook entry that can't be spawned because its program is missing into a failed hook,
+/// explaining where the program was searched, instead of aborting the run with a raw `ENOENT`.
+fn entry_not_found(err: process::Error, program: &str) -> Result<(i32, Vec<u8>)> {
+    let process::Error::Exec { cause, .. } = &err else {
+        return Err(err.into());
+    };
+    if cause.kind() != std::io::ErrorKind::NotFound {
+        return Err(err.into());
+    }
+
+    let path = Path::new(program);
+    let message = if path.components().count() > 1 {
+        if path.exists() {
+            // The program itself exists, so its `#!` interpreter is what's missing.
+            format!("Executable `{program}` exists, but its interpreter was not found, check its shebang line\n")
+        } else {
+            format!("Executable `{program}` not found\n")
+        }
+    } else {
+        let search_path = std::env::var_os("PATH").unwrap_or_default();
+        format!(
+            "Executable `{program}` not found in PATH\n\
+             PATH: {}\n\
+             hint: Install `{program}` so it's on PATH, or use the language of the tool, \
+             e.g. `python`, and add it to `additional_dependencies`\n",
+            search_path.to_string_lossy()
+        )
+    };
+    Ok((1, message.into_bytes()))
+}
+
 #[derive(Debug, Copy, Clone)]
 pub enum Language {
     Python(python::Python),
@@ -36,6 +85,11 @@ pub enum Language {
     System(system::System),
     Fail(fail::Fail),
     Docker(docker::Docker),
+    Rust(rust::Rust),
+    Golang(golang::Golang),
+    Ruby(ruby::Ruby),
+    Pygrep(pygrep::Pygrep),
+    Script(script::Script),
 }
 
 impl From<config::Language> for Language {
@@ -48,20 +102,22 @@ impl From<config::Language> for Language {
             // config::Language::DockerImage => Language::DockerImage,
             // config::Language::Dotnet => Language::Dotnet,
             config::Language::Fail => Language::Fail(fail::Fail),
-            // config::Language::Golang => Language::Golang,
+            config::Language::Golang => Language::Golang(golang::Golang),
             // config::Language::Haskell => Language::Haskell,
             // config::Language::Lua => Language::Lua,
             config::Language::Node => Language::Node(node::Node),
             // config::Language::Perl => Language::Perl,
             config::Language::Python => Language::Python(python::Python),
             // config::Language::R => Language::R,
-            // config::Language::Ruby => Language::Ruby,
-            // config::Language::Rust => Language::Rust,
+            config::Language::Ruby => Language::Ruby(ruby::Ruby),
+            config::Language::Rust => Language::Rust(rust::Rust),
             // config::Language::Swift => Language::Swift,
-            // config::Language::Pygrep => Language::Pygrep,
-            // config::Language::Script => Language::Script,
+            config::Language::Pygrep => Language::Pygrep(pygrep::Pygrep),
+            config::Language::Script => Language::Script(script::Script),
             config::Language::System => Language::System(system::System),
-            _ => todo!("Not implemented yet"),
+            _ => unreachable!(
+                "Language `{language}` is not supported, check `Language::is_supported` first"
+            ),
         }
     }
 }
@@ -74,11 +130,33 @@ impl Display for Language {
             Self::System(system) => system.fmt(f),
             Self::Fail(fail) => fail.fmt(f),
             Self::Docker(docker) => docker.fmt(f),
+            Self::Rust(rust) => rust.fmt(f),
+            Self::Golang(golang) => golang.fmt(f),
+            Self::Ruby(ruby) => ruby.fmt(f),
+            Self::Pygrep(pygrep) => pygrep.fmt(f),
+            Self::Script(script) => script.fmt(f),
         }
     }
 }
 
 impl Language {
+    /// Languages that hooks can be run with, in the order they are listed to the user.
+    pub const SUPPORTED: &'static [config::Language] = &[
+        config::Language::Docker,
+        config::Language::Fail,
+        config::Language::Golang,
+        config::Language::Pygrep,
+        config::Language::Python,
+        config::Language::Ruby,
+        config::Language::Rust,
+        config::Language::Script,
+        config::Language::System,
+    ];
+
+    pub fn is_supported(language: config::Language) -> bool {
+        Self::SUPPORTED.contains(&language)
+    }
+
     pub fn name(self) -> config::Language {
         match self {
             Self::Python(python) => python.name(),
@@ -86,6 +164,11 @@ impl Language {
             Self::System(system) => system.name(),
             Self::Fail(fail) => fail.name(),
             Self::Docker(docker) => docker.name(),
+            Self::Rust(rust) => rust.name(),
+            Self::Golang(golang) => golang.name(),
+            Self::Ruby(ruby) => ruby.name(),
+            Self::Pygrep(pygrep) => pygrep.name(),
+            Self::Script(script) => script.name(),
         }
     }
 
@@ -96,6 +179,11 @@ impl Language {
             Self::System(system) => system.default_version(),
             Self::Fail(fail) => fail.default_version(),
             Self::Docker(docker) => docker.default_version(),
+            Self::Rust(rust) => rust.default_version(),
+            Self::Golang(golang) => golang.default_version(),
+            Self::Ruby(ruby) => ruby.default_version(),
+            Self::Pygrep(pygrep) => pygrep.default_version(),
+            Self::Script(script) => script.default_version(),
         }
     }
 
@@ -106,6 +194,27 @@ impl Language {
             Self::System(system) => system.environment_dir(),
             Self::Fail(fail) => fail.environment_dir(),
             Self::Docker(docker) => docker.environment_dir(),
+            Self::Rust(rust) => rust.environment_dir(),
+            Self::Golang(golang) => golang.environment_dir(),
+            Self::Ruby(ruby) => ruby.environment_dir(),
+            Self::Pygrep(pygrep) => pygrep.environment_dir(),
+            Self::Script(script) => script.environment_dir(),
+        }
+    }
+
+    /// The directory of the executables installed in a hook environment, which is put first in
+    /// `PATH` when the hook runs.
+    pub fn bin_dir(self, env_dir: &Path) -> Option<PathBuf> {
+        match self {
+            Self::Python(_) => Some(python::bin_dir(env_dir)),
+            Self::Golang(_) | Self::Rust(_) => Some(env_dir.join("bin")),
+            Self::Ruby(_) => Some(env_dir.join("gems").join("bin")),
+            Self::Node(_)
+            | Self::System(_)
+            | Self::Fail(_)
+            | Self::Docker(_)
+            | Self::Pygrep(_)
+            | Self::Script(_) => None,
         }
     }
 
@@ -116,6 +225,11 @@ impl Language {
             Self::System(system) => system.install(hook).await,
             Self::Fail(fail) => fail.install(hook).await,
             Self::Docker(docker) => docker.install(hook).await,
+            Self::Rust(rust) => rust.install(hook).await,
+            Self::Golang(golang) => golang.install(hook).await,
+            Self::Ruby(ruby) => ruby.install(hook).await,
+            Self::Pygrep(pygrep) => pygrep.install(hook).await,
+            Self::Script(script) => script.install(hook).await,
         }
     }
 
@@ -126,6 +240,11 @@ impl Language {
             Self::System(system) => system.check_health().await,
             Self::Fail(fail) => fail.check_health().await,
             Self::Docker(docker) => docker.check_health().await,
+            Self::Rust(rust) => rust.check_health().await,
+            Self::Golang(golang) => golang.check_health().await,
+            Self::Ruby(ruby) => ruby.check_health().await,
+            Self::Pygrep(pygrep) => pygrep.check_health().await,
+            Self::Script(script) => script.check_health().await,
         }
     }
 
@@ -141,6 +260,11 @@ impl Language {
             Self::System(system) => system.run(hook, filenames, env_vars).await,
             Self::Fail(fail) => fail.run(hook, filenames, env_vars).await,
             Self::Docker(docker) => docker.run(hook, filenames, env_vars).await,
+            Self::Rust(rust) => rust.run(hook, filenames, env_vars).await,
+            Self::Golang(golang) => golang.run(hook, filenames, env_vars).await,
+            Self::Ruby(ruby) => ruby.run(hook, filenames, env_vars).await,
+            Self::Pygrep(pygrep) => pygrep.run(hook, filenames, env_vars).await,
+            Self::Script(script) => script.run(hook, filenames, env_vars).await,
         }
     }
 }
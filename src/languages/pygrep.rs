@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::Arc;
+
+use anyhow::Context;
+use fancy_regex::Regex;
+
+use crate::config;
+use crate::hook::Hook;
+use crate::languages::{LanguageImpl, DEFAULT_VERSION};
+
+#[derive(Debug, Copy, Clone)]
+pub struct Pygrep;
+
+#[derive(Debug, Default)]
+struct Args {
+    ignore_case: bool,
+    multiline: bool,
+    negate: bool,
+}
+
+impl Args {
+    fn parse(args: &[String]) -> anyhow::Result<Self> {
+        let mut parsed = Self::default();
+        for arg in args {
+            match arg.as_str() {
+                "-i" | "--ignore-case" => parsed.ignore_case = true,
+                "--multiline" => parsed.multiline = true,
+                "--negate" => parsed.negate = true,
+                _ => anyhow::bail!("Unknown argument for pygrep: `{arg}`"),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+impl LanguageImpl for Pygrep {
+    fn name(&self) -> config::Language {
+        config::Language::Pygrep
+    }
+
+    fn default_version(&self) -> &str {
+        DEFAULT_VERSION
+    }
+
+    fn environment_dir(&self) -> Option<&str> {
+        None
+    }
+
+    async fn install(&self, _hook: &Hook) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn check_health(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn run(
+        &self,
+        hook: &Hook,
+        filenames: &[&String],
+        _env_vars: Arc<HashMap<&'static str, String>>,
+    ) -> anyhow::Result<(i32, Vec<u8>)> {
+        let args = Args::parse(&hook.args)?;
+
+        // Same flags as Python's `re.IGNORECASE` and `re.MULTILINE | re.DOTALL`.
+        let mut flags = String::new();
+        if args.ignore_case {
+            flags.push('i');
+        }
+        if args.multiline {
+            flags.push_str("ms");
+        }
+        let pattern = if flags.is_empty() {
+            hook.entry.clone()
+        } else {
+            format!("(?{flags}){}", hook.entry)
+        };
+        let regex = Regex::new(&pattern)
+            .with_context(|| format!("Invalid pygrep pattern: `{}`", hook.entry))?;
+
+        let mut output = String::new();
+        let mut matches = 0;
+        let mut reported_files = 0;
+        for filename in filenames {
+            let content = fs_err::read(hook.work_dir().join(filename))?;
+            let content = String::from_utf8_lossy(&content);
+
+            let count = if args.multiline {
+                grep_multiline(&regex, filename, &content, !args.negate, &mut output)?
+            } else {
+                grep_lines(&regex, filename, &content, !args.negate, &mut output)?
+            };
+
+            if args.negate {
+                if count == 0 {
+                    reported_files += 1;
+                    if hook.verbose {
+                        writeln!(output, "{filename}: no match for `{}`", hook.entry)?;
+                    } else {
+                        writeln!(output, "{filename}")?;
+                    }
+                }
+            } else if count > 0 {
+                matches += count;
+                reported_files += 1;
+            }
+        }
+
+        if hook.verbose {
+            if args.negate {
+                writeln!(
+                    output,
+                    "{reported_files} of {} file(s) don't match `{}`",
+                    filenames.len(),
+                    hook.entry
+                )?;
+            } else {
+                writeln!(
+                    output,
+                    "{matches} match(es) of `{}` in {reported_files} of {} file(s)",
+                    hook.entry,
+                    filenames.len()
+                )?;
+            }
+        }
+
+        let code = i32::from(reported_files > 0);
+        Ok((code, output.into_bytes()))
+    }
+}
+
+/// Search each line, reports the matched lines as `filename:line:content`.
+/// Returns the number of matches, stops at the first match if not reporting.
+fn grep_lines(
+    regex: &Regex,
+    filename: &str,
+    content: &str,
+    report: bool,
+    output: &mut String,
+) -> anyhow::Result<usize> {
+    let mut matches = 0;
+    for (idx, line) in content.lines().enumerate() {
+        if regex.is_match(line)? {
+            matches += 1;
+            if !report {
+                break;
+            }
+            writeln!(output, "{filename}:{}:{line}", idx + 1)?;
+        }
+    }
+    Ok(matches)
+}
+
+/// Search the whole content, reports each match as `filename:line:` followed by the matched lines.
+/// Returns the number of matches, stops at the first match if not reporting.
+fn grep_multiline(
+    regex: &Regex,
+    filename: &str,
+    content: &str,
+    report: bool,
+    output: &mut String,
+) -> anyhow::Result<usize> {
+    let mut matches = 0;
+    for m in regex.find_iter(content) {
+        let m = m?;
+        matches += 1;
+        if !report {
+            break;
+        }
+
+        // The first line is reported in full, the rest as matched.
+        let line_no = content[..m.start()].matches('\n').count();
+        let mut lines = m.as_str().split('\n');
+        lines.next();
+        let first = content.split('\n').nth(line_no).unwrap_or_default();
+
+        write!(output, "{filename}:{}:{first}", line_no + 1)?;
+        for line in lines {
+            write!(output, "\n{line}")?;
+        }
+        output.push('\n');
+    }
+    Ok(matches)
+}
@@ -5,7 +5,7 @@ use std::sync::Arc;
 use crate::config;
 use crate::hook::Hook;
 use crate::languages::python::uv::ensure_uv;
-use crate::languages::LanguageImpl;
+use crate::languages::{hook_cmd, LanguageImpl};
 use crate::process::Cmd;
 use crate::run::run_by_batch;
 
@@ -76,8 +76,7 @@ impl LanguageImpl for Python {
             .environment_dir()
             .expect("No environment dir for Python");
 
-        let cmds = shlex::split(&hook.entry)
-            .ok_or_else(|| anyhow::anyhow!("Failed to parse entry command"))?;
+        let cmds = hook.entry_argv()?;
 
         // Construct PATH with venv bin directory first
         let new_path = std::env::join_paths(
@@ -93,6 +92,7 @@ impl LanguageImpl for Python {
         let hook_args = Arc::new(hook.args.clone());
         let env_dir = Arc::new(env_dir.clone());
         let new_path = Arc::new(new_path);
+        let work_dir = Arc::new(hook.work_dir().to_path_buf());
 
         let run = move |batch: Vec<String>| {
             // This closure should be Fn, as it is called for each batch. We need to clone the variables,
@@ -101,11 +101,12 @@ impl LanguageImpl for Python {
             let hook_args = hook_args.clone();
             let env_dir = env_dir.clone();
             let new_path = new_path.clone();
+            let work_dir = work_dir.clone();
             let env_vars = env_vars.clone();
 
             // TODO: combine stdout and stderr
             async move {
-                let mut output = Cmd::new(&cmds[0], "run python command")
+                let output = hook_cmd(&cmds[0], "run python command")
                     .args(&cmds[1..])
                     .env("VIRTUAL_ENV", env_dir.as_ref())
                     .env("PATH", new_path.as_ref())
@@ -113,11 +114,12 @@ impl LanguageImpl for Python {
                     .envs(env_vars.as_ref())
                     .args(hook_args.as_slice())
                     .args(batch)
+                    .current_dir(work_dir.as_ref())
                     .check(false)
+                    .merge_stderr()
                     .output()
                     .await?;
 
-                output.stdout.extend(output.stderr);
                 let code = output.status.code().unwrap_or(1);
                 anyhow::Ok((code, output.stdout))
             }
@@ -138,7 +140,7 @@ impl LanguageImpl for Python {
     }
 }
 
-fn bin_dir(venv: &Path) -> PathBuf {
+pub(crate) fn bin_dir(venv: &Path) -> PathBuf {
     if cfg!(windows) {
         venv.join("Scripts")
     } else {
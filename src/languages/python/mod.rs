@@ -1,4 +1,5 @@
 mod r#impl;
 mod uv;
 
+pub(crate) use r#impl::bin_dir;
 pub use r#impl::Python;
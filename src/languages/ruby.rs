@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::config;
+use crate::hook::Hook;
+use crate::languages::{hook_cmd, LanguageImpl, DEFAULT_VERSION};
+use crate::process::Cmd;
+use crate::run::run_by_batch;
+
+#[derive(Debug, Copy, Clone)]
+pub struct Ruby;
+
+impl LanguageImpl for Ruby {
+    fn name(&self) -> config::Language {
+        config::Language::Ruby
+    }
+
+    fn default_version(&self) -> &str {
+        DEFAULT_VERSION
+    }
+
+    fn environment_dir(&self) -> Option<&str> {
+        Some("rbenv")
+    }
+
+    async fn install(&self, hook: &Hook) -> anyhow::Result<()> {
+        let env = hook.environment_dir().expect("No environment dir found");
+        let gem_home = env.join("gems");
+        fs_err::create_dir_all(&gem_home)?;
+
+        let version = ruby_version(hook);
+        if let Some(version) = version {
+            Cmd::new("rbenv", "install ruby")
+                .arg("install")
+                .arg("--skip-existing")
+                .arg(version)
+                .check(true)
+                .output()
+                .await?;
+        }
+
+        // Build the gems of the hook repo, they are installed together with the
+        // additional dependencies.
+        let mut gemspecs = Vec::new();
+        for entry in fs_err::read_dir(hook.path())? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "gemspec") {
+                gemspecs.push(path);
+            }
+        }
+        for gemspec in &gemspecs {
+            gem(&gem_home, version, "build gem")
+                .arg("build")
+                .arg(gemspec)
+                .current_dir(hook.path())
+                .check(true)
+                .output()
+                .await?;
+        }
+
+        let mut gems = Vec::new();
+        for entry in fs_err::read_dir(hook.path())? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "gem") {
+                gems.push(path.into_os_string());
+            }
+        }
+        gems.extend(hook.additional_dependencies.iter().map(Into::into));
+
+        if !gems.is_empty() {
+            gem(&gem_home, version, "install gems")
+                .arg("install")
+                .arg("--no-document")
+                .arg("--no-format-executable")
+                .arg("--no-user-install")
+                .arg("--install-dir")
+                .arg(&gem_home)
+                .arg("--bindir")
+                .arg(gem_home.join("bin"))
+                .args(gems)
+                .current_dir(hook.path())
+                .check(true)
+                .output()
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn check_health(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn run(
+        &self,
+        hook: &Hook,
+        filenames: &[&String],
+        env_vars: Arc<HashMap<&'static str, String>>,
+    ) -> anyhow::Result<(i32, Vec<u8>)> {
+        let env_dir = hook.environment_dir().expect("No environment dir for Ruby");
+        let gem_home = env_dir.join("gems");
+
+        let cmds = hook.entry_argv()?;
+
+        // Gem executables are installed to `$GEM_HOME/bin`.
+        let new_path = std::env::join_paths(
+            std::iter::once(gem_home.join("bin")).chain(
+                std::env::var_os("PATH")
+                    .as_ref()
+                    .iter()
+                    .flat_map(std::env::split_paths),
+            ),
+        )?;
+
+        let cmds = Arc::new(cmds);
+        let hook_args = Arc::new(hook.args.clone());
+        let version = Arc::new(ruby_version(hook).map(ToString::to_string));
+        let gem_home = Arc::new(gem_home);
+        let new_path = Arc::new(new_path);
+        let work_dir = Arc::new(hook.work_dir().to_path_buf());
+
+        let run = move |batch: Vec<String>| {
+            let cmds = cmds.clone();
+            let hook_args = hook_args.clone();
+            let version = version.clone();
+            let gem_home = gem_home.clone();
+            let new_path = new_path.clone();
+            let work_dir = work_dir.clone();
+            let env_vars = env_vars.clone();
+
+            async move {
+                let mut cmd = hook_cmd(&cmds[0], "run ruby command");
+                if let Some(version) = version.as_deref() {
+                    cmd.env("RBENV_VERSION", version);
+                }
+                let output = cmd
+                    .args(&cmds[1..])
+                    .env("GEM_HOME", gem_home.as_ref())
+                    .env("GEM_PATH", gem_home.as_ref())
+                    .env("BUNDLE_IGNORE_CONFIG", "1")
+                    .env("PATH", new_path.as_ref())
+                    .envs(env_vars.as_ref())
+                    .args(hook_args.as_slice())
+                    .args(batch)
+                    .current_dir(work_dir.as_ref())
+                    .check(false)
+                    .merge_stderr()
+                    .output()
+                    .await?;
+
+                let code = output.status.code().unwrap_or(1);
+                anyhow::Ok((code, output.stdout))
+            }
+        };
+
+        let results = run_by_batch(hook, filenames, run).await?;
+
+        // Collect results
+        let mut combined_status = 0;
+        let mut combined_output = Vec::new();
+
+        for (code, output) in results {
+            combined_status |= code;
+            combined_output.extend(output);
+        }
+
+        Ok((combined_status, combined_output))
+    }
+}
+
+/// The rbenv ruby version to use, `None` means the system ruby.
+fn ruby_version(hook: &Hook) -> Option<&str> {
+    match hook.language_version.as_str() {
+        DEFAULT_VERSION | "system" => None,
+        version => Some(version),
+    }
+}
+
+/// A `gem` command that only sees the gems of the environment.
+fn gem(gem_home: &Path, version: Option<&str>, summary: &str) -> Cmd {
+    let mut cmd = Cmd::new("gem", summary);
+    if let Some(version) = version {
+        cmd.env("RBENV_VERSION", version);
+    }
+    cmd.env("GEM_HOME", gem_home).env("GEM_PATH", gem_home);
+    cmd
+}
@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::config;
+use crate::hook::Hook;
+use crate::languages::{hook_cmd, LanguageImpl, DEFAULT_VERSION};
+use crate::process::Cmd;
+use crate::run::run_by_batch;
+
+#[derive(Debug, Copy, Clone)]
+pub struct Rust;
+
+impl LanguageImpl for Rust {
+    fn name(&self) -> config::Language {
+        config::Language::Rust
+    }
+
+    fn default_version(&self) -> &str {
+        DEFAULT_VERSION
+    }
+
+    fn environment_dir(&self) -> Option<&str> {
+        Some("rustenv")
+    }
+
+    async fn install(&self, hook: &Hook) -> anyhow::Result<()> {
+        let env = hook.environment_dir().expect("No environment dir found");
+        fs_err::create_dir_all(&env)?;
+
+        let toolchain = toolchain(hook);
+        if let Some(toolchain) = toolchain {
+            Cmd::new("rustup", "install rust toolchain")
+                .arg("toolchain")
+                .arg("install")
+                .arg("--profile")
+                .arg("minimal")
+                .arg(toolchain)
+                .check(true)
+                .output()
+                .await?;
+        }
+
+        // `cli:` dependencies are binaries to install, the others are library dependencies
+        // added to the hook crate.
+        let (cli_deps, lib_deps): (Vec<_>, Vec<_>) = hook
+            .additional_dependencies
+            .iter()
+            .partition(|dep| dep.starts_with("cli:"));
+
+        let has_manifest = hook.path().join("Cargo.toml").try_exists()?;
+        if !lib_deps.is_empty() {
+            if !has_manifest {
+                anyhow::bail!(
+                    "Library dependencies of hook `{}` need a `Cargo.toml` to be added to",
+                    hook.id
+                );
+            }
+            cargo(toolchain, "add rust dependencies")
+                .arg("add")
+                .args(lib_deps)
+                .current_dir(hook.path())
+                .check(true)
+                .output()
+                .await?;
+        }
+
+        let mut packages = Vec::new();
+        if has_manifest {
+            packages.push(vec!["--path", "."]);
+        }
+        for dep in cli_deps.iter().filter_map(|dep| dep.strip_prefix("cli:")) {
+            match dep.split_once(':') {
+                Some((name, version)) => packages.push(vec![name, "--version", version]),
+                None => packages.push(vec![dep]),
+            }
+        }
+
+        for package in packages {
+            cargo(toolchain, "install rust package")
+                .arg("install")
+                .arg("--bins")
+                .arg("--root")
+                .arg(&env)
+                .args(package)
+                .current_dir(hook.path())
+                .check(true)
+                .output()
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn check_health(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn run(
+        &self,
+        hook: &Hook,
+        filenames: &[&String],
+        env_vars: Arc<HashMap<&'static str, String>>,
+    ) -> anyhow::Result<(i32, Vec<u8>)> {
+        let env_dir = hook.environment_dir().expect("No environment dir for Rust");
+
+        let cmds = hook.entry_argv()?;
+
+        // Binaries are installed to `<env>/bin`.
+        let new_path = std::env::join_paths(
+            std::iter::once(env_dir.join("bin")).chain(
+                std::env::var_os("PATH")
+                    .as_ref()
+                    .iter()
+                    .flat_map(std::env::split_paths),
+            ),
+        )?;
+
+        let cmds = Arc::new(cmds);
+        let hook_args = Arc::new(hook.args.clone());
+        let toolchain = Arc::new(toolchain(hook).map(ToString::to_string));
+        let new_path = Arc::new(new_path);
+        let work_dir = Arc::new(hook.work_dir().to_path_buf());
+
+        let run = move |batch: Vec<String>| {
+            let cmds = cmds.clone();
+            let hook_args = hook_args.clone();
+            let toolchain = toolchain.clone();
+            let new_path = new_path.clone();
+            let work_dir = work_dir.clone();
+            let env_vars = env_vars.clone();
+
+            async move {
+                let mut cmd = hook_cmd(&cmds[0], "run rust command");
+                if let Some(toolchain) = toolchain.as_deref() {
+                    cmd.env("RUSTUP_TOOLCHAIN", toolchain);
+                }
+                let output = cmd
+                    .args(&cmds[1..])
+                    .env("PATH", new_path.as_ref())
+                    .envs(env_vars.as_ref())
+                    .args(hook_args.as_slice())
+                    .args(batch)
+                    .current_dir(work_dir.as_ref())
+                    .check(false)
+                    .merge_stderr()
+                    .output()
+                    .await?;
+
+                let code = output.status.code().unwrap_or(1);
+                anyhow::Ok((code, output.stdout))
+            }
+        };
+
+        let results = run_by_batch(hook, filenames, run).await?;
+
+        // Collect results
+        let mut combined_status = 0;
+        let mut combined_output = Vec::new();
+
+        for (code, output) in results {
+            combined_status |= code;
+            combined_output.extend(output);
+        }
+
+        Ok((combined_status, combined_output))
+    }
+}
+
+/// The rustup toolchain to use, `None` means the system toolchain.
+fn toolchain(hook: &Hook) -> Option<&str> {
+    match hook.language_version.as_str() {
+        DEFAULT_VERSION | "system" => None,
+        version => Some(version),
+    }
+}
+
+fn cargo(toolchain: Option<&str>, summary: &str) -> Cmd {
+    let mut cmd = Cmd::new("cargo", summary);
+    if let Some(toolchain) = toolchain {
+        cmd.env("RUSTUP_TOOLCHAIN", toolchain);
+    }
+    cmd
+}
@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::config;
+use crate::hook::Hook;
+use crate::languages::{entry_not_found, hook_cmd, LanguageImpl, DEFAULT_VERSION};
+use crate::run::run_by_batch;
+
+#[derive(Debug, Copy, Clone)]
+pub struct Script;
+
+impl LanguageImpl for Script {
+    fn name(&self) -> config::Language {
+        config::Language::Script
+    }
+
+    fn default_version(&self) -> &str {
+        DEFAULT_VERSION
+    }
+
+    fn environment_dir(&self) -> Option<&str> {
+        None
+    }
+
+    async fn install(&self, _hook: &Hook) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn check_health(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn run(
+        &self,
+        hook: &Hook,
+        filenames: &[&String],
+        env_vars: Arc<HashMap<&'static str, String>>,
+    ) -> anyhow::Result<(i32, Vec<u8>)> {
+        let mut cmds = hook.entry_argv()?;
+        // The script is relative to the hook repo, which is the repo root for local hooks,
+        // so it can be found regardless of where pre-commit is invoked.
+        cmds[0] = hook.path().join(&cmds[0]).to_string_lossy().into_owned();
+
+        let cmds = Arc::new(cmds);
+        let hook_args = Arc::new(hook.args.clone());
+        let work_dir = Arc::new(hook.work_dir().to_path_buf());
+
+        let run = move |batch: Vec<String>| {
+            let cmds = cmds.clone();
+            let hook_args = hook_args.clone();
+            let work_dir = work_dir.clone();
+            let env_vars = env_vars.clone();
+
+            async move {
+                let output = match hook_cmd(&cmds[0], "run script")
+                    .args(&cmds[1..])
+                    .args(hook_args.as_ref())
+                    .args(batch)
+                    .current_dir(work_dir.as_ref())
+                    .envs(env_vars.as_ref())
+                    .check(false)
+                    .merge_stderr()
+                    .output()
+                    .await
+                {
+                    Ok(output) => output,
+                    Err(err) => return entry_not_found(err, &cmds[0]),
+                };
+
+                let code = output.status.code().unwrap_or(1);
+                anyhow::Ok((code, output.stdout))
+            }
+        };
+
+        let results = run_by_batch(hook, filenames, run).await?;
+
+        // Collect results
+        let mut combined_status = 0;
+        let mut combined_output = Vec::new();
+
+        for (code, output) in results {
+            combined_status |= code;
+            combined_output.extend(output);
+        }
+
+        Ok((combined_status, combined_output))
+    }
+}
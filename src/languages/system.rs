@@ -3,8 +3,7 @@ use std::sync::Arc;
 
 use crate::config;
 use crate::hook::Hook;
-use crate::languages::{LanguageImpl, DEFAULT_VERSION};
-use crate::process::Cmd;
+use crate::languages::{entry_not_found, hook_cmd, LanguageImpl, DEFAULT_VERSION};
 use crate::run::run_by_batch;
 
 #[derive(Debug, Copy, Clone)]
@@ -37,28 +36,34 @@ impl LanguageImpl for System {
         filenames: &[&String],
         env_vars: Arc<HashMap<&'static str, String>>,
     ) -> anyhow::Result<(i32, Vec<u8>)> {
-        let cmds = shlex::split(&hook.entry).ok_or(anyhow::anyhow!("Failed to parse entry"))?;
+        let cmds = hook.entry_argv()?;
 
         let cmds = Arc::new(cmds);
         let hook_args = Arc::new(hook.args.clone());
+        let work_dir = Arc::new(hook.work_dir().to_path_buf());
 
         let run = move |batch: Vec<String>| {
             let cmds = cmds.clone();
             let hook_args = hook_args.clone();
+            let work_dir = work_dir.clone();
             let env_vars = env_vars.clone();
 
             async move {
-                let mut output = Cmd::new(&cmds[0], "run system command")
+                let output = match hook_cmd(&cmds[0], "run system command")
                     .args(&cmds[1..])
                     .args(hook_args.as_ref())
                     .args(batch)
-                    .stderr(std::process::Stdio::inherit())
+                    .current_dir(work_dir.as_ref())
                     .envs(env_vars.as_ref())
                     .check(false)
+                    .merge_stderr()
                     .output()
-                    .await?;
+                    .await
+                {
+                    Ok(output) => output,
+                    Err(err) => return entry_not_found(err, &cmds[0]),
+                };
 
-                output.stdout.extend(output.stderr);
                 let code = output.status.code().unwrap_or(1);
                 anyhow::Ok((code, output.stdout))
             }
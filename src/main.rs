@@ -2,6 +2,7 @@ use std::fmt::Write;
 use std::path::{Path, PathBuf};
 use std::process::ExitCode;
 use std::str::FromStr;
+use std::sync::LazyLock;
 
 use anstream::{eprintln, ColorChoice};
 use anyhow::{Context, Result};
@@ -13,9 +14,11 @@ use tracing_subscriber::EnvFilter;
 
 use crate::cleanup::cleanup;
 use crate::cli::{Cli, Command, ExitStatus, SelfCommand, SelfNamespace, SelfUpdateArgs};
-use crate::git::get_root;
+use crate::git::{get_root, git_version, GIT_ENV, MIN_GIT_VERSION};
 use crate::printer::Printer;
 
+mod annotations;
+mod ci;
 mod cleanup;
 mod cli;
 mod config;
@@ -24,6 +27,7 @@ mod git;
 mod hook;
 mod identify;
 mod languages;
+mod meta_hooks;
 mod printer;
 mod process;
 #[cfg(all(unix, feature = "profiler"))]
@@ -31,6 +35,7 @@ mod profiler;
 mod run;
 mod store;
 mod warnings;
+mod watch;
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub(crate) enum Level {
@@ -83,6 +88,8 @@ fn adjust_relative_paths(cli: &mut Cli, new_cwd: &Path) -> Result<()> {
     }
 
     if let Some(Command::Run(ref mut args) | Command::TryRepo(ref mut args)) = cli.command {
+        args.files = expand_file_lists(std::mem::take(&mut args.files))?;
+        args.files.append(&mut args.trailing_files);
         args.files = args
             .files
             .iter()
@@ -99,8 +106,42 @@ fn adjust_relative_paths(cli: &mut Cli, new_cwd: &Path) -> Result<()> {
     Ok(())
 }
 
+/// Expand `@<path>` in `--files` to the filenames listed in that file, one per line,
+/// like the response files of many tools on Windows, where command lines are short.
+///
+/// The listed filenames are relative to the current directory, as if given on the command line.
+fn expand_file_lists(files: Vec<PathBuf>) -> Result<Vec<PathBuf>> {
+    let mut expanded = Vec::with_capacity(files.len());
+    for file in files {
+        let Some(list) = file.to_str().and_then(|file| file.strip_prefix('@')) else {
+            expanded.push(file);
+            continue;
+        };
+        let content = fs_err::read_to_string(list)?;
+        expanded.extend(
+            content
+                .lines()
+                .filter(|line| !line.is_empty())
+                .map(PathBuf::from),
+        );
+    }
+    Ok(expanded)
+}
+
+/// Warn if git is too old, rather than failing later with an obscure git error.
+async fn check_git_version() {
+    match git_version().await {
+        Ok(version) if version < MIN_GIT_VERSION => {
+            warn_user!("git {version} is not supported, upgrade git to >= {MIN_GIT_VERSION}");
+        }
+        Ok(version) => debug!("git: {version}"),
+        // Git not found is reported by the commands that need it.
+        Err(err) => debug!("Failed to get git version: {err}"),
+    }
+}
+
 async fn run(mut cli: Cli) -> Result<ExitStatus> {
-    ColorChoice::write_global(cli.globals.color.into());
+    ColorChoice::write_global(cli.globals.color_choice().into());
 
     setup_logging(match cli.globals.verbose {
         0 => Level::Default,
@@ -108,17 +149,19 @@ async fn run(mut cli: Cli) -> Result<ExitStatus> {
         _ => Level::ExtraVerbose,
     })?;
 
-    let printer = if cli.globals.quiet {
-        Printer::Quiet
-    } else if cli.globals.verbose > 0 {
+    let printer = if cli.globals.verbose > 0 {
         Printer::Verbose
+    } else if cli.globals.quiet == 1 {
+        Printer::Quiet
+    } else if cli.globals.quiet > 1 {
+        Printer::Silent
     } else if cli.globals.no_progress {
         Printer::NoProgress
     } else {
         Printer::Default
     };
 
-    if cli.globals.quiet {
+    if matches!(printer, Printer::Quiet | Printer::Silent) {
         warnings::disable();
     } else {
         warnings::enable();
@@ -130,12 +173,15 @@ async fn run(mut cli: Cli) -> Result<ExitStatus> {
 
     debug!("pre-commit: {}", env!("CARGO_PKG_VERSION"));
 
+    check_git_version().await;
+
     match get_root().await {
         Ok(root) => {
             debug!("Git root: {}", root.display());
 
             // Adjust relative paths before changing the working directory.
             adjust_relative_paths(&mut cli, &root)?;
+            LazyLock::force(&GIT_ENV);
 
             std::env::set_current_dir(&root)?;
         }
@@ -178,25 +224,19 @@ async fn run(mut cli: Cli) -> Result<ExitStatus> {
         Command::Uninstall(args) => {
             show_settings!(args);
 
-            cli::uninstall(cli.globals.config, args.hook_types, printer).await
+            cli::uninstall(cli.globals.config, args.hook_types, args.all, printer).await
         }
         Command::Run(args) => {
             show_settings!(args);
 
-            cli::run(
-                cli.globals.config,
-                args.hook_id,
-                args.hook_stage,
-                args.from_ref,
-                args.to_ref,
-                args.all_files,
-                args.files,
-                args.show_diff_on_failure,
-                args.extra,
-                cli.globals.verbose > 0,
-                printer,
-            )
-            .await
+            if let Some(hook_id) = args.print_env_path {
+                return cli::print_env_path(cli.globals.config, &hook_id, printer).await;
+            }
+            if args.list_stages {
+                return cli::list_stages(cli.globals.config, printer).await;
+            }
+
+            cli::run(cli.globals.config, *args, cli.globals.verbose > 0, printer).await
         }
         Command::HookImpl(args) => {
             show_settings!(args);
@@ -212,10 +252,27 @@ async fn run(mut cli: Cli) -> Result<ExitStatus> {
             .await
         }
         Command::Clean => cli::clean(printer),
+        Command::AutoUpdate(args) => {
+            show_settings!(args);
+
+            cli::autoupdate(
+                cli.globals.config,
+                args.bleeding_edge,
+                args.freeze,
+                args.repo,
+                args.jobs,
+                args.dry_run,
+                printer,
+            )
+            .await
+        }
+        Command::CacheDir => cli::cache_dir(printer),
+        Command::Doctor => cli::doctor(printer).await,
+        Command::GC => cli::gc(printer).await,
         Command::ValidateConfig(args) => {
             show_settings!(args);
 
-            Ok(cli::validate_configs(args.configs))
+            Ok(cli::validate_configs(args.configs, args.check_revs, args.strict).await)
         }
         Command::ValidateManifest(args) => {
             show_settings!(args);
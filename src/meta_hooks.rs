@@ -0,0 +1,188 @@
+//! The built-in hooks of the `meta` repo, they check the configuration itself.
+
+use std::io::Write;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use fancy_regex::Regex;
+
+use crate::config::{Language, ManifestHook, MetaHookID, CONFIG_FILE};
+use crate::git;
+use crate::hook::{Hook, Project, Repo};
+use crate::printer::Printer;
+use crate::run::{hook_filenames, FilenameFilter};
+use crate::store::Store;
+
+/// The manifest of the meta hooks.
+///
+/// Like other hooks, they run in all stages unless `stages` or `default_stages` is set.
+pub fn manifest() -> Vec<ManifestHook> {
+    let config_file = format!("^{}$", fancy_regex::escape(CONFIG_FILE));
+    vec![
+        meta_hook(
+            MetaHookID::CheckHooksApply,
+            "Check hooks apply to the repository",
+            Some(config_file.clone()),
+            false,
+        ),
+        meta_hook(
+            MetaHookID::CheckUselessExcludes,
+            "Check for useless excludes",
+            Some(config_file),
+            false,
+        ),
+        meta_hook(MetaHookID::Identity, "identity", None, true),
+    ]
+}
+
+fn meta_hook(id: MetaHookID, name: &str, files: Option<String>, verbose: bool) -> ManifestHook {
+    ManifestHook {
+        id: id.to_string(),
+        name: name.to_string(),
+        entry: id.to_string(),
+        language: Language::System,
+        alias: None,
+        files,
+        exclude: None,
+        types: None,
+        types_or: None,
+        exclude_types: None,
+        additional_dependencies: None,
+        args: None,
+        always_run: None,
+        fail_fast: None,
+        pass_filenames: None,
+        description: None,
+        language_version: None,
+        log_file: None,
+        require_serial: None,
+        stages: None,
+        verbose: Some(verbose),
+        work_dir: None,
+        minimum_pre_commit_version: None,
+    }
+}
+
+/// Run a meta hook on the given files.
+pub async fn run(hook: &Hook, filenames: &[&String]) -> Result<(i32, Vec<u8>)> {
+    match hook.id.as_str() {
+        "check-hooks-apply" => check_hooks_apply(filenames).await,
+        "check-useless-excludes" => check_useless_excludes(filenames).await,
+        "identity" => Ok((0, identity(filenames))),
+        id => unreachable!("Unknown meta hook `{id}`"),
+    }
+}
+
+/// Print the filenames the hook is run with.
+fn identity(filenames: &[&String]) -> Vec<u8> {
+    let mut output = Vec::new();
+    for filename in filenames {
+        output.extend(filename.as_bytes());
+        output.push(b'\n');
+    }
+    output
+}
+
+/// Check that every hook in the config matches at least one file in the repository.
+async fn check_hooks_apply(filenames: &[&String]) -> Result<(i32, Vec<u8>)> {
+    let all_files = git::get_all_files().await?;
+
+    let mut code = 0;
+    let mut output = Vec::new();
+    for config in filenames {
+        let (project, hooks) = load_hooks(config).await?;
+        let files = project_files(&project, &all_files)?;
+
+        for hook in &hooks {
+            if hook.always_run || matches!(hook.repo(), Repo::Meta { .. }) {
+                continue;
+            }
+            if hook_filenames(hook, &files)?.is_empty() {
+                code = 1;
+                writeln!(output, "{} does not apply to this repository", hook.id)?;
+            }
+        }
+    }
+
+    Ok((code, output))
+}
+
+/// Check that every `exclude` pattern in the config excludes at least one file.
+async fn check_useless_excludes(filenames: &[&String]) -> Result<(i32, Vec<u8>)> {
+    let all_files = git::get_all_files().await?;
+
+    let mut code = 0;
+    let mut output = Vec::new();
+    for config in filenames {
+        let (project, hooks) = load_hooks(config).await?;
+
+        if let Some(exclude) = project.config().exclude.as_deref() {
+            if !matches_any(exclude, &all_files)? {
+                code = 1;
+                writeln!(
+                    output,
+                    "The global exclude pattern `{exclude}` does not match any files"
+                )?;
+            }
+        }
+
+        let files = project_files(&project, &all_files)?;
+        for hook in &hooks {
+            let Some(exclude) = hook.exclude.as_deref() else {
+                continue;
+            };
+            // The files the hook would run on without its `exclude`.
+            let mut unexcluded = hook.clone();
+            unexcluded.exclude = None;
+            let candidates = hook_filenames(&unexcluded, &files)?;
+            if !matches_any(exclude, candidates)? {
+                code = 1;
+                writeln!(
+                    output,
+                    "The exclude pattern `{exclude}` for {} does not match any files",
+                    hook.id
+                )?;
+            }
+        }
+    }
+
+    Ok((code, output))
+}
+
+async fn load_hooks(config: &str) -> Result<(Project, Vec<Hook>)> {
+    let store = Store::from_settings()?.init()?;
+    let mut project = Project::new(PathBuf::from(config))?;
+
+    let _lock = store.lock_async().await?;
+    let hooks = project.init_hooks(&store, Printer::Silent).await?;
+    Ok((project, hooks))
+}
+
+/// The files selected by the top level `files` and `exclude` patterns.
+fn project_files(project: &Project, all_files: &[String]) -> Result<Vec<String>> {
+    let filter = FilenameFilter::new(
+        project.config().files.as_deref(),
+        project.config().exclude.as_deref(),
+    )?;
+    Ok(all_files
+        .iter()
+        .filter(|filename| filter.filter(filename))
+        .cloned()
+        .collect())
+}
+
+/// Whether the pattern matches any of the files.
+///
+/// An empty pattern is the default that excludes nothing, it is never reported as useless.
+fn matches_any<'a>(pattern: &str, filenames: impl IntoIterator<Item = &'a String>) -> Result<bool> {
+    if pattern.is_empty() {
+        return Ok(true);
+    }
+    let re = Regex::new(pattern)?;
+    for filename in filenames {
+        if re.is_match(filename)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
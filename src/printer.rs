@@ -20,6 +20,8 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
+use std::io::IsTerminal;
+
 use anstream::{eprint, print};
 use indicatif::ProgressDrawTarget;
 
@@ -27,8 +29,10 @@ use indicatif::ProgressDrawTarget;
 pub enum Printer {
     /// A printer that prints to standard streams (e.g., stdout).
     Default,
-    /// A printer that suppresses all output.
+    /// A printer that suppresses all output except failures.
     Quiet,
+    /// A printer that suppresses all output.
+    Silent,
     /// A printer that prints all output, including debug messages.
     Verbose,
     /// A printer that prints to standard streams, excluding all progress outputs
@@ -41,6 +45,7 @@ impl Printer {
         match self {
             Self::Default => ProgressDrawTarget::stderr(),
             Self::Quiet => ProgressDrawTarget::hidden(),
+            Self::Silent => ProgressDrawTarget::hidden(),
             // Confusingly, hide the progress bar when in verbose mode.
             // Otherwise, it gets interleaved with debug messages.
             Self::Verbose => ProgressDrawTarget::hidden(),
@@ -53,6 +58,20 @@ impl Printer {
         match self {
             Self::Default => Stdout::Enabled,
             Self::Quiet => Stdout::Disabled,
+            Self::Silent => Stdout::Disabled,
+            Self::Verbose => Stdout::Enabled,
+            Self::NoProgress => Stdout::Enabled,
+        }
+    }
+
+    /// Return the [`Stdout`] for important messages, e.g. hook failures.
+    ///
+    /// Unlike [`Printer::stdout`], this is still enabled in quiet mode.
+    pub fn stdout_important(self) -> Stdout {
+        match self {
+            Self::Default => Stdout::Enabled,
+            Self::Quiet => Stdout::Enabled,
+            Self::Silent => Stdout::Disabled,
             Self::Verbose => Stdout::Enabled,
             Self::NoProgress => Stdout::Enabled,
         }
@@ -63,6 +82,7 @@ impl Printer {
         match self {
             Self::Default => Stderr::Enabled,
             Self::Quiet => Stderr::Disabled,
+            Self::Silent => Stderr::Disabled,
             Self::Verbose => Stderr::Enabled,
             Self::NoProgress => Stderr::Enabled,
         }
@@ -112,3 +132,33 @@ impl std::fmt::Write for Stderr {
         Ok(())
     }
 }
+
+/// The width of the terminal stdout is attached to, `COLUMNS` overrides the detected width.
+///
+/// `None` when stdout is not a terminal, output to files or pipes is never truncated.
+pub fn terminal_width() -> Option<usize> {
+    if !std::io::stdout().is_terminal() {
+        return None;
+    }
+    if let Some(columns) = std::env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse().ok())
+        .filter(|&columns| columns > 0)
+    {
+        return Some(columns);
+    }
+    stdout_width()
+}
+
+#[cfg(unix)]
+fn stdout_width() -> Option<usize> {
+    // SAFETY: `winsize` is plain data, and `TIOCGWINSZ` only writes to it.
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let ret = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    (ret == 0 && size.ws_col > 0).then_some(usize::from(size.ws_col))
+}
+
+#[cfg(not(unix))]
+fn stdout_width() -> Option<usize> {
+    None
+}
@@ -27,8 +27,10 @@
 /// Adapt [axoprocess] to use [`tokio::process::Process`] instead of [`std::process::Command`].
 use std::{
     ffi::OsStr,
+    num::NonZeroUsize,
     path::Path,
     process::{CommandArgs, CommandEnvs, ExitStatus, Stdio},
+    time::Duration,
 };
 
 use miette::Diagnostic;
@@ -52,32 +54,71 @@ pub enum Error {
     },
     /// The command ran but signaled some kind of error condition
     /// (assuming the exit code is used for that)
-    #[error("failed to {summary} (status: {status})")]
+    #[error("failed to {summary} (status: {status}){}", DisplayOutput(.command, .output.as_ref()))]
     Status {
         /// Summary of what the Command was trying to do
         summary: String,
+        /// The command line that was run
+        command: String,
         /// What status the Command returned
         status: ExitStatus,
+        /// The captured output, if the Command was run with [`Cmd::output`][]
+        output: Option<std::process::Output>,
+    },
+    /// The command didn't finish within [`Cmd::timeout`][], and was killed
+    #[error("failed to {summary} (timed out after {timeout:?})")]
+    Timeout {
+        /// Summary of what the Command was trying to do
+        summary: String,
+        /// The timeout that elapsed
+        timeout: Duration,
     },
 }
 
+/// Render the command line and the captured stdout and stderr below the error message.
+struct DisplayOutput<'a>(&'a str, Option<&'a std::process::Output>);
+
+impl std::fmt::Display for DisplayOutput<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "\n[command]\n{}", self.0)?;
+        let Some(output) = self.1 else {
+            return Ok(());
+        };
+        for (name, content) in [("stdout", &output.stdout), ("stderr", &output.stderr)] {
+            let content = String::from_utf8_lossy(content.trim_ascii());
+            if !content.is_empty() {
+                write!(f, "\n[{name}]\n{content}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
 /// A fancier Command, see the crate's top-level docs!
 pub struct Cmd {
     /// The inner Command, in case you need to access it
     pub inner: tokio::process::Command,
     summary: String,
     check_status: bool,
+    timeout: Option<Duration>,
+    merge_stderr: bool,
 }
 
 /// Constructors
 impl Cmd {
     /// Create a new Command with an additional "summary" of what this is trying to do
+    ///
+    /// The child is killed if the execution future is dropped before it exits,
+    /// e.g. when a task is cancelled, so no process is left running behind.
     pub fn new(command: impl AsRef<OsStr>, summary: impl Into<String>) -> Self {
-        let inner = tokio::process::Command::new(command);
+        let mut inner = tokio::process::Command::new(command);
+        inner.kill_on_drop(true);
         Self {
             summary: summary.into(),
             inner,
             check_status: true,
+            timeout: None,
+            merge_stderr: false,
         }
     }
 }
@@ -108,6 +149,25 @@ impl Cmd {
         self.check_status = checked;
         self
     }
+
+    /// Kill the command if it doesn't finish within `timeout`,
+    /// and produce an [`Error::Timeout`][] from [`Cmd::output`][] and [`Cmd::status`][].
+    ///
+    /// Defaults to no timeout.
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Capture stderr into the stdout of [`Cmd::output`][], in the order the command wrote them,
+    /// like `2>&1`. The captured stderr is empty.
+    ///
+    /// On Unix, both streams share one pipe, so the order is exact.
+    /// Elsewhere, stderr is appended after stdout.
+    pub fn merge_stderr(&mut self) -> &mut Self {
+        self.merge_stderr = true;
+        self
+    }
 }
 
 /// Execution APIs
@@ -133,11 +193,23 @@ impl Cmd {
     /// but logged, with the error wrapped, and status checked (by default)
     pub async fn output(&mut self) -> Result<std::process::Output> {
         self.log_command();
-        let res = self.inner.output().await.map_err(|cause| Error::Exec {
+        let res = if self.merge_stderr {
+            with_timeout(self.timeout, &self.summary, merged_output(&mut self.inner)).await?
+        } else {
+            with_timeout(self.timeout, &self.summary, self.inner.output()).await?
+        };
+        let res = res.map_err(|cause| Error::Exec {
             summary: self.summary.clone(),
             cause,
         })?;
-        self.maybe_check_status(res.status)?;
+        if self.check_status && !res.status.success() {
+            return Err(Error::Status {
+                summary: self.summary.clone(),
+                command: self.command_line(),
+                status: res.status,
+                output: Some(res),
+            });
+        }
         Ok(res)
     }
 
@@ -145,15 +217,89 @@ impl Cmd {
     /// but logged, with the error wrapped, and status checked (by default)
     pub async fn status(&mut self) -> Result<ExitStatus> {
         self.log_command();
-        let res = self.inner.status().await.map_err(|cause| Error::Exec {
-            summary: self.summary.clone(),
-            cause,
-        })?;
+        let res = with_timeout(self.timeout, &self.summary, self.inner.status())
+            .await?
+            .map_err(|cause| Error::Exec {
+                summary: self.summary.clone(),
+                cause,
+            })?;
         self.maybe_check_status(res)?;
         Ok(res)
     }
 }
 
+/// Run `fut` with the timeout if any, the child of a command future is killed on drop
+/// when it elapses.
+async fn with_timeout<T>(
+    timeout: Option<Duration>,
+    summary: &str,
+    fut: impl std::future::Future<Output = T>,
+) -> Result<T> {
+    let Some(timeout) = timeout else {
+        return Ok(fut.await);
+    };
+    tokio::time::timeout(timeout, fut)
+        .await
+        .map_err(|_| Error::Timeout {
+            summary: summary.to_string(),
+            timeout,
+        })
+}
+
+/// Run the command with stdout and stderr sharing one pipe, so the output is read in the
+/// order it was written. Like [`tokio::process::Command::output`][], stdin is null.
+#[cfg(unix)]
+async fn merged_output(cmd: &mut tokio::process::Command) -> std::io::Result<std::process::Output> {
+    use std::io::Read;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+    let mut fds = [0; 2];
+    // SAFETY: `fds` has room for the two file descriptors `pipe` writes.
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    // SAFETY: `pipe` succeeded, so both are open and not owned by anything else.
+    let (reader, writer) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+    for fd in [&reader, &writer] {
+        // Don't leak the pipe into other children, duplicating the write end to the
+        // stdout and stderr of the child clears the flag there.
+        // SAFETY: the file descriptor is open.
+        if unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+
+    cmd.stdin(Stdio::null())
+        .stdout(writer.try_clone()?)
+        .stderr(writer);
+    let child = cmd.spawn();
+    // The command holds the write ends until they are replaced, the read only ends when
+    // every write end is closed.
+    cmd.stdout(Stdio::inherit()).stderr(Stdio::inherit());
+    let mut child = child?;
+
+    let reader = std::fs::File::from(reader);
+    let read = tokio::task::spawn_blocking(move || {
+        let mut output = Vec::new();
+        (&reader).read_to_end(&mut output).map(|_| output)
+    });
+    let status = child.wait().await?;
+    let stdout = read.await.map_err(std::io::Error::other)??;
+
+    Ok(std::process::Output {
+        status,
+        stdout,
+        stderr: Vec::new(),
+    })
+}
+
+#[cfg(not(unix))]
+async fn merged_output(cmd: &mut tokio::process::Command) -> std::io::Result<std::process::Output> {
+    let mut output = cmd.output().await?;
+    output.stdout.append(&mut output.stderr);
+    Ok(output)
+}
+
 /// Transparently forwarded [`std::process::Command`][] APIs
 impl Cmd {
     /// Forwards to [`std::process::Command::arg`][]
@@ -259,7 +405,9 @@ impl Cmd {
         } else {
             Err(Error::Status {
                 summary: self.summary.clone(),
+                command: self.command_line(),
                 status,
+                output: None,
             })
         }
     }
@@ -273,6 +421,15 @@ impl Cmd {
         Ok(())
     }
 
+    /// The command line, quoted to be copied into a shell, without colors.
+    pub fn command_line(&self) -> String {
+        let words: Vec<_> = std::iter::once(self.get_program())
+            .chain(self.get_args())
+            .map(OsStr::to_string_lossy)
+            .collect();
+        shlex::try_join(words.iter().map(AsRef::as_ref)).unwrap_or_else(|_| words.join(" "))
+    }
+
     /// Log the current Command using the method specified by [`Cmd::log`][]
     /// (defaults to [`tracing::info!`][]).
     pub fn log_command(&self) {
@@ -308,3 +465,283 @@ impl std::fmt::Display for Cmd {
         Ok(())
     }
 }
+
+/// The space each argument or env var takes up besides its content: the terminating NUL, and on
+/// Unix, the pointer to it in `argv` or `envp`, which Linux also counts against `ARG_MAX`.
+#[cfg(unix)]
+const ARG_OVERHEAD: usize = std::mem::size_of::<usize>() + 1;
+#[cfg(not(unix))]
+const ARG_OVERHEAD: usize = 1;
+
+/// The maximum length of a command line on this platform, in bytes.
+///
+/// On Unix, the arguments share `ARG_MAX` with the environment, so the size of the current
+/// environment is subtracted. Some headroom is kept, like `xargs` does, and the result is
+/// capped at 128 KiB like pre-commit, since some systems fail below their advertised limit.
+pub fn max_command_length() -> usize {
+    const HEADROOM: usize = 2048;
+    const MAX: usize = 1 << 17;
+
+    #[cfg(unix)]
+    {
+        // SAFETY: `sysconf` has no preconditions.
+        let arg_max = unsafe { libc::sysconf(libc::_SC_ARG_MAX) };
+        let arg_max = usize::try_from(arg_max)
+            .ok()
+            .filter(|&arg_max| arg_max > 0)
+            .unwrap_or(1 << 12);
+        // `key=value`.
+        let env_length: usize = std::env::vars_os()
+            .map(|(key, value)| key.len() + value.len() + 1 + ARG_OVERHEAD)
+            .sum();
+        arg_max
+            .saturating_sub(HEADROOM)
+            .saturating_sub(env_length)
+            .min(MAX)
+    }
+    #[cfg(windows)]
+    {
+        (1 << 15) - HEADROOM
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        (1 << 12) - HEADROOM
+    }
+}
+
+/// The length `arg` takes up on a command line.
+fn arg_length<S: AsRef<OsStr>>(arg: S) -> usize {
+    arg.as_ref().len() + ARG_OVERHEAD
+}
+
+/// The length `args` take up on a command line.
+fn args_length<S: AsRef<OsStr>>(args: impl IntoIterator<Item = S>) -> usize {
+    args.into_iter().map(arg_length).sum()
+}
+
+/// Split `filenames` into batches of at most `max_files`, so that `argv` followed by each
+/// batch stays under `max_length`, see [`max_command_length`].
+pub fn batches<'a, S: AsRef<OsStr>, F: AsRef<OsStr>>(
+    argv: &[S],
+    filenames: &'a [F],
+    max_length: usize,
+    max_files: NonZeroUsize,
+) -> Batches<'a, F> {
+    Batches {
+        filenames,
+        base_length: args_length(argv),
+        max_length,
+        max_files: max_files.get(),
+    }
+}
+
+/// An iterator over batches of filenames, created by [`batches`].
+///
+/// A filename too long to fit in any batch is yielded on its own, so the error surfaces
+/// from the OS when the command is run.
+pub struct Batches<'a, F> {
+    filenames: &'a [F],
+    base_length: usize,
+    max_length: usize,
+    max_files: usize,
+}
+
+impl<'a, F: AsRef<OsStr>> Iterator for Batches<'a, F> {
+    type Item = &'a [F];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.filenames.is_empty() {
+            return None;
+        }
+
+        let mut length = self.base_length;
+        let mut end = 0;
+        for filename in self.filenames {
+            length += arg_length(filename);
+            if end > 0 && (length > self.max_length || end >= self.max_files) {
+                break;
+            }
+            end += 1;
+        }
+
+        let (batch, rest) = self.filenames.split_at(end);
+        self.filenames = rest;
+        Some(batch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filenames(names: &[&str]) -> Vec<String> {
+        names.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn batches_fit() {
+        let files = filenames(&["a", "b", "c"]);
+        let result: Vec<_> = batches(&["cmd"], &files, 100, NonZeroUsize::MAX).collect();
+        assert_eq!(result, vec![&files[..]]);
+    }
+
+    #[test]
+    fn batches_split() {
+        let files = filenames(&["aaaa", "bbbb", "cccc", "dddd", "eeee"]);
+        let max_length = arg_length("cmd") + 2 * arg_length("aaaa");
+        let result: Vec<_> = batches(&["cmd"], &files, max_length, NonZeroUsize::MAX).collect();
+        assert_eq!(result, vec![&files[0..2], &files[2..4], &files[4..5]]);
+
+        // Arguments count towards the length.
+        let result: Vec<_> =
+            batches(&["cmd", "--flag"], &files, max_length, NonZeroUsize::MAX).collect();
+        assert_eq!(result.len(), 5);
+    }
+
+    #[test]
+    fn batches_too_long() {
+        let files = filenames(&["a", "too-long-for-any-batch", "b"]);
+        let max_length = arg_length("cmd") + arg_length("a");
+        let result: Vec<_> = batches(&["cmd"], &files, max_length, NonZeroUsize::MAX).collect();
+        assert_eq!(result, vec![&files[0..1], &files[1..2], &files[2..3]]);
+    }
+
+    #[test]
+    fn batches_max_files() {
+        let files = filenames(&["a", "b", "c", "d", "e"]);
+        let max_files = NonZeroUsize::new(2).unwrap();
+        let result: Vec<_> = batches(&["cmd"], &files, 100, max_files).collect();
+        assert_eq!(result, vec![&files[0..2], &files[2..4], &files[4..5]]);
+
+        // The smaller of the two limits wins.
+        let max_files = NonZeroUsize::new(3).unwrap();
+        let max_length = arg_length("cmd") + 2 * arg_length("a");
+        let result: Vec<_> = batches(&["cmd"], &files, max_length, max_files).collect();
+        assert_eq!(result, vec![&files[0..2], &files[2..4], &files[4..5]]);
+        let result: Vec<_> = batches(&["cmd"], &files, 100, max_files).collect();
+        assert_eq!(result, vec![&files[0..3], &files[3..5]]);
+    }
+
+    /// Linux counts a pointer for each argument against `ARG_MAX`, which dominates for many
+    /// short filenames.
+    #[cfg(unix)]
+    #[test]
+    fn batches_pointer_overhead() {
+        let files: Vec<_> = (0..10_000).map(|i| (i % 10).to_string()).collect();
+        // Room for all the files if only their content and NUL counted.
+        let max_length = arg_length("cmd") + files.len() * 2;
+        let result: Vec<_> = batches(&["cmd"], &files, max_length, NonZeroUsize::MAX).collect();
+
+        let per_batch = files.len() * 2 / (2 + std::mem::size_of::<usize>());
+        assert_eq!(result[0].len(), per_batch);
+        assert!(result.iter().all(|batch| batch.len() <= per_batch));
+        assert_eq!(
+            result.iter().map(|batch| batch.len()).sum::<usize>(),
+            files.len()
+        );
+    }
+
+    #[test]
+    fn max_length() {
+        assert!(max_command_length() > 0);
+        assert!(max_command_length() <= 1 << 17);
+    }
+
+    #[test]
+    fn batches_empty() {
+        let result: Vec<_> = batches(&["cmd"], &[] as &[String], 10, NonZeroUsize::MAX).collect();
+        assert!(result.is_empty());
+    }
+
+    /// Whether the process is still running, a zombie waiting to be reaped doesn't count.
+    #[cfg(target_os = "linux")]
+    fn is_running(pid: &str) -> bool {
+        fs_err::read_to_string(format!("/proc/{pid}/stat")).is_ok_and(|stat| {
+            stat.rsplit(')')
+                .next()
+                .map(str::trim_start)
+                .is_some_and(|rest| !rest.starts_with('Z'))
+        })
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn merge_stderr_in_order() -> anyhow::Result<()> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+
+        let output = runtime.block_on(
+            Cmd::new("sh", "write to both streams")
+                .arg("-c")
+                .arg("echo out 1; echo err 1 >&2; echo out 2; echo err 2 >&2")
+                .merge_stderr()
+                .output(),
+        )?;
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "out 1\nerr 1\nout 2\nerr 2\n"
+        );
+        assert!(output.stderr.is_empty());
+
+        // Without merging, the streams are captured apart.
+        let output = runtime.block_on(
+            Cmd::new("sh", "write to both streams")
+                .arg("-c")
+                .arg("echo out; echo err >&2")
+                .output(),
+        )?;
+        assert_eq!(output.stdout, b"out\n");
+        assert_eq!(output.stderr, b"err\n");
+
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn killed_on_timeout_and_cancel() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+
+        let sleep = |name: &str| {
+            let mut cmd = Cmd::new("sh", "sleep");
+            cmd.arg("-c")
+                .arg("echo $$ > \"$0\"; exec sleep 30")
+                .arg(dir.path().join(name));
+            cmd
+        };
+        let wait_exited = |name: &str| {
+            let pid = fs_err::read_to_string(dir.path().join(name))?;
+            let pid = pid.trim();
+            for _ in 0..50 {
+                if !is_running(pid) {
+                    return anyhow::Ok(());
+                }
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            anyhow::bail!("process {pid} is still running");
+        };
+
+        let err = runtime
+            .block_on(
+                sleep("timeout")
+                    .timeout(Duration::from_millis(500))
+                    .output(),
+            )
+            .unwrap_err();
+        assert!(matches!(err, Error::Timeout { .. }), "{err}");
+        wait_exited("timeout")?;
+
+        // Dropping the future, e.g. when the task is cancelled, kills the child as well.
+        let cancelled = runtime.block_on(async {
+            let mut cmd = sleep("cancel");
+            tokio::time::timeout(Duration::from_millis(500), cmd.output()).await
+        });
+        assert!(cancelled.is_err());
+        wait_exited("cancel")?;
+
+        Ok(())
+    }
+}
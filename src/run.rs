@@ -1,11 +1,14 @@
+use std::borrow::Cow;
 use std::cmp::max;
 use std::collections::HashMap;
 use std::fmt::Write as _;
 use std::future::Future;
 use std::io::Write as _;
+use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::UNIX_EPOCH;
 
 use anstream::{eprintln, ColorChoice};
 use anyhow::Result;
@@ -15,21 +18,35 @@ use rand::prelude::{SliceRandom, StdRng};
 use rand::SeedableRng;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use tokio::task::JoinSet;
-use tracing::{error, trace};
-use unicode_width::UnicodeWidthStr;
+use tracing::{error, trace, warn};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
+use crate::annotations::github_annotations;
 use crate::cleanup::add_cleanup;
-use crate::cli::ExitStatus;
-use crate::fs::Simplified;
+use crate::cli::{DiffScope, ExitStatus, OutputFormat};
+use crate::config::Stage;
+use crate::fs::{normalize_path, relative_to, Simplified};
 use crate::git;
-use crate::git::{get_diff, git_cmd, GIT};
-use crate::hook::Hook;
-use crate::identify::tags_from_path;
-use crate::printer::Printer;
+use crate::git::{get_diff, git_cmd, GIT, GIT_ENV};
+use crate::hook::{Hook, Repo, INSTALL_STATE_FILE};
+use crate::identify::{tags, tags_from_path_cached};
+use crate::meta_hooks;
+use crate::printer::{terminal_width, Printer};
+use crate::process;
 use crate::store::Store;
+use crate::warn_user_once;
 
 const SKIPPED: &str = "Skipped";
+const PASSED: &str = "Passed";
+const CACHED: &str = "(cached)";
 const NO_FILES: &str = "(no files to check)";
+const FAIL_FAST: &str = "(skipped due to fail_fast)";
+/// The width after the hook name of the longest status line.
+const STATUS_WIDTH: usize = 3 + FAIL_FAST.len() + 1 + SKIPPED.len();
+/// The width kept for hook names in a narrow terminal.
+const MIN_NAME_WIDTH: usize = 10;
+/// The number of filenames shown in the command line of a hook in verbose mode.
+const COMMAND_LINE_FILENAMES: usize = 3;
 
 /// Filter filenames by include/exclude patterns.
 pub struct FilenameFilter {
@@ -38,9 +55,19 @@ pub struct FilenameFilter {
 }
 
 impl FilenameFilter {
+    /// Create a filter from `files` and `exclude` patterns.
+    ///
+    /// A missing or empty `files` pattern matches everything, and a missing or empty `exclude`
+    /// pattern excludes nothing. An empty regex matches every string, so it must not be compiled.
     pub fn new(include: Option<&str>, exclude: Option<&str>) -> Result<Self, Box<regex::Error>> {
-        let include = include.map(Regex::new).transpose()?;
-        let exclude = exclude.map(Regex::new).transpose()?;
+        let include = include
+            .filter(|pattern| !pattern.is_empty())
+            .map(Regex::new)
+            .transpose()?;
+        let exclude = exclude
+            .filter(|pattern| !pattern.is_empty())
+            .map(Regex::new)
+            .transpose()?;
         Ok(Self { include, exclude })
     }
 
@@ -81,6 +108,16 @@ impl<'a> FileTagFilter<'a> {
     }
 
     fn filter(&self, file_types: &[&str]) -> bool {
+        // Directories (submodules) are only passed to hooks that select them explicitly.
+        if file_types.contains(&tags::DIRECTORY)
+            && !self
+                .all
+                .iter()
+                .chain(self.any)
+                .any(|t| t == tags::DIRECTORY)
+        {
+            return false;
+        }
         if !self.all.is_empty() && !self.all.iter().all(|t| file_types.contains(&t.as_str())) {
             return false;
         }
@@ -113,70 +150,200 @@ fn status_line(start: &str, cols: usize, end_msg: &str, end_color: Style, postfi
     )
 }
 
-fn calculate_columns(hooks: &[Hook]) -> usize {
-    let name_len = hooks
-        .iter()
-        .map(|hook| hook.name.width_cjk())
-        .max()
-        .unwrap_or(0);
-    max(80, name_len + 3 + NO_FILES.len() + 1 + SKIPPED.len())
+/// The width of the status lines, to fit the longest hook name, but no wider than the
+/// terminal. Names that don't fit are elided by [`display_name`].
+fn calculate_columns<'a>(hooks: impl Iterator<Item = &'a Hook>, width: Option<usize>) -> usize {
+    let name_len = hooks.map(|hook| hook.name.width_cjk()).max().unwrap_or(0);
+    let columns = max(80, name_len + STATUS_WIDTH);
+    match width {
+        Some(width) => columns.min(max(width, MIN_NAME_WIDTH + STATUS_WIDTH)),
+        None => columns,
+    }
+}
+
+/// The hook name elided with `…` to fit the status line.
+fn display_name(name: &str, columns: usize) -> Cow<'_, str> {
+    let max_width = columns.saturating_sub(STATUS_WIDTH);
+    if name.width_cjk() <= max_width {
+        return Cow::Borrowed(name);
+    }
+
+    let mut width = "…".width_cjk();
+    let mut elided = String::new();
+    for c in name.chars() {
+        width += c.width_cjk().unwrap_or(0);
+        if width > max_width {
+            break;
+        }
+        elided.push(c);
+    }
+    elided.push('…');
+    Cow::Owned(elided)
+}
+
+/// Why a hook didn't run.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum SkipReason {
+    /// Skipped by the `SKIP` env var.
+    Env,
+    /// No files to check matched the hook.
+    NoFiles,
+    /// The hook is not configured for the stage being run.
+    Stage,
+    /// A previous hook failed with `fail_fast`.
+    FailFast,
 }
 
-/// Run all hooks.
+impl SkipReason {
+    /// The message shown before `Skipped`, `None` if the hook is not shown at all.
+    /// Some reasons are only shown in verbose mode, to keep the default output short.
+    fn message(self, verbose: bool) -> Option<&'static str> {
+        match self {
+            SkipReason::Env if verbose => Some("(SKIP env)"),
+            SkipReason::Env => Some(""),
+            SkipReason::NoFiles => Some(NO_FILES),
+            SkipReason::Stage if verbose => Some("(not in stage)"),
+            SkipReason::Stage => None,
+            SkipReason::FailFast => Some(FAIL_FAST),
+        }
+    }
+
+    fn style(self) -> Style {
+        match self {
+            SkipReason::Env | SkipReason::FailFast => Style::new().black().on_yellow(),
+            SkipReason::NoFiles | SkipReason::Stage => Style::new().black().on_cyan(),
+        }
+    }
+}
+
+/// The result of a single hook.
+enum HookResult<'a> {
+    Skipped(SkipReason),
+    /// The hook ran, with the files it ran on.
+    Ran {
+        success: bool,
+        filenames: Vec<&'a String>,
+    },
+}
+
+fn print_skipped(
+    hook: &Hook,
+    reason: SkipReason,
+    columns: usize,
+    verbose: bool,
+    printer: Printer,
+) -> Result<()> {
+    // A hook with `verbose: true` always shows why it didn't run, but like other hooks, it is
+    // only listed under other stages with `--verbose`.
+    let verbose = verbose || (hook.verbose && reason != SkipReason::Stage);
+    if let Some(message) = reason.message(verbose) {
+        writeln!(
+            printer.stdout(),
+            "{}",
+            status_line(
+                &display_name(&hook.name, columns),
+                columns,
+                SKIPPED,
+                reason.style(),
+                message
+            )
+        )?;
+    }
+    Ok(())
+}
+
+/// Run all hooks for the stages, hooks of other stages are only reported in verbose mode.
+#[allow(clippy::fn_params_excessive_bools)]
 pub async fn run_hooks(
     hooks: &[Hook],
+    stages: &[Stage],
     skips: &[String],
     filenames: Vec<String>,
     env_vars: HashMap<&'static str, String>,
     fail_fast: bool,
     show_diff_on_failure: bool,
+    diff_scope: DiffScope,
+    baseline: Option<&str>,
+    output_format: OutputFormat,
+    max_output_lines: usize,
+    show_file_count: bool,
+    cache: Option<&Store>,
     verbose: bool,
     printer: Printer,
 ) -> Result<ExitStatus> {
     let env_vars = Arc::new(env_vars);
+    let in_stage = |hook: &Hook| stages.iter().any(|stage| hook.stages.contains(stage));
 
-    let columns = calculate_columns(hooks);
+    let columns = calculate_columns(
+        hooks.iter().filter(|&hook| verbose || in_stage(hook)),
+        terminal_width(),
+    );
     // TODO: progress bar, format output
     let mut success = true;
+    // Files of the failed hooks, to scope the diff on failure.
+    let mut touched = Vec::new();
+
+    let labels = hook_labels(hooks);
 
     let mut diff = get_diff().await?;
-    // hooks must run in serial
-    for hook in hooks {
-        let (hook_success, new_diff) = run_hook(
+    // hooks must run in serial, so results are reported in config order.
+    for (idx, hook) in hooks.iter().enumerate() {
+        if !in_stage(hook) {
+            print_skipped(hook, SkipReason::Stage, columns, verbose, printer)?;
+            continue;
+        }
+
+        let (result, new_diff) = run_hook(
             hook,
+            &labels[idx],
             &filenames,
             env_vars.clone(),
             skips,
             diff,
             columns,
+            output_format,
+            max_output_lines,
+            show_file_count,
+            cache,
             verbose,
             printer,
         )
         .await?;
 
-        success &= hook_success;
         diff = new_diff;
+        match result {
+            HookResult::Skipped(reason) => {
+                print_skipped(hook, reason, columns, verbose, printer)?;
+            }
+            HookResult::Ran {
+                success: hook_success,
+                filenames,
+            } => {
+                success &= hook_success;
+                if !hook_success {
+                    touched.extend(filenames);
+                }
+            }
+        }
+
         if !success && (fail_fast || hook.fail_fast) {
+            // Make it clear that the remaining hooks did not run, rather than passed.
+            for hook in &hooks[idx + 1..] {
+                let reason = if in_stage(hook) {
+                    SkipReason::FailFast
+                } else {
+                    SkipReason::Stage
+                };
+                print_skipped(hook, reason, columns, verbose, printer)?;
+            }
             break;
         }
     }
 
     if !success && show_diff_on_failure {
-        writeln!(printer.stdout(), "All changes made by hooks:")?;
-        let color = match ColorChoice::global() {
-            ColorChoice::Auto => "--color=auto",
-            ColorChoice::Always | ColorChoice::AlwaysAnsi => "--color=always",
-            ColorChoice::Never => "--color=never",
-        };
-        git_cmd("git diff")?
-            .arg("--no-pager")
-            .arg("diff")
-            .arg("--no-ext-diff")
-            .arg(color)
-            .check(true)
-            .spawn()?
-            .wait()
-            .await?;
+        touched.sort_unstable();
+        touched.dedup();
+        show_diff(diff_scope, baseline, &touched, printer).await?;
     };
 
     if success {
@@ -186,6 +353,66 @@ pub async fn run_hooks(
     }
 }
 
+/// The hook ids to display, the same hook id can appear multiple times in the config,
+/// these are numbered in config order to tell them apart.
+fn hook_labels(hooks: &[Hook]) -> Vec<Cow<'_, str>> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for hook in hooks {
+        *counts.entry(&hook.id).or_default() += 1;
+    }
+
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    hooks
+        .iter()
+        .map(|hook| {
+            if counts[hook.id.as_str()] > 1 {
+                let nth = seen.entry(&hook.id).or_default();
+                *nth += 1;
+                Cow::Owned(format!("{} ({nth})", hook.id))
+            } else {
+                Cow::Borrowed(hook.id.as_str())
+            }
+        })
+        .collect()
+}
+
+/// Show the changes made by hooks, only for the `touched` files when scoped.
+///
+/// The diff is against the `baseline` tree from before the hooks ran: the index when the
+/// non-staged changes were stashed, otherwise the working tree, e.g. with `--all-files`.
+/// So changes the hooks staged are shown and the user's own changes are not.
+async fn show_diff(
+    diff_scope: DiffScope,
+    baseline: Option<&str>,
+    touched: &[&String],
+    printer: Printer,
+) -> Result<()> {
+    if diff_scope == DiffScope::Touched && touched.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(printer.stdout_important(), "All changes made by hooks:")?;
+    let color = match ColorChoice::global() {
+        ColorChoice::Auto => "--color=auto",
+        ColorChoice::Always | ColorChoice::AlwaysAnsi => "--color=always",
+        ColorChoice::Never => "--color=never",
+    };
+    let mut cmd = git_cmd("git diff")?;
+    cmd.arg("--no-pager")
+        .arg("diff")
+        .arg("--no-ext-diff")
+        .arg(color);
+    if let Some(tree) = baseline {
+        cmd.arg(tree);
+    }
+    if diff_scope == DiffScope::Touched {
+        cmd.arg("--").args(touched);
+    }
+    cmd.check(true).spawn()?.wait().await?;
+
+    Ok(())
+}
+
 /// Shuffle the files so that they more evenly fill out the xargs
 /// partitions, but do it deterministically in case a hook cares about ordering.
 fn shuffle<T>(filenames: &mut [T]) {
@@ -194,82 +421,86 @@ fn shuffle<T>(filenames: &mut [T]) {
     filenames.shuffle(&mut rng);
 }
 
-async fn run_hook(
+/// Run a hook, returns the result and the new diff.
+async fn run_hook<'a>(
     hook: &Hook,
-    filenames: &[String],
+    hook_id: &str,
+    filenames: &'a [String],
     env_vars: Arc<HashMap<&'static str, String>>,
     skips: &[String],
     diff: Vec<u8>,
     columns: usize,
+    output_format: OutputFormat,
+    max_output_lines: usize,
+    show_file_count: bool,
+    cache: Option<&Store>,
     verbose: bool,
     printer: Printer,
-) -> Result<(bool, Vec<u8>)> {
+) -> Result<(HookResult<'a>, Vec<u8>)> {
     if skips.contains(&hook.id) || skips.contains(&hook.alias) {
-        writeln!(
-            printer.stdout(),
-            "{}",
-            status_line(
-                &hook.name,
-                columns,
-                SKIPPED,
-                Style::new().black().on_yellow(),
-                "",
-            )
-        )?;
-        return Ok((true, diff));
+        return Ok((HookResult::Skipped(SkipReason::Env), diff));
     }
 
-    let filter = FilenameFilter::from_hook(hook)?;
-    let filenames = filenames
-        .into_par_iter()
-        .filter(|filename| filter.filter(filename));
-
-    let filter = FileTagFilter::from_hook(hook);
-    let mut filenames: Vec<_> = filenames
-        .filter(|filename| {
-            let path = Path::new(filename);
-            match tags_from_path(path) {
-                Ok(tags) => filter.filter(&tags),
-                Err(err) => {
-                    error!(filename, error = %err, "Failed to get tags");
-                    false
-                }
-            }
-        })
-        .collect();
+    let mut filenames = hook_filenames(hook, filenames)?;
 
     if filenames.is_empty() && !hook.always_run {
-        writeln!(
-            printer.stdout(),
-            "{}",
-            status_line(
-                &hook.name,
-                columns,
-                SKIPPED,
-                Style::new().black().on_cyan(),
-                NO_FILES,
-            )
-        )?;
-        return Ok((true, diff));
+        return Ok((HookResult::Skipped(SkipReason::NoFiles), diff));
     }
 
-    write!(
-        printer.stdout(),
-        "{}{}",
-        &hook.name,
-        ".".repeat(columns - hook.name.width_cjk() - 6 - 1)
-    )?;
-    std::io::stdout().flush()?;
+    let name = display_name(&hook.name, columns);
 
-    let start = std::time::Instant::now();
+    // Only passing results are cached, so a hit is reported as passed.
+    let mut cache_key = None;
+    if let Some(store) = cache.filter(|_| is_cacheable(hook, verbose)) {
+        match hook_result_key(hook, &filenames, &env_vars) {
+            Ok(key) if store.hook_passed(&key)? => {
+                writeln!(
+                    printer.stdout(),
+                    "{}",
+                    status_line(&name, columns, PASSED, Style::new().on_green(), CACHED)
+                )?;
+                let result = HookResult::Ran {
+                    success: true,
+                    filenames,
+                };
+                return Ok((result, diff));
+            }
+            Ok(key) => cache_key = Some((store, key)),
+            Err(err) => warn!(hook = %hook.id, %err, "Failed to compute the hook result key"),
+        }
+    }
 
-    let (status, output) = if hook.pass_filenames {
+    let count = if show_file_count {
+        file_count(filenames.len())
+    } else {
+        String::new()
+    };
+    let dots = ".".repeat(columns - name.width_cjk() - 6 - count.len() - 1);
+    write!(printer.stdout(), "{name}{dots}{count}")?;
+    std::io::stdout().flush()?;
+
+    let relative;
+    let entry_filenames = if hook.pass_filenames {
         shuffle(&mut filenames);
-        hook.language.run(hook, &filenames, env_vars).await?
+        if let Some(work_dir) = hook.work_dir.as_deref() {
+            relative = relative_filenames(&filenames, Path::new(work_dir))?;
+            relative.iter().collect()
+        } else {
+            filenames.clone()
+        }
+    } else {
+        Vec::new()
+    };
+    let command = if verbose {
+        command_line(hook, &entry_filenames)?
     } else {
-        hook.language.run(hook, &[], env_vars).await?
+        None
     };
 
+    let start = std::time::Instant::now();
+
+    let (status, output) = run_entry(hook, &entry_filenames, env_vars).await?;
+
     let duration = start.elapsed();
 
     let new_diff = get_diff().await?;
@@ -277,116 +508,405 @@ async fn run_hook(
     let success = status == 0 && !file_modified;
 
     if success {
-        writeln!(printer.stdout(), "{}", "Passed".on_green())?;
+        writeln!(printer.stdout(), "{}", PASSED.on_green())?;
+        // The cache is only an optimization, don't fail the run.
+        if let Some((store, key)) = &cache_key {
+            if let Err(err) = store.save_hook_passed(key) {
+                warn!(hook = %hook.id, %err, "Failed to save the hook result");
+            }
+        }
     } else {
-        writeln!(printer.stdout(), "{}", "Failed".on_red())?;
+        // In quiet mode, the hook name is not printed until we know it failed.
+        if printer == Printer::Quiet {
+            write!(printer.stdout_important(), "{name}{dots}{count}")?;
+        }
+        writeln!(printer.stdout_important(), "{}", "Failed".on_red())?;
     }
 
+    // The output goes to the log file instead of the terminal, whether the hook passed or not.
+    if let Some(file) = hook.log_file.as_deref() {
+        write_log_file(Path::new(file), output.trim_ascii())?;
+    }
+
+    // Failures are reported even in quiet mode.
+    let mut out = if success {
+        printer.stdout()
+    } else {
+        printer.stdout_important()
+    };
+
     if verbose || hook.verbose || !success {
-        writeln!(
-            printer.stdout(),
-            "{}",
-            format!("- hook id: {}", hook.id).dimmed()
-        )?;
+        writeln!(out, "{}", format!("- hook id: {hook_id}").dimmed())?;
         if verbose || hook.verbose {
             writeln!(
-                printer.stdout(),
+                out,
                 "{}",
                 format!("- duration: {:.2?}s", duration.as_secs_f64()).dimmed()
             )?;
         }
+        if let Some(command) = &command {
+            writeln!(out, "{}", format!("- command: {command}").dimmed())?;
+            let cwd = hook.work_dir.as_deref().unwrap_or(".");
+            writeln!(out, "{}", format!("- cwd: {cwd}").dimmed())?;
+            let bin_dir = hook
+                .environment_dir()
+                .and_then(|env_dir| hook.language.bin_dir(&env_dir));
+            if let Some(bin_dir) = bin_dir {
+                writeln!(
+                    out,
+                    "{}",
+                    format!("- bin dir: {}", bin_dir.user_display()).dimmed()
+                )?;
+            }
+        }
         if status != 0 {
-            writeln!(
-                printer.stdout(),
-                "{}",
-                format!("- exit code: {status}").dimmed()
-            )?;
+            writeln!(out, "{}", format!("- exit code: {status}").dimmed())?;
         }
         if file_modified {
+            writeln!(out, "{}", "- files were modified by this hook".dimmed())?;
+        }
+
+        // To be consistent with pre-commit, merge stderr into stdout.
+        let stdout = truncate_output(output.trim_ascii(), max_output_lines);
+        if !stdout.is_empty() && hook.log_file.is_none() {
             writeln!(
-                printer.stdout(),
+                out,
                 "{}",
-                "- files were modified by this hook".dimmed()
+                textwrap::indent(&String::from_utf8_lossy(&stdout), "  ").dimmed()
             )?;
         }
+    }
 
-        // To be consistent with pre-commit, merge stderr into stdout.
-        let stdout = output.trim_ascii();
-        if !stdout.is_empty() {
-            if let Some(file) = hook.log_file.as_deref() {
-                fs_err::OpenOptions::new()
-                    .create(true)
-                    .append(true)
-                    .open(file)
-                    .and_then(|mut f| {
-                        f.write_all(stdout)?;
-                        Ok(())
-                    })?;
-            } else {
-                writeln!(
-                    printer.stdout(),
-                    "{}",
-                    textwrap::indent(&String::from_utf8_lossy(stdout), "  ").dimmed()
-                )?;
-            };
+    // Annotations are written to stdout, where GitHub Actions picks them up.
+    if !success && output_format == OutputFormat::Github {
+        for annotation in github_annotations(hook, &filenames, &output) {
+            writeln!(printer.stdout_important(), "{annotation}")?;
         }
     }
 
-    Ok((success, new_diff))
+    Ok((HookResult::Ran { success, filenames }, new_diff))
 }
 
-fn target_concurrency(serial: bool) -> usize {
-    if serial || std::env::var_os("PRE_COMMIT_NO_CONCURRENCY").is_some() {
-        1
-    } else {
-        std::thread::available_parallelism()
-            .map(std::num::NonZero::get)
-            .unwrap_or(1)
+/// Select the files a hook runs on, by its `files`/`exclude` patterns and its file types.
+pub fn hook_filenames<'a>(hook: &Hook, filenames: &'a [String]) -> Result<Vec<&'a String>> {
+    let filter = FilenameFilter::from_hook(hook)?;
+    let filenames = filenames
+        .into_par_iter()
+        .filter(|filename| filter.filter(filename));
+
+    let filter = FileTagFilter::from_hook(hook);
+    Ok(filenames
+        .filter(|filename| {
+            let path = Path::new(filename);
+            match tags_from_path_cached(path) {
+                Ok(tags) => filter.filter(&tags),
+                Err(err) => {
+                    error!(filename, error = %err, "Failed to get tags");
+                    false
+                }
+            }
+        })
+        .collect())
+}
+
+/// The command line a hook runs, to reproduce it by hand, `None` for built-in meta hooks.
+///
+/// Only the first few filenames are shown, the rest are elided with `…`.
+fn command_line(hook: &Hook, filenames: &[&String]) -> Result<Option<String>> {
+    if let Repo::Meta { .. } = hook.repo() {
+        return Ok(None);
+    }
+
+    let mut argv = hook.entry_argv()?;
+    argv.extend(hook.args.iter().cloned());
+    argv.extend(
+        filenames
+            .iter()
+            .take(COMMAND_LINE_FILENAMES)
+            .map(|filename| (*filename).clone()),
+    );
+    let mut command =
+        shlex::try_join(argv.iter().map(String::as_str)).unwrap_or_else(|_| argv.join(" "));
+    if filenames.len() > COMMAND_LINE_FILENAMES {
+        command.push_str(" …");
     }
+    Ok(Some(command))
 }
 
-// TODO: do a more accurate calculation
-fn partitions<'a>(
-    hook: &'a Hook,
-    filenames: &'a [&String],
-    concurrency: usize,
-) -> Vec<Vec<&'a String>> {
-    // If there are no filenames, we still want to run the hook once.
-    if filenames.is_empty() {
-        return vec![vec![]];
+/// Run the hook entry, meta hooks are built in instead of run with their language.
+async fn run_entry(
+    hook: &Hook,
+    filenames: &[&String],
+    env_vars: Arc<HashMap<&'static str, String>>,
+) -> Result<(i32, Vec<u8>)> {
+    if let Repo::Meta { .. } = hook.repo() {
+        meta_hooks::run(hook, filenames).await
+    } else {
+        hook.language.run(hook, filenames, env_vars).await
     }
+}
 
-    let max_per_batch = max(4, filenames.len().div_ceil(concurrency));
-    // TODO: subtract the env size
-    let max_cli_length = if cfg!(unix) {
-        1 << 12
+/// The number of files a hook runs on, shown before its status.
+fn file_count(count: usize) -> String {
+    if count == 1 {
+        "(1 file)".to_string()
     } else {
-        (1 << 15) - 2048 // UNICODE_STRING max - headroom
+        format!("({count} files)")
+    }
+}
+
+/// Whether the result of a hook can be reused when it passed before.
+///
+/// A hook which is not passed filenames, or runs without files, may check anything in the
+/// repo, not only the files in the key, and so do meta hooks, which check the config against
+/// all files. Local hooks run scripts and tools of the project, which aren't pinned by a rev,
+/// so a change to them would go unnoticed. The output is not cached, so hooks which show it
+/// always run.
+fn is_cacheable(hook: &Hook, verbose: bool) -> bool {
+    hook.pass_filenames
+        && !hook.always_run
+        && !verbose
+        && !hook.verbose
+        && matches!(hook.repo(), Repo::Remote { .. })
+}
+
+/// The key of a hook result: the hex MD5 digest of the hook configuration, its environment,
+/// the env vars and the path and content of each file, so a change to any of them runs the
+/// hook again.
+///
+/// The environment is identified by its env key, which covers the repo rev, the language
+/// version and the additional dependencies, and by when it was installed, so a tool upgraded
+/// by `autoupdate` or by reinstalling the environment doesn't reuse stale passes.
+///
+/// Files are read from the working tree, which only has the staged content when the
+/// non-staged changes are stashed.
+fn hook_result_key(
+    hook: &Hook,
+    filenames: &[&String],
+    env_vars: &HashMap<&'static str, String>,
+) -> std::io::Result<String> {
+    let mut files = filenames
+        .into_par_iter()
+        .map(|filename| Ok((*filename, file_digest(Path::new(filename))?)))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    files.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+
+    let mut env_vars: Vec<_> = env_vars.iter().collect();
+    env_vars.sort_unstable();
+
+    let mut context = md5::Context::new();
+    let mut push = |field: &[u8]| {
+        context.consume(field);
+        context.consume(b"\0");
     };
+    push(hook.env_key().as_bytes());
+    push(installed_at(hook)?.as_bytes());
+    push(hook.id.as_bytes());
+    push(hook.entry.as_bytes());
+    push(hook.work_dir.as_deref().unwrap_or_default().as_bytes());
+    push(hook.args.len().to_string().as_bytes());
+    for arg in &hook.args {
+        push(arg.as_bytes());
+    }
+    for (key, value) in env_vars {
+        push(key.as_bytes());
+        push(value.as_bytes());
+    }
+    for (filename, digest) in files {
+        push(filename.as_bytes());
+        push(&digest.0);
+    }
+    Ok(format!("{:x}", context.compute()))
+}
 
-    let command_length =
-        hook.entry.len() + hook.args.iter().map(String::len).sum::<usize>() + hook.args.len();
+/// When the hook environment was installed, empty for languages without an environment.
+fn installed_at(hook: &Hook) -> std::io::Result<String> {
+    let Some(env_dir) = hook.environment_dir() else {
+        return Ok(String::new());
+    };
+    let modified = fs_err::metadata(env_dir.join(INSTALL_STATE_FILE))?.modified()?;
+    let since_epoch = modified.duration_since(UNIX_EPOCH).unwrap_or_default();
+    Ok(since_epoch.as_nanos().to_string())
+}
 
-    let mut partitions = Vec::new();
-    let mut current = Vec::new();
-    let mut current_length = command_length + 1;
+/// The digest of a file content, a symlink is hashed by its target.
+fn file_digest(path: &Path) -> std::io::Result<md5::Digest> {
+    let metadata = fs_err::symlink_metadata(path)?;
+    if metadata.is_symlink() {
+        let target = fs_err::read_link(path)?;
+        Ok(md5::compute(target.as_os_str().as_encoded_bytes()))
+    } else if metadata.is_dir() {
+        // Submodules, their content is not part of this repo.
+        Ok(md5::compute(b""))
+    } else {
+        Ok(md5::compute(fs_err::read(path)?))
+    }
+}
+
+/// Write the full output of a hook run to its log file, replacing the output of previous runs.
+fn write_log_file(path: &Path, output: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs_err::create_dir_all(parent)?;
+    }
+    fs_err::write(path, output)?;
+    Ok(())
+}
+
+/// Keep at most `max_lines` lines of the output, and append a marker with the number of truncated lines.
+fn truncate_output(output: &[u8], max_lines: usize) -> Cow<'_, [u8]> {
+    if max_lines == 0 {
+        return Cow::Borrowed(output);
+    }
 
-    for &filename in filenames {
-        let length = filename.len() + 1;
-        if current_length + length > max_cli_length || current.len() >= max_per_batch {
-            partitions.push(current);
-            current = Vec::new();
-            current_length = command_length + 1;
+    let mut lines = output.split_inclusive(|&b| b == b'\n');
+    let kept = lines
+        .by_ref()
+        .take(max_lines)
+        .map(<[u8]>::len)
+        .sum::<usize>();
+    let truncated = lines.count();
+    if truncated == 0 {
+        return Cow::Borrowed(output);
+    }
+
+    let mut out = output[..kept].to_vec();
+    if !out.ends_with(b"\n") {
+        out.push(b'\n');
+    }
+    out.extend(format!("... [{truncated} lines truncated]").as_bytes());
+    Cow::Owned(out)
+}
+
+/// Make the filenames relative to the working directory of the hook.
+fn relative_filenames(filenames: &[&String], work_dir: &Path) -> Result<Vec<String>> {
+    filenames
+        .iter()
+        .map(|filename| -> Result<String> {
+            let mut relative = relative_to(filename, work_dir)?
+                .to_string_lossy()
+                .to_string();
+            normalize_path(&mut relative);
+            Ok(relative)
+        })
+        .collect()
+}
+
+/// The default cap of repos to clone or install concurrently, these operations are mostly
+/// network-bound so more CPUs don't help.
+const DEFAULT_PARALLEL_REPOS_CAP: usize = 8;
+
+/// `jobs` beyond this many times the number of CPUs are most likely a typo.
+const JOBS_WARN_FACTOR: usize = 16;
+
+static CONCURRENCY: OnceLock<Concurrency> = OnceLock::new();
+
+/// Concurrency limits of a run.
+///
+/// `jobs` limits the hook batches running at the same time, and `repos` limits the repos being
+/// cloned or installed at the same time. Repos are prepared before any hook runs, so the two limits
+/// are independent.
+#[derive(Debug, Clone, Copy)]
+pub struct Concurrency {
+    pub jobs: usize,
+    pub repos: usize,
+    /// The maximum number of files passed to each hook invocation, on top of the command line
+    /// length limit.
+    pub batch_size: Option<NonZeroUsize>,
+}
+
+impl Concurrency {
+    /// Create the concurrency limits, unset limits default to the number of CPUs.
+    ///
+    /// `jobs` is taken from `--jobs`, then `PRE_COMMIT_JOBS`, then `jobs` of the config.
+    pub fn new(
+        jobs: Option<usize>,
+        config_jobs: Option<usize>,
+        repos: Option<NonZeroUsize>,
+        batch_size: Option<NonZeroUsize>,
+    ) -> Self {
+        let cpus = std::thread::available_parallelism()
+            .map(NonZeroUsize::get)
+            .unwrap_or(1);
+        let jobs = jobs.or_else(env_jobs).or(config_jobs);
+        Self {
+            jobs: resolve_jobs(jobs, cpus),
+            repos: repos.map_or(cpus.min(DEFAULT_PARALLEL_REPOS_CAP), NonZeroUsize::get),
+            batch_size,
         }
-        current.push(filename);
-        current_length += length;
     }
 
-    if !current.is_empty() {
-        partitions.push(current);
+    /// Set the concurrency limits for the current process, only the first call takes effect.
+    pub fn init(self) {
+        let _ = CONCURRENCY.set(self);
+    }
+
+    /// Get the concurrency limits for the current process.
+    pub fn get() -> Self {
+        *CONCURRENCY.get_or_init(|| Self::new(None, None, None, None))
+    }
+}
+
+fn env_jobs() -> Option<usize> {
+    let value = std::env::var("PRE_COMMIT_JOBS").ok()?;
+    let Ok(jobs) = value.trim().parse() else {
+        warn_user_once!("Ignoring invalid `PRE_COMMIT_JOBS`: `{value}`");
+        return None;
+    };
+    Some(jobs)
+}
+
+/// Resolve the number of jobs, at least 1 and the number of CPUs by default.
+fn resolve_jobs(jobs: Option<usize>, cpus: usize) -> usize {
+    let Some(jobs) = jobs else {
+        return cpus;
+    };
+    if jobs > cpus.saturating_mul(JOBS_WARN_FACTOR) {
+        warn_user_once!(
+            "Running {jobs} jobs on {cpus} CPU(s), hooks will compete for resources; consider lowering `--jobs`"
+        );
+    }
+    jobs.max(1)
+}
+
+fn target_concurrency(serial: bool) -> usize {
+    if serial || std::env::var_os("PRE_COMMIT_NO_CONCURRENCY").is_some() {
+        1
+    } else {
+        Concurrency::get().jobs
+    }
+}
+
+/// The hook entry and args, which come before the filenames on the command line.
+fn command_argv(hook: &Hook) -> Vec<&str> {
+    std::iter::once(hook.entry.as_str())
+        .chain(hook.args.iter().map(String::as_str))
+        .collect()
+}
+
+/// Split the filenames into batches that fit in the command line, spread over `concurrency`
+/// jobs and capped at `batch_size` files.
+///
+/// The batches are produced lazily, each borrows a slice of `filenames`.
+fn partitions<'a, 'b>(
+    argv: &[&str],
+    filenames: &'a [&'b String],
+    concurrency: usize,
+    batch_size: Option<NonZeroUsize>,
+) -> impl Iterator<Item = &'a [&'b String]> {
+    let mut max_per_batch = NonZeroUsize::new(max(4, filenames.len().div_ceil(concurrency)))
+        .expect("at least 4 files per batch");
+    if let Some(batch_size) = batch_size {
+        max_per_batch = max_per_batch.min(batch_size);
     }
 
-    partitions
+    // If there are no filenames, we still want to run the hook once.
+    let empty = filenames.is_empty().then_some(&[][..]);
+    empty.into_iter().chain(process::batches(
+        argv,
+        filenames,
+        process::max_command_length(),
+        max_per_batch,
+    ))
 }
 
 pub async fn run_by_batch<T, F, Fut>(hook: &Hook, filenames: &[&String], run: F) -> Result<Vec<T>>
@@ -396,15 +916,10 @@ where
     Fut: Future<Output = Result<T>> + Send + 'static,
     T: Send + 'static,
 {
-    let mut concurrency = target_concurrency(hook.require_serial);
-
-    // Split files into batches
-    let partitions = partitions(hook, filenames, concurrency);
-    concurrency = concurrency.min(partitions.len());
+    let concurrency = target_concurrency(hook.require_serial);
     let semaphore = Arc::new(tokio::sync::Semaphore::new(concurrency));
     trace!(
         total_files = filenames.len(),
-        partitions = partitions.len(),
         concurrency = concurrency,
         "Running {}",
         hook.id,
@@ -412,24 +927,26 @@ where
 
     let run = Arc::new(run);
 
-    // Spawn tasks for each batch
+    // Spawn a task for each batch once a job is free, so only the running batches are copied.
     let mut tasks = JoinSet::new();
-
-    for batch in partitions {
-        let semaphore = semaphore.clone();
+    let argv = command_argv(hook);
+    let batches = partitions(&argv, filenames, concurrency, Concurrency::get().batch_size);
+    for batch in batches {
+        let permit = semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|_| anyhow::anyhow!("Failed to acquire semaphore"))?;
         let run = run.clone();
 
-        let batch: Vec<_> = batch.into_iter().map(ToString::to_string).collect();
+        let batch: Vec<_> = batch.iter().map(ToString::to_string).collect();
 
         tasks.spawn(async move {
-            let _permit = semaphore
-                .acquire()
-                .await
-                .map_err(|_| anyhow::anyhow!("Failed to acquire semaphore"))?;
-
+            let _permit = permit;
             run(batch).await
         });
     }
+    trace!(partitions = tasks.len(), "Spawned {}", hook.id);
 
     let mut results = Vec::new();
     while let Some(result) = tasks.join_next().await {
@@ -470,6 +987,7 @@ impl IntentToAddKeeper {
         // Restore the intent-to-add changes.
         if !self.0.is_empty() {
             Command::new(GIT.as_ref()?)
+                .envs(GIT_ENV.iter().cloned())
                 .arg("add")
                 .arg("--intent-to-add")
                 .arg("--")
@@ -495,9 +1013,7 @@ impl Drop for IntentToAddKeeper {
 }
 
 impl WorkingTreeKeeper {
-    async fn clean(patch_dir: &Path) -> Result<Self> {
-        let tree = git::write_tree().await?;
-
+    async fn clean(patch_dir: &Path, tree: &str) -> Result<Self> {
         let mut cmd = git_cmd("git diff-index")?;
         let output = cmd
             .arg("diff-index")
@@ -554,6 +1070,7 @@ impl WorkingTreeKeeper {
 
     fn checkout_working_tree() -> Result<()> {
         let status = Command::new(GIT.as_ref()?)
+            .envs(GIT_ENV.iter().cloned())
             .arg("-c")
             .arg("submodule.recurse=0")
             .arg("checkout")
@@ -573,6 +1090,7 @@ impl WorkingTreeKeeper {
 
     fn git_apply(patch: &Path) -> Result<()> {
         let status = Command::new(GIT.as_ref()?)
+            .envs(GIT_ENV.iter().cloned())
             .arg("apply")
             .arg("--whitespace=nowarn")
             .arg(patch)
@@ -633,9 +1151,25 @@ pub struct WorkTreeKeeper {
     working_tree: Option<WorkingTreeKeeper>,
 }
 
-#[derive(Default)]
 pub struct RestoreGuard {
-    _guard: (),
+    /// The tree of the index when the working tree was cleaned.
+    tree: String,
+}
+
+impl RestoreGuard {
+    /// The tree of the index before hooks ran, the baseline of the changes made by hooks.
+    pub fn tree(&self) -> &str {
+        &self.tree
+    }
+
+    /// Leave the working tree as the hooks left it, the non-staged changes are not re-applied.
+    pub fn keep_working_tree(self) {
+        if let Some(keeper) = &mut *RESTORE_WORKTREE.lock().unwrap() {
+            keeper.keep_working_tree();
+        }
+        // Only the intent-to-add changes are left to restore.
+        drop(self);
+    }
 }
 
 impl Drop for RestoreGuard {
@@ -650,9 +1184,11 @@ impl WorkTreeKeeper {
     /// Clear intent-to-add changes from the index and clear the non-staged changes from the working directory.
     /// Restore them when the instance is dropped.
     pub async fn clean(store: &Store) -> Result<RestoreGuard> {
+        let intent_to_add = IntentToAddKeeper::clean().await?;
+        let tree = git::write_tree().await?;
         let cleaner = Self {
-            intent_to_add: Some(IntentToAddKeeper::clean().await?),
-            working_tree: Some(WorkingTreeKeeper::clean(store.path()).await?),
+            intent_to_add: Some(intent_to_add),
+            working_tree: Some(WorkingTreeKeeper::clean(store.path(), &tree).await?),
         };
 
         // Set to the global for the cleanup hook.
@@ -665,7 +1201,28 @@ impl WorkTreeKeeper {
             }
         });
 
-        Ok(RestoreGuard::default())
+        Ok(RestoreGuard { tree })
+    }
+
+    fn keep_working_tree(&mut self) {
+        let Some(patch) = self
+            .working_tree
+            .take()
+            .and_then(|mut keeper| keeper.0.take())
+        else {
+            return;
+        };
+        eprintln!(
+            "{}",
+            format!(
+                "\nNon-staged changes are not restored, they are saved in `{}`.\n\
+                 To restore them, run `git checkout -- .` to discard the hook modifications, \
+                 then `git apply {}`",
+                patch.user_display(),
+                patch.user_display()
+            )
+            .yellow()
+        );
     }
 
     /// Restore the intent-to-add changes and non-staged changes.
@@ -674,3 +1231,51 @@ impl WorkTreeKeeper {
         self.working_tree.take();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn elide_long_hook_names() {
+        assert_eq!(display_name("short", 80), "short");
+
+        let name = "a very long hook name that does not fit in a narrow terminal";
+        let columns = STATUS_WIDTH + 20;
+        let elided = display_name(name, columns);
+        assert!(elided.ends_with('…'));
+        assert!(name.starts_with(elided.trim_end_matches('…')));
+        assert!(elided.width_cjk() <= 20);
+        assert!(elided.width_cjk() >= 19);
+    }
+
+    #[test]
+    fn resolve_jobs_default_and_clamp() {
+        assert_eq!(resolve_jobs(None, 8), 8);
+        assert_eq!(resolve_jobs(Some(0), 8), 1);
+        assert_eq!(resolve_jobs(Some(3), 8), 3);
+        assert_eq!(resolve_jobs(Some(1000), 8), 1000);
+    }
+
+    #[test]
+    fn partitions_batch_size() {
+        let batch_counts = |filenames: &[String], batch_size: Option<usize>| {
+            let filenames: Vec<_> = filenames.iter().collect();
+            let batch_size = batch_size.and_then(NonZeroUsize::new);
+            partitions(&[], &filenames, 1, batch_size)
+                .map(<[_]>::len)
+                .collect::<Vec<_>>()
+        };
+
+        let short: Vec<_> = (0..100).map(|i| format!("file{i:02}.txt")).collect();
+        assert_eq!(batch_counts(&short, None), [100]);
+        assert_eq!(batch_counts(&short, Some(1000)), [100]);
+        assert_eq!(batch_counts(&short, Some(30)), [30, 30, 30, 10]);
+        assert_eq!(batch_counts(&short, Some(1)), [1; 100]);
+
+        // Long filenames still fit in the command line length of the platform.
+        let long: Vec<_> = (0..100).map(|i| format!("{i:0>200}")).collect();
+        assert_eq!(batch_counts(&long, Some(50)), [50, 50]);
+        assert_eq!(batch_counts(&long, Some(10)), [10; 10]);
+    }
+}
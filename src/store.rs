@@ -1,5 +1,6 @@
 use std::fmt::Write;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 
 use anyhow::Result;
 use rusqlite::Connection;
@@ -10,8 +11,16 @@ use crate::config::ConfigRemoteRepo;
 use crate::fs::{copy_dir_all, LockedFile};
 use crate::git::clone_repo;
 use crate::hook::{Hook, Repo};
+use crate::identify;
 use crate::printer::Printer;
 
+/// The name and rev of the repo for local hooks.
+pub const LOCAL_NAME: &str = "local";
+pub const LOCAL_REV: &str = "1";
+
+/// Marks a completely cloned repo, kept in the git dir so it's not part of the checkout.
+pub const CLONE_COMPLETE_MARKER: &str = "pre-commit-clone-complete";
+
 #[derive(Debug, Error)]
 pub enum Error {
     #[error("Home directory not found")]
@@ -110,6 +119,42 @@ impl Store {
             )?;
             conn
         };
+        // Added later, so create it for existing databases as well.
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS configs (
+                path TEXT NOT NULL PRIMARY KEY
+            );",
+            [],
+        )?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS file_tags (
+                root TEXT NOT NULL,
+                path TEXT NOT NULL,
+                mtime INTEGER NOT NULL,
+                size INTEGER NOT NULL,
+                mode INTEGER NOT NULL,
+                tags TEXT NOT NULL,
+                PRIMARY KEY (root, path)
+            );",
+            [],
+        )?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS hook_results (
+                key TEXT NOT NULL PRIMARY KEY,
+                used_at INTEGER NOT NULL DEFAULT 0
+            );",
+            [],
+        )?;
+        // `used_at` was added later, results without it are pruned by the next `gc`.
+        let has_used_at = conn
+            .prepare("SELECT 1 FROM pragma_table_info('hook_results') WHERE name = 'used_at'")?
+            .exists([])?;
+        if !has_used_at {
+            conn.execute(
+                "ALTER TABLE hook_results ADD COLUMN used_at INTEGER NOT NULL DEFAULT 0",
+                [],
+            )?;
+        }
 
         Ok(Self {
             conn: Some(conn),
@@ -136,6 +181,170 @@ impl Store {
             .collect::<Result<Vec<_>, Error>>()
     }
 
+    /// List all repos as `(name, rev, path)`, without loading their manifests.
+    /// The name has the dependencies appended, see [`Store::repo_name`].
+    pub fn repo_entries(&self) -> Result<Vec<(String, String, String)>, Error> {
+        let mut stmt = self.conn().prepare("SELECT repo, ref, path FROM repos")?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+            .collect::<Result<_, _>>()?;
+        Ok(rows)
+    }
+
+    /// Remove a repo from the store, including its directory.
+    pub fn remove_repo(&self, name: &str, rev: &str, path: &Path) -> Result<(), Error> {
+        if path.try_exists()? {
+            fs_err::remove_dir_all(path)?;
+        }
+        self.conn()
+            .execute("DELETE FROM repos WHERE repo = ? AND ref = ?", [name, rev])?;
+        Ok(())
+    }
+
+    /// Record that the config file uses this store, so `gc` keeps the repos it references.
+    pub fn mark_config_used(&self, path: &Path) -> Result<(), Error> {
+        let path = std::path::absolute(path)?;
+        self.conn().execute(
+            "INSERT OR IGNORE INTO configs (path) VALUES (?)",
+            [path.to_string_lossy()],
+        )?;
+        Ok(())
+    }
+
+    /// List all config files that have used this store.
+    pub fn configs(&self) -> Result<Vec<PathBuf>, Error> {
+        let mut stmt = self.conn().prepare("SELECT path FROM configs")?;
+        let rows = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .map(|path| path.map(PathBuf::from))
+            .collect::<Result<_, _>>()?;
+        Ok(rows)
+    }
+
+    /// Forget a config file, e.g. it has been deleted.
+    pub fn remove_config(&self, path: &Path) -> Result<(), Error> {
+        self.conn().execute(
+            "DELETE FROM configs WHERE path = ?",
+            [path.to_string_lossy()],
+        )?;
+        Ok(())
+    }
+
+    /// Load the cached tags of the files in the repo at `root` into the tags cache.
+    pub fn load_file_tags(&self, root: &Path) -> Result<(), Error> {
+        let mut stmt = self
+            .conn()
+            .prepare("SELECT path, mtime, size, mode, tags FROM file_tags WHERE root = ?")?;
+        let rows: Vec<(String, i64, i64, i64, String)> = stmt
+            .query_map([root.to_string_lossy()], |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                ))
+            })?
+            .collect::<Result<_, _>>()?;
+
+        identify::load_tags_cache(rows.into_iter().filter_map(
+            |(path, mtime, size, mode, tags)| {
+                Some((
+                    path,
+                    mtime,
+                    u64::try_from(size).ok()?,
+                    u32::try_from(mode).ok()?,
+                    tags,
+                ))
+            },
+        ));
+        Ok(())
+    }
+
+    /// Save the file tags computed since they were loaded, for the repo at `root`.
+    pub fn save_file_tags(&self, root: &Path) -> Result<(), Error> {
+        let updated = identify::take_updated_tags();
+        if updated.is_empty() {
+            return Ok(());
+        }
+
+        let root = root.to_string_lossy();
+        let tx = self.conn().unchecked_transaction()?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT OR REPLACE INTO file_tags (root, path, mtime, size, mode, tags)
+                VALUES (?, ?, ?, ?, ?, ?)",
+            )?;
+            for (path, cached) in updated {
+                let Ok(size) = i64::try_from(cached.size) else {
+                    continue;
+                };
+                stmt.execute(rusqlite::params![
+                    root,
+                    path,
+                    cached.mtime,
+                    size,
+                    i64::from(cached.mode),
+                    cached.tags.join(","),
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Forget the cached tags of files that no longer exist, and of repos that were removed.
+    /// Returns the number of entries removed.
+    pub fn prune_file_tags(&self) -> Result<usize, Error> {
+        let mut stmt = self.conn().prepare("SELECT root, path FROM file_tags")?;
+        let rows: Vec<(String, String)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_, _>>()?;
+
+        let tx = self.conn().unchecked_transaction()?;
+        let mut removed = 0;
+        {
+            let mut stmt = tx.prepare("DELETE FROM file_tags WHERE root = ? AND path = ?")?;
+            for (root, path) in rows {
+                if !Path::new(&root).join(&path).try_exists()? {
+                    removed += stmt.execute([root, path])?;
+                }
+            }
+        }
+        tx.commit()?;
+        Ok(removed)
+    }
+
+    /// Check whether a hook passed before, with the key from `run::hook_result_key`.
+    /// A hit marks the result as used, so `gc` keeps it.
+    pub fn hook_passed(&self, key: &str) -> Result<bool, Error> {
+        let updated = self.conn().execute(
+            "UPDATE hook_results SET used_at = ? WHERE key = ?",
+            rusqlite::params![unix_now(), key],
+        )?;
+        Ok(updated > 0)
+    }
+
+    /// Record that a hook passed, so the next run with the same key can skip it.
+    pub fn save_hook_passed(&self, key: &str) -> Result<(), Error> {
+        self.conn().execute(
+            "INSERT OR REPLACE INTO hook_results (key, used_at) VALUES (?, ?)",
+            rusqlite::params![key, unix_now()],
+        )?;
+        Ok(())
+    }
+
+    /// Forget the hook results that have not been used for `max_age`.
+    /// Returns the number of results removed.
+    pub fn prune_hook_results(&self, max_age: Duration) -> Result<usize, Error> {
+        let max_age = i64::try_from(max_age.as_secs()).unwrap_or(i64::MAX);
+        let removed = self.conn().execute(
+            "DELETE FROM hook_results WHERE used_at < ?",
+            [unix_now().saturating_sub(max_age)],
+        )?;
+        Ok(removed)
+    }
+
     // Append dependencies to the repo name as the key.
     fn repo_name(repo: &str, deps: &[String]) -> String {
         let mut name = repo.to_string();
@@ -183,16 +392,15 @@ impl Store {
         deps: &[String],
         printer: Printer,
     ) -> Result<PathBuf, Error> {
-        const LOCAL_NAME: &str = "local";
-        const LOCAL_REV: &str = "1";
-
         if hook.language.environment_dir().is_none() {
             return Err(Error::LocalHookNoNeedEnv(hook.id.clone()));
         }
 
         let path = if let Some((_, _, path)) = self.get_repo(LOCAL_NAME, LOCAL_REV, deps)? {
+            report_cache(printer, true, "repo", &Self::repo_name(LOCAL_NAME, deps))?;
             path
         } else {
+            report_cache(printer, false, "repo", &Self::repo_name(LOCAL_NAME, deps))?;
             let temp = tempfile::Builder::new()
                 .prefix("repo")
                 .keep(true)
@@ -209,72 +417,101 @@ impl Store {
         Ok(PathBuf::from(path))
     }
 
-    /// Clone a remote repo into the store.
-    pub async fn prepare_remote_repo(
+    /// Get the path of a cached remote repo, if it was completely prepared.
+    ///
+    /// An incomplete repo, e.g. an interrupted clone or a directory removed by hand,
+    /// is dropped from the store so it's prepared again.
+    fn cached_remote_repo(
         &self,
-        repo_config: &ConfigRemoteRepo,
+        repo: &str,
+        rev: &str,
         deps: &[String],
+    ) -> Result<Option<PathBuf>, Error> {
+        let Some((name, rev, path)) = self.get_repo(repo, rev, deps)? else {
+            return Ok(None);
+        };
+        let path = PathBuf::from(path);
+        if path.join(".git").join(CLONE_COMPLETE_MARKER).try_exists()? {
+            return Ok(Some(path));
+        }
+
+        debug!(path = %path.display(), "Removing incomplete repo");
+        self.remove_repo(&name, &rev, &path)?;
+        Ok(None)
+    }
+
+    /// Clone a remote repo into the store, or reuse the cached clone without network access.
+    pub async fn clone_or_reuse(
+        &self,
+        url: &str,
+        rev: &str,
         printer: Printer,
     ) -> Result<PathBuf, Error> {
-        if let Some((_, _, path)) = self.get_repo(
-            repo_config.repo.as_str(),
-            repo_config.rev.as_str(),
-            deps.as_ref(),
-        )? {
-            return Ok(PathBuf::from(path));
+        if let Some(path) = self.cached_remote_repo(url, rev, &[])? {
+            debug!(path = %path.display(), "Reusing cached repo {url}@{rev}");
+            report_cache(printer, true, "repo", &format!("{url}@{rev}"))?;
+            return Ok(path);
         }
+        report_cache(printer, false, "repo", &format!("{url}@{rev}"))?;
 
-        // Clone and checkout the repo.
         let temp = tempfile::Builder::new()
             .prefix("repo")
             .keep(true)
             .tempdir_in(&self.path)?;
         let path = temp.path().to_string_lossy().to_string();
 
+        writeln!(printer.stdout(), "Cloning {url}@{rev}")?;
+        debug!(target = path, repo = format!("{url}@{rev}"), "Cloning repo");
+        clone_repo(url, rev, temp.path()).await?;
+        fs_err::write(temp.path().join(".git").join(CLONE_COMPLETE_MARKER), b"")?;
+
+        self.insert_repo(url, rev, &path, &[])?;
+        Ok(PathBuf::from(path))
+    }
+
+    /// Prepare a remote repo in the store, with the additional dependencies.
+    pub async fn prepare_remote_repo(
+        &self,
+        repo_config: &ConfigRemoteRepo,
+        deps: &[String],
+        printer: Printer,
+    ) -> Result<PathBuf, Error> {
+        let url = repo_config.repo.as_str();
+        let rev = repo_config.rev.as_str();
         if deps.is_empty() {
-            writeln!(
-                printer.stdout(),
-                "Cloning {}@{}",
-                repo_config.repo,
-                repo_config.rev
-            )?;
-            debug!(
-                target = path,
-                repo = format!("{}@{}", repo_config.repo, repo_config.rev),
-                "Cloning repo",
-            );
-            clone_repo(repo_config.repo.as_str(), &repo_config.rev, temp.path()).await?;
-        } else {
-            // TODO: use hardlink?
-            // Optimization: This is an optimization from the Python pre-commit implementation.
-            // Copy already cloned base remote repo.
-            let (_, _, base_repo_path) = self
-                .get_repo(repo_config.repo.as_str(), repo_config.rev.as_str(), &[])?
-                .expect("base repo should be cloned before");
-            writeln!(
-                printer.stdout(),
-                "Preparing {}@{} with dependencies {}",
-                repo_config.repo,
-                repo_config.rev,
-                deps.join(","),
-            )?;
-            debug!(
-                source = base_repo_path,
-                target = path,
-                deps = deps.join(","),
-                "Preparing {}@{} by copying",
-                repo_config.repo,
-                repo_config.rev,
-            );
-            copy_dir_all(base_repo_path, &path)?;
+            return self.clone_or_reuse(url, rev, printer).await;
         }
+        let name = format!("{}@{rev}", Self::repo_name(url, deps));
+        if let Some(path) = self.cached_remote_repo(url, rev, deps)? {
+            report_cache(printer, true, "repo", &name)?;
+            return Ok(path);
+        }
+        report_cache(printer, false, "repo", &name)?;
+
+        let temp = tempfile::Builder::new()
+            .prefix("repo")
+            .keep(true)
+            .tempdir_in(&self.path)?;
+        let path = temp.path().to_string_lossy().to_string();
 
-        self.insert_repo(
-            repo_config.repo.as_str(),
-            repo_config.rev.as_str(),
-            &path,
-            deps,
+        // TODO: use hardlink?
+        // Optimization: This is an optimization from the Python pre-commit implementation.
+        // Copy already cloned base remote repo, including its completion marker.
+        let base_repo_path = self.clone_or_reuse(url, rev, printer).await?;
+        writeln!(
+            printer.stdout(),
+            "Preparing {url}@{rev} with dependencies {}",
+            deps.join(","),
         )?;
+        debug!(
+            source = %base_repo_path.display(),
+            target = path,
+            deps = deps.join(","),
+            "Preparing {url}@{rev} by copying",
+        );
+        copy_dir_all(&base_repo_path, &path)?;
+
+        self.insert_repo(url, rev, &path, deps)?;
 
         Ok(PathBuf::from(path))
     }
@@ -293,6 +530,25 @@ impl Store {
     }
 }
 
+/// Report whether a repo or an environment is reused from the store, with `--verbose`.
+///
+/// One line per item, `Cache hit: <kind> <name>` or `Cache miss: <kind> <name>`,
+/// to see what is rebuilt, e.g. in CI.
+/// The current time as seconds since the Unix epoch.
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |now| i64::try_from(now.as_secs()).unwrap_or(i64::MAX))
+}
+
+pub fn report_cache(printer: Printer, hit: bool, kind: &str, name: &str) -> std::fmt::Result {
+    if printer != Printer::Verbose {
+        return Ok(());
+    }
+    let result = if hit { "hit" } else { "miss" };
+    writeln!(printer.stdout(), "Cache {result}: {kind} {name}")
+}
+
 // TODO
 /// For local repo, creates a dummy package for each supported language, to make
 /// the installation code like `pip install .` work.
@@ -310,3 +566,56 @@ fn make_local_repo(_repo: &str, path: &Path) -> Result<(), Error> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prune_hook_results() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let store = Store::from_path(dir.path()).init()?;
+
+        store.save_hook_passed("old")?;
+        store.save_hook_passed("recent")?;
+        store.conn().execute(
+            "UPDATE hook_results SET used_at = ? WHERE key = 'old'",
+            [unix_now() - 3600],
+        )?;
+
+        assert_eq!(store.prune_hook_results(Duration::from_secs(60))?, 1);
+        assert!(!store.hook_passed("old")?);
+        assert!(store.hook_passed("recent")?);
+        Ok(())
+    }
+
+    #[test]
+    fn prune_file_tags() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let store = Store::from_path(dir.path().join("store")).init()?;
+        let root = dir.path().join("repo");
+        fs_err::create_dir(&root)?;
+        fs_err::write(root.join("kept.txt"), "")?;
+
+        for (root, path) in [
+            (root.as_path(), "kept.txt"),
+            (root.as_path(), "deleted.txt"),
+            (&dir.path().join("removed-repo"), "a.txt"),
+        ] {
+            store.conn().execute(
+                "INSERT INTO file_tags (root, path, mtime, size, mode, tags)
+                VALUES (?, ?, 0, 0, 0, 'file')",
+                [root.to_string_lossy().as_ref(), path],
+            )?;
+        }
+
+        assert_eq!(store.prune_file_tags()?, 2);
+        let paths: Vec<String> = store
+            .conn()
+            .prepare("SELECT path FROM file_tags")?
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+        assert_eq!(paths, ["kept.txt"]);
+        Ok(())
+    }
+}
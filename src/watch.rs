@@ -0,0 +1,328 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use tracing::debug;
+
+use crate::git;
+
+/// How long the files must stay unchanged before a burst of changes is reported.
+const DEBOUNCE: std::time::Duration = std::time::Duration::from_millis(200);
+
+/// Wait until the files of the project change, and then stay unchanged for a short while, so a
+/// burst of changes (e.g. saving many files or switching branches) triggers a single re-run.
+///
+/// Only tracked files and untracked files which are not ignored count, changes to ignored files
+/// (e.g. build outputs) are skipped.
+pub(crate) async fn wait_for_changes() -> Result<()> {
+    imp::wait_for_changes().await?;
+    debug!("Files changed, re-running hooks");
+    Ok(())
+}
+
+/// The tracked files and the untracked files which are not ignored.
+async fn project_files() -> Result<HashSet<PathBuf>> {
+    let (tracked, untracked) =
+        futures::try_join!(git::get_all_files(), git::get_untracked_files())?;
+    Ok(tracked
+        .into_iter()
+        .chain(untracked)
+        .map(PathBuf::from)
+        .collect())
+}
+
+/// Whether a changed path is one of `files`, or a directory containing any of them.
+fn is_relevant(path: &Path, files: &HashSet<PathBuf>) -> bool {
+    files.contains(path) || files.iter().any(|file| file.starts_with(path))
+}
+
+/// Watch with inotify the directories containing the project files.
+///
+/// Directories created after the watch starts are not watched themselves, but creating them
+/// is reported if they contain project files.
+#[cfg(target_os = "linux")]
+mod imp {
+    use std::collections::{HashMap, HashSet};
+    use std::ffi::{CString, OsStr};
+    use std::io;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
+
+    use anyhow::Result;
+    use tracing::trace;
+
+    use super::{is_relevant, project_files, DEBOUNCE};
+
+    const EVENT_MASK: u32 = libc::IN_MODIFY
+        | libc::IN_ATTRIB
+        | libc::IN_CREATE
+        | libc::IN_DELETE
+        | libc::IN_MOVED_FROM
+        | libc::IN_MOVED_TO;
+
+    /// The size of `struct inotify_event` without the name.
+    const EVENT_HEADER: usize = 16;
+
+    pub(super) async fn wait_for_changes() -> Result<()> {
+        let files = project_files().await?;
+        let dirs: HashSet<_> = files
+            .iter()
+            .map(|file| file.parent().unwrap_or(Path::new("")).to_path_buf())
+            .chain([PathBuf::new()])
+            .collect();
+        let mut watcher = Watcher::new(dirs)?;
+
+        loop {
+            let (returned, changes) = tokio::task::spawn_blocking(move || {
+                let changes = watcher.wait(DEBOUNCE);
+                (watcher, changes)
+            })
+            .await?;
+            watcher = returned;
+
+            match changes? {
+                Changes::Overflow => return Ok(()),
+                Changes::Paths(paths) => {
+                    // Files may have been created since the watch started, list them again.
+                    let current = project_files().await?;
+                    if let Some(path) = paths
+                        .iter()
+                        .find(|path| is_relevant(path, &files) || is_relevant(path, &current))
+                    {
+                        trace!(path = %path.display(), "File changed");
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    pub(super) enum Changes {
+        Paths(HashSet<PathBuf>),
+        /// Events were dropped by the kernel, anything may have changed.
+        Overflow,
+    }
+
+    pub(super) struct Watcher {
+        fd: OwnedFd,
+        dirs: HashMap<i32, PathBuf>,
+    }
+
+    impl Watcher {
+        /// Watch the entries of `dirs`, relative to the current directory, an empty path is the
+        /// current directory itself. Directories which don't exist are skipped.
+        pub(super) fn new(dirs: impl IntoIterator<Item = PathBuf>) -> io::Result<Self> {
+            let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+            let mut watched = HashMap::new();
+            for dir in dirs {
+                let target = if dir.as_os_str().is_empty() {
+                    Path::new(".")
+                } else {
+                    &dir
+                };
+                let target = CString::new(target.as_os_str().as_bytes())?;
+                let wd =
+                    unsafe { libc::inotify_add_watch(fd.as_raw_fd(), target.as_ptr(), EVENT_MASK) };
+                if wd < 0 {
+                    let err = io::Error::last_os_error();
+                    if err.kind() == io::ErrorKind::NotFound {
+                        continue;
+                    }
+                    return Err(err);
+                }
+                watched.insert(wd, dir);
+            }
+
+            Ok(Self { fd, dirs: watched })
+        }
+
+        /// Block until something changes, and then until nothing changes for `debounce`.
+        pub(super) fn wait(&self, debounce: Duration) -> io::Result<Changes> {
+            let mut paths = HashSet::new();
+            let mut timeout = None;
+            while self.poll(timeout)? {
+                if !self.read(&mut paths)? {
+                    return Ok(Changes::Overflow);
+                }
+                timeout = Some(debounce);
+            }
+            Ok(Changes::Paths(paths))
+        }
+
+        /// Wait until events are available, returns `false` on timeout.
+        fn poll(&self, timeout: Option<Duration>) -> io::Result<bool> {
+            let timeout = timeout.map_or(-1, |timeout| {
+                i32::try_from(timeout.as_millis()).unwrap_or(i32::MAX)
+            });
+            let mut pollfd = libc::pollfd {
+                fd: self.fd.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            loop {
+                let ret = unsafe { libc::poll(&mut pollfd, 1, timeout) };
+                if ret >= 0 {
+                    return Ok(ret > 0);
+                }
+                let err = io::Error::last_os_error();
+                if err.kind() != io::ErrorKind::Interrupted {
+                    return Err(err);
+                }
+            }
+        }
+
+        /// Read the pending events into `paths`, returns `false` if the event queue overflowed.
+        fn read(&self, paths: &mut HashSet<PathBuf>) -> io::Result<bool> {
+            let mut buf = [0u8; 4096];
+            loop {
+                let len =
+                    unsafe { libc::read(self.fd.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len()) };
+                if len < 0 {
+                    let err = io::Error::last_os_error();
+                    match err.kind() {
+                        io::ErrorKind::WouldBlock => return Ok(true),
+                        io::ErrorKind::Interrupted => continue,
+                        _ => return Err(err),
+                    }
+                }
+                #[allow(clippy::cast_sign_loss)]
+                let len = len as usize;
+
+                let mut offset = 0;
+                while offset + EVENT_HEADER <= len {
+                    let field = |at: usize| {
+                        let at = offset + at;
+                        <[u8; 4]>::try_from(&buf[at..at + 4]).expect("4 bytes")
+                    };
+                    let wd = i32::from_ne_bytes(field(0));
+                    let mask = u32::from_ne_bytes(field(4));
+                    let name_len = u32::from_ne_bytes(field(12)) as usize;
+                    let name = &buf[offset + EVENT_HEADER..offset + EVENT_HEADER + name_len];
+                    offset += EVENT_HEADER + name_len;
+
+                    if mask & libc::IN_Q_OVERFLOW != 0 {
+                        return Ok(false);
+                    }
+                    let Some(dir) = self.dirs.get(&wd) else {
+                        continue;
+                    };
+                    // The name is padded with NULs.
+                    let name = name.split(|&b| b == 0).next().unwrap_or_default();
+                    if !name.is_empty() {
+                        paths.insert(dir.join(OsStr::from_bytes(name)));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Poll the project files, where no native watcher is available.
+#[cfg(not(target_os = "linux"))]
+mod imp {
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+    use std::time::{Duration, SystemTime};
+
+    use anyhow::Result;
+
+    use super::{project_files, DEBOUNCE};
+
+    const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+    pub(super) async fn wait_for_changes() -> Result<()> {
+        let initial = snapshot_files().await?;
+        let mut current = loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            let current = snapshot_files().await?;
+            if current != initial {
+                break current;
+            }
+        };
+        loop {
+            tokio::time::sleep(DEBOUNCE).await;
+            let next = snapshot_files().await?;
+            if next == current {
+                return Ok(());
+            }
+            current = next;
+        }
+    }
+
+    /// The modification time and size of each file, `None` for deleted files.
+    async fn snapshot_files() -> Result<HashMap<PathBuf, Option<(SystemTime, u64)>>> {
+        let files = project_files().await?;
+        Ok(files
+            .into_iter()
+            .map(|file| {
+                let metadata = std::fs::symlink_metadata(&file)
+                    .and_then(|metadata| Ok((metadata.modified()?, metadata.len())))
+                    .ok();
+                (file, metadata)
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::path::{Path, PathBuf};
+
+    use super::is_relevant;
+
+    #[test]
+    fn relevant_paths() {
+        let files: HashSet<_> = ["a.txt", "src/b.rs"]
+            .into_iter()
+            .map(PathBuf::from)
+            .collect();
+        assert!(is_relevant(Path::new("a.txt"), &files));
+        assert!(is_relevant(Path::new("src/b.rs"), &files));
+        assert!(is_relevant(Path::new("src"), &files));
+        assert!(!is_relevant(Path::new("target"), &files));
+        assert!(!is_relevant(Path::new("src/b"), &files));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn inotify_reports_changes() -> anyhow::Result<()> {
+        use std::time::Duration;
+
+        use super::imp::{Changes, Watcher};
+
+        let dir = tempfile::tempdir()?;
+        let sub = dir.path().join("sub");
+        fs_err::create_dir(&sub)?;
+        fs_err::write(sub.join("a.txt"), "a")?;
+
+        let watcher = Watcher::new([sub.clone(), dir.path().join("missing")])?;
+        let writer = std::thread::spawn({
+            let sub = sub.clone();
+            move || {
+                std::thread::sleep(Duration::from_millis(50));
+                fs_err::write(sub.join("a.txt"), "b").unwrap();
+                fs_err::write(sub.join("new.txt"), "new").unwrap();
+            }
+        });
+        let changes = watcher.wait(Duration::from_millis(200))?;
+        writer.join().unwrap();
+
+        let Changes::Paths(paths) = changes else {
+            panic!("unexpected overflow");
+        };
+        assert_eq!(
+            paths,
+            HashSet::from([sub.join("a.txt"), sub.join("new.txt")])
+        );
+        Ok(())
+    }
+}
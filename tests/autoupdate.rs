@@ -0,0 +1,90 @@
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::common::{cmd_snapshot, make_hooks_repo, tag_hooks_repo, TestContext};
+
+mod common;
+
+/// Create a hooks repo with two tagged commits, `v1` and `v2`.
+fn make_tagged_hooks_repo(path: &Path) {
+    make_hooks_repo(
+        path,
+        &[(
+            ".pre-commit-hooks.yaml",
+            indoc::indoc! {r"
+                - id: echo
+                  name: echo
+                  entry: echo
+                  language: system
+            "},
+        )],
+    );
+
+    tag_hooks_repo(path, "v2");
+}
+
+#[test]
+fn autoupdate() -> Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+
+    let hooks_repo = tempfile::tempdir()?;
+    make_tagged_hooks_repo(hooks_repo.path());
+    let url = url::Url::from_directory_path(hooks_repo.path()).unwrap();
+
+    context.write_pre_commit_config(&indoc::formatdoc! {r"
+        repos:
+          - repo: {url}
+            rev: v1  # keep this comment
+            hooks:
+              - id: echo
+    "});
+    let original = context.read(".pre-commit-config.yaml");
+
+    let escaped = regex::escape(url.as_str());
+    let filters: Vec<_> = [(escaped.as_str(), "[HOOKS_REPO]")]
+        .into_iter()
+        .chain(context.filters())
+        .collect();
+
+    // Dry run reports the update without touching the config.
+    cmd_snapshot!(filters.clone(), context.autoupdate().arg("--dry-run"), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    [HOOKS_REPO]: v1 -> v2
+
+    ----- stderr -----
+    "#);
+    assert_eq!(context.read(".pre-commit-config.yaml"), original);
+
+    cmd_snapshot!(filters.clone(), context.autoupdate(), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    [HOOKS_REPO]: v1 -> v2
+
+    ----- stderr -----
+    "#);
+    insta::with_settings!({ filters => filters.clone() }, {
+        insta::assert_snapshot!(context.read(".pre-commit-config.yaml"), @r#"
+        repos:
+          - repo: [HOOKS_REPO]
+            rev: v2  # keep this comment
+            hooks:
+              - id: echo
+        "#);
+    });
+
+    cmd_snapshot!(filters.clone(), context.autoupdate(), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    [HOOKS_REPO]: already up to date
+
+    ----- stderr -----
+    "#);
+
+    Ok(())
+}
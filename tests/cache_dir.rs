@@ -0,0 +1,37 @@
+use assert_fs::fixture::PathChild;
+
+use crate::common::{cmd_snapshot, TestContext};
+
+mod common;
+
+#[test]
+fn cache_dir() {
+    let context = TestContext::new();
+
+    cmd_snapshot!(context.filters(), context.command().arg("cache-dir"), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    [HOME]/
+
+    ----- stderr -----
+    "#);
+
+    // Falls back to `XDG_CACHE_HOME` if `PRE_COMMIT_HOME` is not set.
+    let cache = context.workdir().child("cache");
+    cmd_snapshot!(context.filters(), context
+        .command()
+        .arg("cache-dir")
+        .env_remove("PRE_COMMIT_HOME")
+        .env("XDG_CACHE_HOME", &*cache), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    [TEMP_DIR]/cache/pre-commit
+
+    ----- stderr -----
+    "#);
+
+    // Doesn't create the directory.
+    assert!(!cache.exists());
+}
@@ -116,6 +116,10 @@ impl TestContext {
         let mut cmd = Command::new(bin);
         cmd.current_dir(self.workdir());
         cmd.env("PRE_COMMIT_HOME", &*self.home_dir);
+        // Don't change the behavior when the tests themselves run in CI.
+        for var in CI_VARS {
+            cmd.env_remove(var);
+        }
         cmd
     }
 
@@ -131,6 +135,24 @@ impl TestContext {
         command
     }
 
+    pub fn gc(&self) -> Command {
+        let mut command = self.command();
+        command.arg("gc");
+        command
+    }
+
+    pub fn autoupdate(&self) -> Command {
+        let mut command = self.command();
+        command.arg("autoupdate");
+        command
+    }
+
+    pub fn doctor(&self) -> Command {
+        let mut command = self.command();
+        command.arg("doctor");
+        command
+    }
+
     pub fn validate_config(&self) -> Command {
         let mut command = self.command();
         command.arg("validate-config");
@@ -172,6 +194,11 @@ impl TestContext {
             .collect()
     }
 
+    /// Get the `PRE_COMMIT_HOME` directory for the test context.
+    pub fn home_dir(&self) -> &ChildPath {
+        &self.home_dir
+    }
+
     /// Get the working directory for the test context.
     pub fn workdir(&self) -> &ChildPath {
         &self.temp_dir
@@ -228,6 +255,74 @@ impl TestContext {
     }
 }
 
+/// Create a git repo with the given files, tagged as `v1`, to be used as a remote hooks repo.
+pub fn make_hooks_repo(path: &Path, files: &[(&str, &str)]) {
+    for (name, content) in files {
+        let file = path.join(name);
+        fs_err::create_dir_all(file.parent().unwrap()).expect("Failed to create directory");
+        fs_err::write(file, content).expect("Failed to write file");
+    }
+
+    for args in [
+        &["init"][..],
+        &["add", "."],
+        &[
+            "-c",
+            "user.name=Test User",
+            "-c",
+            "user.email=test@example.com",
+            "commit",
+            "-m",
+            "Initial commit",
+        ],
+        &["tag", "v1"],
+    ] {
+        Command::new("git")
+            .args(args)
+            .current_dir(path)
+            .assert()
+            .success();
+    }
+}
+
+/// Commit the changes in a hooks repo made by [`make_hooks_repo`], and tag the commit as a new rev.
+pub fn tag_hooks_repo(path: &Path, tag: &str) {
+    for args in [
+        &["add", "."][..],
+        &[
+            "-c",
+            "user.name=Test User",
+            "-c",
+            "user.email=test@example.com",
+            "commit",
+            "--allow-empty",
+            "-m",
+            tag,
+        ],
+        &["tag", tag],
+    ] {
+        Command::new("git")
+            .args(args)
+            .current_dir(path)
+            .assert()
+            .success();
+    }
+}
+
+/// Env vars which make pre-commit detect a CI environment.
+const CI_VARS: &[&str] = &[
+    "CI",
+    "GITHUB_ACTIONS",
+    "GITLAB_CI",
+    "BUILDKITE",
+    "CIRCLECI",
+    "TRAVIS",
+    "TF_BUILD",
+    "JENKINS_URL",
+    "TEAMCITY_VERSION",
+    "BITBUCKET_BUILD_NUMBER",
+];
+
 #[doc(hidden)] // Macro and test context only, don't use directly.
 pub const INSTA_FILTERS: &[(&str, &str)] = &[
     // File sizes
@@ -0,0 +1,79 @@
+use anyhow::Result;
+use assert_cmd::assert::OutputAssertExt;
+use assert_fs::fixture::{FileWriteStr, PathChild};
+
+use crate::common::{cmd_snapshot, TestContext};
+
+mod common;
+
+#[test]
+fn doctor() -> Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+    context.write_pre_commit_config(indoc::indoc! {r"
+        repos:
+          - repo: local
+            hooks:
+              - id: echo
+                name: echo
+                language: system
+                entry: echo
+    "});
+    context.git_add(".");
+
+    let filters: Vec<_> = [
+        (r"git: \d+\.\d+\.\d+ at .*", "git: [VERSION] at [GIT]"),
+        (
+            r"Failed to open the store: .*",
+            "Failed to open the store: [ERROR]",
+        ),
+    ]
+    .into_iter()
+    .chain(context.filters())
+    .collect();
+
+    // Warnings don't fail the check.
+    cmd_snapshot!(filters.clone(), context.doctor(), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    OK   git: [VERSION] at [GIT]
+    OK   store: [HOME]/ is writable
+    OK   core.hooksPath: not set
+    WARN hook scripts: missing: pre-commit, run `pre-commit install` to fix this
+    OK   cache: 0 repo(s) cached
+
+    ----- stderr -----
+    "#);
+
+    context.install().assert().success();
+
+    cmd_snapshot!(filters.clone(), context.doctor(), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    OK   git: [VERSION] at [GIT]
+    OK   store: [HOME]/ is writable
+    OK   core.hooksPath: not set
+    OK   hook scripts: pre-commit up to date
+    OK   cache: 0 repo(s) cached
+
+    ----- stderr -----
+    "#);
+
+    // The store can't be created where a file is.
+    context.workdir().child("not-a-dir").write_str("")?;
+    cmd_snapshot!(filters.clone(), context.doctor().env("PRE_COMMIT_HOME", context.workdir().child("not-a-dir").as_ref()), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    OK   git: [VERSION] at [GIT]
+    FAIL store: Failed to open the store: [ERROR]
+    OK   core.hooksPath: not set
+    OK   hook scripts: pre-commit up to date
+
+    ----- stderr -----
+    "#);
+
+    Ok(())
+}
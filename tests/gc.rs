@@ -0,0 +1,74 @@
+use anyhow::Result;
+use assert_cmd::assert::OutputAssertExt;
+use assert_fs::fixture::{FileWriteStr, PathChild};
+
+use crate::common::{cmd_snapshot, make_hooks_repo, TestContext};
+
+mod common;
+
+#[test]
+fn gc() -> Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+
+    let hooks_repo = tempfile::tempdir()?;
+    make_hooks_repo(
+        hooks_repo.path(),
+        &[(
+            ".pre-commit-hooks.yaml",
+            indoc::indoc! {r"
+                - id: echo
+                  name: echo
+                  entry: echo
+                  language: system
+            "},
+        )],
+    );
+    let url = url::Url::from_directory_path(hooks_repo.path()).unwrap();
+
+    context.write_pre_commit_config(&indoc::formatdoc! {r"
+        repos:
+          - repo: {url}
+            rev: v1
+            hooks:
+              - id: echo
+    "});
+    context.git_add(".");
+    context.run().assert().success();
+
+    // The repo is still referenced by the config.
+    cmd_snapshot!(context.filters(), context.gc(), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    0 repo(s) removed ([SIZE])
+    0 environment(s) removed ([SIZE])
+
+    ----- stderr -----
+    "#);
+
+    context
+        .workdir()
+        .child(".pre-commit-config.yaml")
+        .write_str(indoc::indoc! {r"
+            repos:
+              - repo: local
+                hooks:
+                  - id: echo
+                    name: echo
+                    entry: echo
+                    language: system
+        "})?;
+
+    cmd_snapshot!(context.filters(), context.gc(), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    1 repo(s) removed ([SIZE])
+    0 environment(s) removed ([SIZE])
+
+    ----- stderr -----
+    "#);
+
+    Ok(())
+}
@@ -1,5 +1,7 @@
 use std::process::Command;
 
+use assert_cmd::assert::OutputAssertExt;
+use assert_fs::prelude::*;
 use common::TestContext;
 use indoc::indoc;
 
@@ -56,3 +58,256 @@ fn hook_impl() {
       .pre-commit-config.yaml
     "#);
 }
+
+#[test]
+fn hook_git_env() -> anyhow::Result<()> {
+    let context = TestContext::new();
+
+    context.init_project();
+
+    context.write_pre_commit_config(indoc! { r"
+        repos:
+        - repo: local
+          hooks:
+           - id: git-status
+             name: git status
+             language: system
+             entry: git status --short
+             work_dir: sub
+             pass_filenames: false
+             verbose: true
+    "});
+    context.workdir().child("sub/a.txt").write_str("Hello\n")?;
+
+    context.git_add(".");
+
+    cmd_snapshot!(context.filters(), context.install(), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    pre-commit installed at .git/hooks/pre-commit
+
+    ----- stderr -----
+    "#);
+
+    // `GIT_INDEX_FILE` set by git must not leak into the hook, which runs in a subdirectory.
+    let mut commit = Command::new("git");
+    commit
+        .arg("commit")
+        .arg("--quiet")
+        .current_dir(context.workdir())
+        .arg("-m")
+        .arg("Initial commit");
+
+    cmd_snapshot!(context.filters(), commit, @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+
+    ----- stderr -----
+    git status...............................................................Passed
+    - hook id: git-status
+    - duration: [TIME]
+      A  ../.pre-commit-config.yaml
+      A  a.txt
+    "#);
+
+    Ok(())
+}
+
+#[test]
+fn post_merge() -> anyhow::Result<()> {
+    let context = TestContext::new();
+
+    context.init_project();
+
+    context.write_pre_commit_config(indoc! { r#"
+        repos:
+        - repo: local
+          hooks:
+           - id: merged
+             name: merged
+             language: system
+             entry: python3 -c 'import os, sys; print(os.environ["PRE_COMMIT_IS_SQUASH_MERGE"], sys.argv[1:])'
+             stages: [post-merge]
+             verbose: true
+    "#});
+    context.workdir().child("a.txt").write_str("a\n")?;
+
+    let cwd = context.workdir();
+    let git = |args: &[&str]| {
+        Command::new("git")
+            .args(args)
+            .current_dir(cwd)
+            .assert()
+            .success();
+    };
+
+    git(&["add", "."]);
+    git(&["commit", "-m", "Initial commit"]);
+    git(&["checkout", "-b", "feature"]);
+    cwd.child("b.txt").write_str("b\n")?;
+    git(&["add", "b.txt"]);
+    git(&["commit", "-m", "Add b"]);
+    git(&["checkout", "-"]);
+    git(&["merge", "--squash", "feature"]);
+
+    // Simulate git invoking the `post-merge` hook after a squash merge, the changes are staged.
+    cmd_snapshot!(context.filters(), post_merge_hook(&context, "1"), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    merged...................................................................Passed
+    - hook id: merged
+    - duration: [TIME]
+      1 ['b.txt']
+
+    ----- stderr -----
+    "#);
+
+    git(&["commit", "-m", "Squash feature"]);
+    git(&["checkout", "-b", "feature-2"]);
+    cwd.child("c.txt").write_str("c\n")?;
+    git(&["add", "c.txt"]);
+    git(&["commit", "-m", "Add c"]);
+    git(&["checkout", "-"]);
+    git(&["merge", "feature-2"]);
+
+    // After a merge, hooks run on the files changed between `ORIG_HEAD` and `HEAD`.
+    cmd_snapshot!(context.filters(), post_merge_hook(&context, "0"), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    merged...................................................................Passed
+    - hook id: merged
+    - duration: [TIME]
+      0 ['c.txt']
+
+    ----- stderr -----
+    "#);
+
+    Ok(())
+}
+
+fn post_merge_hook(context: &TestContext, squash: &str) -> Command {
+    let mut command = context.command();
+    command
+        .arg("hook-impl")
+        .arg("--hook-type")
+        .arg("post-merge")
+        .arg("--hook-dir")
+        .arg(".git/hooks")
+        .arg("--")
+        .arg(squash);
+    command
+}
+
+#[test]
+fn prepare_commit_msg() -> anyhow::Result<()> {
+    let context = TestContext::new();
+
+    context.init_project();
+
+    context.write_pre_commit_config(indoc! { r#"
+        repos:
+        - repo: local
+          hooks:
+           - id: prepare
+             name: prepare
+             language: system
+             entry: python3 -c 'import os, sys; print(os.environ["PRE_COMMIT_COMMIT_MSG_SOURCE"], os.environ.get("PRE_COMMIT_COMMIT_OBJECT_NAME", "-"), sys.argv[1:])'
+             stages: [prepare-commit-msg]
+             verbose: true
+    "#});
+    context.git_add(".");
+    context
+        .workdir()
+        .child(".git/COMMIT_EDITMSG")
+        .write_str("Message\n")?;
+
+    cmd_snapshot!(context.filters(), context.install().arg("-t").arg("prepare-commit-msg"), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    pre-commit installed at .git/hooks/prepare-commit-msg
+
+    ----- stderr -----
+    "#);
+
+    cmd_snapshot!(context.filters(), prepare_commit_msg_hook(&context, &["message"]), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    prepare..................................................................Passed
+    - hook id: prepare
+    - duration: [TIME]
+      message - ['.git/COMMIT_EDITMSG']
+
+    ----- stderr -----
+    "#);
+
+    cmd_snapshot!(context.filters(), prepare_commit_msg_hook(&context, &["template"]), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    prepare..................................................................Passed
+    - hook id: prepare
+    - duration: [TIME]
+      template - ['.git/COMMIT_EDITMSG']
+
+    ----- stderr -----
+    "#);
+
+    cmd_snapshot!(context.filters(), prepare_commit_msg_hook(&context, &["merge"]), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    prepare..................................................................Passed
+    - hook id: prepare
+    - duration: [TIME]
+      merge - ['.git/COMMIT_EDITMSG']
+
+    ----- stderr -----
+    "#);
+
+    cmd_snapshot!(context.filters(), prepare_commit_msg_hook(&context, &["squash"]), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    prepare..................................................................Passed
+    - hook id: prepare
+    - duration: [TIME]
+      squash - ['.git/COMMIT_EDITMSG']
+
+    ----- stderr -----
+    "#);
+
+    // `commit` is followed by the object name of the commit, e.g. with `git commit --amend`.
+    cmd_snapshot!(context.filters(), prepare_commit_msg_hook(&context, &["commit", "HEAD"]), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    prepare..................................................................Passed
+    - hook id: prepare
+    - duration: [TIME]
+      commit HEAD ['.git/COMMIT_EDITMSG']
+
+    ----- stderr -----
+    "#);
+
+    Ok(())
+}
+
+fn prepare_commit_msg_hook(context: &TestContext, args: &[&str]) -> Command {
+    let mut command = context.command();
+    command
+        .arg("hook-impl")
+        .arg("--hook-type")
+        .arg("prepare-commit-msg")
+        .arg("--hook-dir")
+        .arg(".git/hooks")
+        .arg("--")
+        .arg(".git/COMMIT_EDITMSG")
+        .args(args);
+    command
+}
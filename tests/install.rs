@@ -1,3 +1,5 @@
+use std::process::Command;
+
 use assert_cmd::assert::OutputAssertExt;
 use assert_fs::assert::PathAssert;
 use assert_fs::fixture::{FileWriteStr, PathChild};
@@ -238,3 +240,244 @@ fn uninstall() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// `uninstall --all` removes every script we installed, whatever the configured hook types.
+#[test]
+fn uninstall_all() -> anyhow::Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+
+    context
+        .workdir()
+        .child(".git/hooks/pre-push")
+        .write_str("#!/bin/sh\necho 'pre-push'\n")?;
+    context
+        .install()
+        .args(["-t", "pre-commit", "-t", "pre-push", "-t", "commit-msg"])
+        .assert()
+        .success();
+
+    cmd_snapshot!(context.filters(), context.uninstall().arg("--all"), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    Uninstalled commit-msg
+    Uninstalled pre-commit
+    Uninstalled pre-push
+    Restored previous hook to .git/hooks/pre-push
+
+    ----- stderr -----
+    "#);
+
+    for hook in ["pre-commit", "commit-msg", "pre-push.legacy"] {
+        context
+            .workdir()
+            .child(format!(".git/hooks/{hook}"))
+            .assert(predicate::path::missing());
+    }
+    assert_snapshot!(context.read(".git/hooks/pre-push"), @r#"
+    #!/bin/sh
+    echo 'pre-push'
+    "#);
+
+    Ok(())
+}
+
+/// Hook scripts installed by the Python `pre-commit` are taken over, not kept as `.legacy`.
+#[test]
+fn python_pre_commit_script() -> anyhow::Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+
+    let python_script = indoc::indoc! {r#"
+        #!/usr/bin/env bash
+        # File generated by pre-commit: https://pre-commit.com
+        # ID: 138fd403232d2ddd5efb44317e38bf03
+
+        # start templated
+        INSTALL_PYTHON=/usr/bin/python3
+        ARGS=(hook-impl --config=.pre-commit-config.yaml --hook-type=pre-commit)
+        # end templated
+
+        HERE="$(cd "$(dirname "$0")" && pwd)"
+        ARGS+=(--hook-dir "$HERE" -- "$@")
+
+        exec "$INSTALL_PYTHON" -mpre_commit "${ARGS[@]}"
+    "#};
+    let hook = context.workdir().child(".git/hooks/pre-commit");
+
+    hook.write_str(python_script)?;
+    cmd_snapshot!(context.filters(), context.install(), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    Replacing the hook installed by Python pre-commit at .git/hooks/pre-commit
+    pre-commit installed at .git/hooks/pre-commit
+
+    ----- stderr -----
+    "#);
+    context
+        .workdir()
+        .child(".git/hooks/pre-commit.legacy")
+        .assert(predicate::path::missing());
+
+    hook.write_str(python_script)?;
+    cmd_snapshot!(context.filters(), context.uninstall(), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    Uninstalled pre-commit
+
+    ----- stderr -----
+    "#);
+    hook.assert(predicate::path::missing());
+
+    Ok(())
+}
+
+/// A hook type git would never call is rejected, listing the valid ones.
+#[test]
+fn install_invalid_hook_type() {
+    let context = TestContext::new();
+    context.init_project();
+
+    cmd_snapshot!(context.filters(), context.install().arg("--hook-type").arg("pre-comit"), @r#"
+    success: false
+    exit_code: 2
+    ----- stdout -----
+
+    ----- stderr -----
+    error: invalid value 'pre-comit' for '--hook-type <HOOK_TYPE>'
+      [possible values: commit-msg, post-checkout, post-commit, post-merge, post-rewrite, pre-commit, pre-merge-commit, pre-push, pre-rebase, prepare-commit-msg]
+
+      tip: a similar value exists: 'pre-commit'
+
+    For more information, try '--help'.
+    "#);
+
+    context
+        .workdir()
+        .child(".git/hooks/pre-comit")
+        .assert(predicate::path::missing());
+}
+
+/// Hooks installed from a linked worktree go to the shared hooks directory and run in every worktree.
+#[test]
+fn install_worktree() -> anyhow::Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+    context.write_pre_commit_config(indoc::indoc! {r"
+        repos:
+          - repo: meta
+            hooks:
+              - id: identity
+    "});
+    context.workdir().child("main.txt").write_str("main")?;
+    context.git_add(".");
+    Command::new("git")
+        .args(["commit", "-m", "init"])
+        .current_dir(context.workdir())
+        .assert()
+        .success();
+
+    let root = tempfile::TempDir::new()?;
+    let worktree = root.path().join("worktree");
+    Command::new("git")
+        .args(["worktree", "add", "-b", "feature"])
+        .arg(&worktree)
+        .current_dir(context.workdir())
+        .assert()
+        .success();
+
+    let filters: Vec<_> = TestContext::path_patterns(&worktree)
+        .into_iter()
+        .map(|pattern| (pattern, "[WORKTREE]/".to_string()))
+        .collect();
+    let filters: Vec<_> = filters
+        .iter()
+        .map(|(p, r)| (p.as_str(), r.as_str()))
+        .chain(context.filters())
+        .collect();
+
+    cmd_snapshot!(filters.clone(), context.install().current_dir(&worktree), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    pre-commit installed at [TEMP_DIR]/.git/hooks/pre-commit
+
+    ----- stderr -----
+    "#);
+
+    context
+        .workdir()
+        .child(".git/hooks/pre-commit")
+        .assert(predicate::path::exists());
+
+    // The hook sees the files of the worktree it runs in.
+    fs_err::write(worktree.join("feature.txt"), "feature")?;
+    Command::new("git")
+        .args(["add", "feature.txt"])
+        .current_dir(&worktree)
+        .assert()
+        .success();
+
+    cmd_snapshot!(filters.clone(), Command::new("git").args(["commit", "--quiet", "-m", "feature"]).current_dir(&worktree), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+
+    ----- stderr -----
+    identity.................................................................Passed
+    - hook id: identity
+    - duration: [TIME]
+      feature.txt
+    "#);
+
+    Ok(())
+}
+
+/// Hooks managed by husky are reported, so they don't end up running alongside pre-commit.
+#[test]
+fn install_husky() -> anyhow::Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+
+    let husky = context.workdir().child(".husky");
+    husky.child("pre-commit").write_str("npm test\n")?;
+    husky
+        .child("commit-msg")
+        .write_str("npx commitlint --edit \"$1\"\n")?;
+    husky.child("_").child("h").write_str("")?;
+    husky.child("README.md").write_str("")?;
+
+    cmd_snapshot!(context.filters(), context.install(), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    pre-commit installed at .git/hooks/pre-commit
+
+    ----- stderr -----
+    warning: Found husky hooks in `.husky`: commit-msg, pre-commit, port them to `.pre-commit-config.yaml` and remove husky so they don't run alongside pre-commit
+    "#);
+
+    // Husky points `core.hooksPath` at its own directory.
+    Command::new("git")
+        .args(["config", "core.hooksPath", ".husky/_"])
+        .current_dir(context.workdir())
+        .assert()
+        .success();
+
+    cmd_snapshot!(context.filters(), context.install(), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+
+    ----- stderr -----
+    warning: Found husky hooks in `.husky`: commit-msg, pre-commit, port them to `.pre-commit-config.yaml` and remove husky so they don't run alongside pre-commit
+    Cowardly refusing to install hooks with `core.hooksPath` set.
+    hint: `git config --unset-all core.hooksPath` to fix this.
+
+    "#);
+
+    Ok(())
+}
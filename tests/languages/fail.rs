@@ -3,7 +3,6 @@ use assert_fs::prelude::*;
 
 use crate::common::{cmd_snapshot, TestContext};
 
-/// GitHub Action only has docker for linux hosted runners.
 #[test]
 fn fail() -> Result<()> {
     let context = TestContext::new();
@@ -43,3 +42,85 @@ fn fail() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn fail_no_matched_files() -> Result<()> {
+    let context = TestContext::new();
+
+    context.init_project();
+
+    let cwd = context.workdir();
+    cwd.child("changelog").create_dir_all()?;
+    cwd.child("changelog/changelog.rst").touch()?;
+
+    context.write_pre_commit_config(indoc::indoc! {r"
+            repos:
+              - repo: local
+                hooks:
+                - id: changelogs-rst
+                  name: changelogs must be rst
+                  entry: changelog filenames must end in .rst
+                  language: fail
+                  files: 'changelog/.*(?<!\.rst)$'
+                - id: changelogs-rst-always
+                  name: changelogs must be rst (always run)
+                  entry: changelog filenames must end in .rst
+                  language: fail
+                  files: 'changelog/.*(?<!\.rst)$'
+                  always_run: true
+        "});
+
+    context.git_add(".");
+
+    cmd_snapshot!(context.filters(), context.run(), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    changelogs must be rst...............................(no files to check)Skipped
+    changelogs must be rst (always run)......................................Passed
+
+    ----- stderr -----
+    "#);
+
+    Ok(())
+}
+
+#[test]
+fn fail_verbose() -> Result<()> {
+    let context = TestContext::new();
+
+    context.init_project();
+
+    let cwd = context.workdir();
+    cwd.child("changelog").create_dir_all()?;
+    cwd.child("changelog/changelog.md").touch()?;
+
+    context.write_pre_commit_config(indoc::indoc! {r"
+            repos:
+              - repo: local
+                hooks:
+                - id: changelogs-rst
+                  name: changelogs must be rst
+                  entry: changelog filenames must end in .rst
+                  language: fail
+                  files: 'changelog/.*(?<!\.rst)$'
+                  verbose: true
+        "});
+
+    context.git_add(".");
+
+    cmd_snapshot!(context.filters(), context.run(), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    changelogs must be rst...................................................Failed
+    - hook id: changelogs-rst
+    - duration: [TIME]
+    - exit code: 1
+      changelog/changelog.md: changelog filenames must end in .rst
+
+    ----- stderr -----
+    "#);
+
+    Ok(())
+}
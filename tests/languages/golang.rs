@@ -0,0 +1,94 @@
+use crate::common::{cmd_snapshot, make_hooks_repo, TestContext};
+
+#[test]
+fn golang() -> anyhow::Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+
+    let hooks_repo = tempfile::tempdir()?;
+    make_hooks_repo(
+        hooks_repo.path(),
+        &[
+            (
+                ".pre-commit-hooks.yaml",
+                indoc::indoc! {r"
+                    - id: hello
+                      name: hello
+                      entry: hello
+                      language: golang
+                      always_run: true
+                      verbose: true
+                      pass_filenames: false
+                "},
+            ),
+            (
+                "go.mod",
+                indoc::indoc! {r"
+                    module example.com/hello
+
+                    go 1.18
+                "},
+            ),
+            (
+                "main.go",
+                indoc::indoc! {r#"
+                    package main
+
+                    import "fmt"
+
+                    func main() {
+                        fmt.Println("Hello from go!")
+                    }
+                "#},
+            ),
+        ],
+    );
+    let url = url::Url::from_directory_path(hooks_repo.path()).unwrap();
+
+    context.write_pre_commit_config(&indoc::formatdoc! {r"
+        repos:
+          - repo: {url}
+            rev: v1
+            hooks:
+              - id: hello
+                language_version: system
+    "});
+    context.git_add(".");
+
+    let filters = context
+        .filters()
+        .into_iter()
+        .chain([(r"file://\S+@v1", "[HOOKS_REPO]@v1")])
+        .collect::<Vec<_>>();
+
+    cmd_snapshot!(filters, context.run(), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    Cloning [HOOKS_REPO]@v1
+    Installing environment for [HOOKS_REPO]@v1
+    hello....................................................................Passed
+    - hook id: hello
+    - duration: [TIME]
+      Hello from go!
+
+    ----- stderr -----
+    "#);
+
+    // The environment of the installed hook can be located.
+    let output = context
+        .run()
+        .arg("--print-env-path")
+        .arg("hello")
+        .output()?;
+    assert!(output.status.success());
+    assert!(
+        output.stderr.is_empty(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let env_path = String::from_utf8(output.stdout)?;
+    assert!(std::path::Path::new(env_path.trim()).is_dir(), "{env_path}");
+
+    Ok(())
+}
@@ -4,3 +4,11 @@ mod common;
 #[cfg(all(feature = "docker", target_os = "linux"))]
 mod docker;
 mod fail;
+#[cfg(feature = "golang")]
+mod golang;
+mod pygrep;
+#[cfg(feature = "ruby")]
+mod ruby;
+mod rust;
+#[cfg(unix)]
+mod script;
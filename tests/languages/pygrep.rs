@@ -0,0 +1,180 @@
+use anyhow::Result;
+use assert_fs::prelude::*;
+
+use crate::common::{cmd_snapshot, TestContext};
+
+#[test]
+fn pygrep() -> Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+
+    let cwd = context.workdir();
+    cwd.child("a.py").write_str("import os\n# TODO: remove\n")?;
+    cwd.child("b.py").write_str("print('todo')\n")?;
+
+    context.write_pre_commit_config(indoc::indoc! {r"
+        repos:
+          - repo: local
+            hooks:
+              - id: no-todo
+                name: no todo
+                entry: 'TODO'
+                language: pygrep
+                files: \.py$
+              - id: no-todo-ignore-case
+                name: no todo ignore case
+                entry: 'TODO'
+                language: pygrep
+                files: \.py$
+                args: [--ignore-case]
+    "});
+    context.git_add(".");
+
+    cmd_snapshot!(context.filters(), context.run(), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    no todo..................................................................Failed
+    - hook id: no-todo
+    - exit code: 1
+      a.py:2:# TODO: remove
+    no todo ignore case......................................................Failed
+    - hook id: no-todo-ignore-case
+    - exit code: 1
+      a.py:2:# TODO: remove
+      b.py:1:print('todo')
+
+    ----- stderr -----
+    "#);
+
+    Ok(())
+}
+
+#[test]
+fn pygrep_multiline() -> Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+
+    let cwd = context.workdir();
+    cwd.child("a.py").write_str("x = foo(\n)\ny = foo(1)\n")?;
+    cwd.child("b.py").write_str("x = foo()\n")?;
+
+    context.write_pre_commit_config(indoc::indoc! {r"
+        repos:
+          - repo: local
+            hooks:
+              - id: empty-call
+                name: no multiline empty call
+                entry: 'foo\(\n\s*\)'
+                language: pygrep
+                files: \.py$
+                args: [--multiline]
+    "});
+    context.git_add(".");
+
+    cmd_snapshot!(context.filters(), context.run(), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    no multiline empty call..................................................Failed
+    - hook id: empty-call
+    - exit code: 1
+      a.py:1:x = foo(
+      )
+
+    ----- stderr -----
+    "#);
+
+    Ok(())
+}
+
+#[test]
+fn pygrep_negate() -> Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+
+    let cwd = context.workdir();
+    cwd.child("a.py").write_str("# Copyright\nimport os\n")?;
+    cwd.child("b.py").write_str("import os\n")?;
+
+    context.write_pre_commit_config(indoc::indoc! {r"
+        repos:
+          - repo: local
+            hooks:
+              - id: copyright
+                name: copyright header
+                entry: '^# Copyright'
+                language: pygrep
+                files: \.py$
+                args: [--negate]
+    "});
+    context.git_add(".");
+
+    cmd_snapshot!(context.filters(), context.run(), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    copyright header.........................................................Failed
+    - hook id: copyright
+    - exit code: 1
+      b.py
+
+    ----- stderr -----
+    "#);
+
+    Ok(())
+}
+
+#[test]
+fn pygrep_verbose() -> Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+
+    let cwd = context.workdir();
+    cwd.child("a.py")
+        .write_str("# Copyright\n# TODO: one\n# TODO: two\n")?;
+    cwd.child("b.py").write_str("import os\n")?;
+
+    context.write_pre_commit_config(indoc::indoc! {r"
+        repos:
+          - repo: local
+            hooks:
+              - id: no-todo
+                name: no todo
+                entry: 'TODO'
+                language: pygrep
+                files: \.py$
+                verbose: true
+              - id: copyright
+                name: copyright header
+                entry: '^# Copyright'
+                language: pygrep
+                files: \.py$
+                args: [--negate]
+                verbose: true
+    "});
+    context.git_add(".");
+
+    cmd_snapshot!(context.filters(), context.run(), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    no todo..................................................................Failed
+    - hook id: no-todo
+    - duration: [TIME]
+    - exit code: 1
+      a.py:2:# TODO: one
+      a.py:3:# TODO: two
+      2 match(es) of `TODO` in 1 of 2 file(s)
+    copyright header.........................................................Failed
+    - hook id: copyright
+    - duration: [TIME]
+    - exit code: 1
+      b.py: no match for `^# Copyright`
+      1 of 2 file(s) don't match `^# Copyright`
+
+    ----- stderr -----
+    "#);
+
+    Ok(())
+}
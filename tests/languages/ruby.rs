@@ -0,0 +1,79 @@
+use crate::common::{cmd_snapshot, make_hooks_repo, TestContext};
+
+#[test]
+fn ruby() -> anyhow::Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+
+    let hooks_repo = tempfile::tempdir()?;
+    make_hooks_repo(
+        hooks_repo.path(),
+        &[
+            (
+                ".pre-commit-hooks.yaml",
+                indoc::indoc! {r"
+                    - id: hello
+                      name: hello
+                      entry: hello
+                      language: ruby
+                      always_run: true
+                      verbose: true
+                      pass_filenames: false
+                "},
+            ),
+            (
+                "hello.gemspec",
+                indoc::indoc! {r"
+                    Gem::Specification.new do |s|
+                      s.name = 'hello'
+                      s.version = '0.1.0'
+                      s.summary = 'hello'
+                      s.authors = ['hello']
+                      s.files = ['bin/hello']
+                      s.executables = ['hello']
+                    end
+                "},
+            ),
+            (
+                "bin/hello",
+                indoc::indoc! {r"
+                    #!/usr/bin/env ruby
+                    puts 'Hello from ruby!'
+                "},
+            ),
+        ],
+    );
+    let url = url::Url::from_directory_path(hooks_repo.path()).unwrap();
+
+    context.write_pre_commit_config(&indoc::formatdoc! {r"
+        repos:
+          - repo: {url}
+            rev: v1
+            hooks:
+              - id: hello
+                language_version: system
+    "});
+    context.git_add(".");
+
+    let filters = context
+        .filters()
+        .into_iter()
+        .chain([(r"file://\S+@v1", "[HOOKS_REPO]@v1")])
+        .collect::<Vec<_>>();
+
+    cmd_snapshot!(filters, context.run(), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    Cloning [HOOKS_REPO]@v1
+    Installing environment for [HOOKS_REPO]@v1
+    hello....................................................................Passed
+    - hook id: hello
+    - duration: [TIME]
+      Hello from ruby!
+
+    ----- stderr -----
+    "#);
+
+    Ok(())
+}
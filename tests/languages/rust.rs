@@ -0,0 +1,246 @@
+use std::path::Path;
+
+use crate::common::{cmd_snapshot, make_hooks_repo, TestContext};
+
+/// Create a hooks repo with a `hello` hook, a crate printing `Hello from rust!`.
+fn make_rust_hooks_repo(path: &Path) {
+    make_hooks_repo(
+        path,
+        &[
+            (
+                ".pre-commit-hooks.yaml",
+                indoc::indoc! {r"
+                    - id: hello
+                      name: hello
+                      entry: hello
+                      language: rust
+                      always_run: true
+                      pass_filenames: false
+                "},
+            ),
+            (
+                "Cargo.toml",
+                indoc::indoc! {r#"
+                    [package]
+                    name = "hello"
+                    version = "0.1.0"
+                    edition = "2021"
+                "#},
+            ),
+            (
+                "src/main.rs",
+                r#"fn main() { println!("Hello from rust!"); }"#,
+            ),
+        ],
+    );
+}
+
+#[test]
+fn rust() -> anyhow::Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+
+    let hooks_repo = tempfile::tempdir()?;
+    make_rust_hooks_repo(hooks_repo.path());
+    let url = url::Url::from_directory_path(hooks_repo.path()).unwrap();
+
+    context.write_pre_commit_config(&indoc::formatdoc! {r"
+        repos:
+          - repo: {url}
+            rev: v1
+            hooks:
+              - id: hello
+                verbose: true
+    "});
+    context.git_add(".");
+
+    let filters = context
+        .filters()
+        .into_iter()
+        .chain([(r"file://\S+@v1", "[HOOKS_REPO]@v1")])
+        .collect::<Vec<_>>();
+
+    cmd_snapshot!(filters, context.run(), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    Cloning [HOOKS_REPO]@v1
+    Installing environment for [HOOKS_REPO]@v1
+    hello....................................................................Passed
+    - hook id: hello
+    - duration: [TIME]
+      Hello from rust!
+
+    ----- stderr -----
+    "#);
+
+    Ok(())
+}
+
+/// A failed install reports the repo, the install command and its output.
+#[test]
+fn install_failure() -> anyhow::Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+
+    let hooks_repo = tempfile::tempdir()?;
+    make_hooks_repo(
+        hooks_repo.path(),
+        &[
+            (
+                ".pre-commit-hooks.yaml",
+                indoc::indoc! {r"
+                    - id: broken
+                      name: broken
+                      entry: broken
+                      language: rust
+                "},
+            ),
+            (
+                "Cargo.toml",
+                indoc::indoc! {r#"
+                    [package]
+                    name = "broken"
+                    version = "0.1.0"
+                    edition = "2021"
+                "#},
+            ),
+            (
+                "src/main.rs",
+                r#"fn main() { compile_error!("this hook does not build"); }"#,
+            ),
+        ],
+    );
+    let url = url::Url::from_directory_path(hooks_repo.path()).unwrap();
+
+    context.write_pre_commit_config(&indoc::formatdoc! {r"
+        repos:
+          - repo: {url}
+            rev: v1
+            hooks:
+              - id: broken
+    "});
+    context.git_add(".");
+
+    let header = format!("error: Failed to install environment for {url}@v1");
+
+    let output = context.run().output()?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr.contains(&header), "{stderr}");
+    assert!(
+        stderr.contains("[command]\ncargo install --bins --root"),
+        "{stderr}"
+    );
+    assert!(stderr.contains("this hook does not build"), "{stderr}");
+    assert!(!stderr.contains("partial environment"), "{stderr}");
+
+    // The partial environment is kept for inspection with `--verbose`.
+    let output = context.run().arg("--verbose").output()?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr.contains(&header), "{stderr}");
+    assert!(
+        stderr.contains("the partial environment is kept at"),
+        "{stderr}"
+    );
+
+    Ok(())
+}
+
+/// Concurrent runs build the same environment once, the other run waits and reuses it.
+#[test]
+fn concurrent_install() -> anyhow::Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+
+    let hooks_repo = tempfile::tempdir()?;
+    make_rust_hooks_repo(hooks_repo.path());
+    let url = url::Url::from_directory_path(hooks_repo.path()).unwrap();
+
+    context.write_pre_commit_config(&indoc::formatdoc! {r"
+        repos:
+          - repo: {url}
+            rev: v1
+            hooks:
+              - id: hello
+    "});
+    context.git_add(".");
+
+    let children = (0..2)
+        .map(|_| {
+            context
+                .run()
+                .stdout(std::process::Stdio::piped())
+                .stderr(std::process::Stdio::piped())
+                .spawn()
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut installs = 0;
+    for child in children {
+        let output = child.wait_with_output()?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(output.status.success(), "{stdout}");
+        assert!(stdout.contains("hello....."), "{stdout}");
+        installs += stdout.matches("Installing environment for").count();
+    }
+    assert_eq!(installs, 1);
+
+    Ok(())
+}
+
+/// `--verbose` reports whether repos and environments are reused from the store.
+#[test]
+fn cache_report() -> anyhow::Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+
+    let hooks_repo = tempfile::tempdir()?;
+    make_rust_hooks_repo(hooks_repo.path());
+    let url = url::Url::from_directory_path(hooks_repo.path()).unwrap();
+
+    context.write_pre_commit_config(&indoc::formatdoc! {r"
+        repos:
+          - repo: {url}
+            rev: v1
+            hooks:
+              - id: hello
+    "});
+    context.git_add(".");
+
+    let cache_lines = |output: &std::process::Output| {
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter(|line| line.starts_with("Cache "))
+            .map(|line| line.replace(url.as_str(), "[HOOKS_REPO]/"))
+            .collect::<Vec<_>>()
+    };
+
+    let output = context.run().arg("--verbose").output()?;
+    assert!(output.status.success());
+    assert_eq!(
+        cache_lines(&output),
+        [
+            "Cache miss: repo [HOOKS_REPO]/@v1",
+            "Cache miss: environment rust for [HOOKS_REPO]/@v1",
+        ]
+    );
+
+    let output = context.run().arg("--verbose").output()?;
+    assert!(output.status.success());
+    assert_eq!(
+        cache_lines(&output),
+        [
+            "Cache hit: repo [HOOKS_REPO]/@v1",
+            "Cache hit: environment rust for [HOOKS_REPO]/@v1",
+        ]
+    );
+
+    // Without `--verbose`, nothing is reported.
+    let output = context.run().output()?;
+    assert!(output.status.success());
+    assert!(cache_lines(&output).is_empty());
+
+    Ok(())
+}
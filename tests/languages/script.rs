@@ -0,0 +1,103 @@
+use std::os::unix::fs::PermissionsExt;
+
+use anyhow::Result;
+use assert_fs::prelude::*;
+
+use crate::common::{cmd_snapshot, TestContext};
+
+/// The `entry` of a local script hook is relative to the repo root, not the current directory.
+#[test]
+fn script_from_subdirectory() -> Result<()> {
+    let context = TestContext::new();
+
+    context.init_project();
+
+    let cwd = context.workdir();
+    cwd.child("scripts/check.sh")
+        .write_str("#!/usr/bin/env sh\necho \"checked: $*\"\n")?;
+    fs_err::set_permissions(
+        cwd.child("scripts/check.sh").path(),
+        std::fs::Permissions::from_mode(0o755),
+    )?;
+    cwd.child("sub/a.txt").write_str("Hello\n")?;
+
+    context.write_pre_commit_config(indoc::indoc! {r"
+        repos:
+          - repo: local
+            hooks:
+            - id: check
+              name: check
+              entry: scripts/check.sh --strict
+              language: script
+              files: \.txt$
+              verbose: true
+    "});
+
+    context.git_add(".");
+
+    cmd_snapshot!(context.filters(), context.run().current_dir(cwd.child("sub")), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    check....................................................................Passed
+    - hook id: check
+    - duration: [TIME]
+      checked: --strict sub/a.txt
+
+    ----- stderr -----
+    "#);
+
+    Ok(())
+}
+
+/// A missing script, or a script whose interpreter is missing, fails the hook with an explanation.
+#[test]
+fn script_not_found() -> Result<()> {
+    let context = TestContext::new();
+
+    context.init_project();
+
+    let cwd = context.workdir();
+    cwd.child("bad-shebang.sh")
+        .write_str("#!/nonexistent/interpreter\necho unreachable\n")?;
+    fs_err::set_permissions(
+        cwd.child("bad-shebang.sh").path(),
+        std::fs::Permissions::from_mode(0o755),
+    )?;
+
+    context.write_pre_commit_config(indoc::indoc! {r"
+        repos:
+          - repo: local
+            hooks:
+            - id: missing
+              name: missing
+              entry: missing.sh
+              language: script
+              always_run: true
+            - id: bad-shebang
+              name: bad-shebang
+              entry: bad-shebang.sh
+              language: script
+              always_run: true
+    "});
+
+    context.git_add(".");
+
+    cmd_snapshot!(context.filters(), context.run(), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    missing..................................................................Failed
+    - hook id: missing
+    - exit code: 1
+      Executable `[TEMP_DIR]/missing.sh` not found
+    bad-shebang..............................................................Failed
+    - hook id: bad-shebang
+    - exit code: 1
+      Executable `[TEMP_DIR]/bad-shebang.sh` exists, but its interpreter was not found, check its shebang line
+
+    ----- stderr -----
+    "#);
+
+    Ok(())
+}
@@ -1,8 +1,11 @@
+use std::process::Command;
+
 use anyhow::Result;
+use assert_cmd::assert::OutputAssertExt;
 use assert_fs::prelude::*;
 use insta::assert_snapshot;
 
-use crate::common::{cmd_snapshot, TestContext};
+use crate::common::{cmd_snapshot, make_hooks_repo, tag_hooks_repo, TestContext};
 
 mod common;
 
@@ -78,6 +81,54 @@ fn run_basic() -> Result<()> {
     Ok(())
 }
 
+/// An empty config has nothing to run.
+#[test]
+fn no_hooks() {
+    let context = TestContext::new();
+    context.init_project();
+
+    context.write_pre_commit_config("repos: []\n");
+    context.git_add(".");
+
+    cmd_snapshot!(context.filters(), context.run(), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+
+    ----- stderr -----
+    No hooks to run
+    "#);
+}
+
+/// Hint to select a stage, when nothing runs for `pre-commit` but hooks are installed for others.
+#[test]
+fn default_install_hook_types_hint() {
+    let context = TestContext::new();
+    context.init_project();
+
+    context.write_pre_commit_config(indoc::indoc! {r"
+        default_install_hook_types: [pre-push]
+        repos:
+          - repo: local
+            hooks:
+              - id: echo
+                name: echo
+                language: system
+                entry: echo
+                stages: [pre-push]
+    "});
+    context.git_add(".");
+
+    cmd_snapshot!(context.filters(), context.run(), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+
+    ----- stderr -----
+    warning: No hooks to run for stage `pre-commit`, hooks are installed for `pre-push`, select a stage with `--hook-stage`
+    "#);
+}
+
 #[test]
 fn local() {
     let context = TestContext::new();
@@ -380,6 +431,55 @@ fn files_and_exclude() -> Result<()> {
     Ok(())
 }
 
+/// Empty `files` matches everything and empty `exclude` excludes nothing, at both levels.
+#[test]
+fn empty_files_and_exclude() -> Result<()> {
+    let context = TestContext::new();
+
+    context.init_project();
+
+    let cwd = context.workdir();
+    cwd.child("file.txt").write_str("Hello, world!\n")?;
+    cwd.child("main.py").write_str("print('abc')\n")?;
+
+    context.write_pre_commit_config(indoc::indoc! {r"
+        files: ''
+        exclude: ''
+        repos:
+          - repo: local
+            hooks:
+              - id: global
+                name: global
+                language: system
+                entry: python3 -c 'import sys; print(sorted(sys.argv[1:])); exit(1)'
+              - id: hook
+                name: hook
+                language: system
+                entry: python3 -c 'import sys; print(sorted(sys.argv[1:])); exit(1)'
+                files: ''
+                exclude: ''
+    "});
+    context.git_add(".");
+
+    cmd_snapshot!(context.filters(), context.run(), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    global...................................................................Failed
+    - hook id: global
+    - exit code: 1
+      ['.pre-commit-config.yaml', 'file.txt', 'main.py']
+    hook.....................................................................Failed
+    - hook id: hook
+    - exit code: 1
+      ['.pre-commit-config.yaml', 'file.txt', 'main.py']
+
+    ----- stderr -----
+    "#);
+
+    Ok(())
+}
+
 /// Test selecting files by type, `types`, `types_or`, and `exclude_types`.
 #[test]
 fn file_types() -> Result<()> {
@@ -431,15 +531,15 @@ fn file_types() -> Result<()> {
     exit_code: 1
     ----- stdout -----
     trailing-whitespace......................................................Failed
-    - hook id: trailing-whitespace
+    - hook id: trailing-whitespace (1)
     - exit code: 1
       ['json.json']
     trailing-whitespace......................................................Failed
-    - hook id: trailing-whitespace
+    - hook id: trailing-whitespace (2)
     - exit code: 1
       ['json.json', 'main.py']
     trailing-whitespace......................................................Failed
-    - hook id: trailing-whitespace
+    - hook id: trailing-whitespace (3)
     - exit code: 1
       ['.pre-commit-config.yaml', 'file.txt', 'main.py']
     trailing-whitespace..................................(no files to check)Skipped
@@ -490,13 +590,136 @@ fn fail_fast() {
     exit_code: 1
     ----- stdout -----
     trailing-whitespace......................................................Failed
-    - hook id: trailing-whitespace
+    - hook id: trailing-whitespace (1)
     - exit code: 1
       Fixing files
     trailing-whitespace......................................................Failed
-    - hook id: trailing-whitespace
+    - hook id: trailing-whitespace (2)
     - exit code: 1
       Fixing files
+    trailing-whitespace...........................(skipped due to fail_fast)Skipped
+    trailing-whitespace...........................(skipped due to fail_fast)Skipped
+
+    ----- stderr -----
+    "#);
+}
+
+/// All hooks run by default and the exit code reflects any failure, `fail_fast` of the config
+/// or a hook stops at the first failure, and `--keep-going` runs all hooks regardless.
+#[test]
+fn keep_going() {
+    let context = TestContext::new();
+    context.init_project();
+
+    let config = |config_fail_fast: bool, hook_fail_fast: bool| {
+        indoc::formatdoc! {r"
+            fail_fast: {config_fail_fast}
+            repos:
+              - repo: local
+                hooks:
+                  - id: pass
+                    name: pass
+                    language: system
+                    entry: python3 -c 'exit(0)'
+                    always_run: true
+                    pass_filenames: false
+                  - id: fail
+                    name: fail
+                    language: system
+                    entry: python3 -c 'exit(1)'
+                    always_run: true
+                    pass_filenames: false
+                    fail_fast: {hook_fail_fast}
+                  - id: after
+                    name: after
+                    language: system
+                    entry: python3 -c 'exit(0)'
+                    always_run: true
+                    pass_filenames: false
+        "}
+    };
+
+    // By default, all hooks run and the failure is reported in the exit code.
+    context.write_pre_commit_config(&config(false, false));
+    context.git_add(".");
+    cmd_snapshot!(context.filters(), context.run(), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    pass.....................................................................Passed
+    fail.....................................................................Failed
+    - hook id: fail
+    - exit code: 1
+    after....................................................................Passed
+
+    ----- stderr -----
+    "#);
+
+    // Only passing hooks, the run succeeds.
+    cmd_snapshot!(context.filters(), context.run().arg("pass"), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    pass.....................................................................Passed
+
+    ----- stderr -----
+    "#);
+
+    // `fail_fast` of the config stops at the first failure.
+    context.write_pre_commit_config(&config(true, false));
+    context.git_add(".");
+    cmd_snapshot!(context.filters(), context.run(), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    pass.....................................................................Passed
+    fail.....................................................................Failed
+    - hook id: fail
+    - exit code: 1
+    after.........................................(skipped due to fail_fast)Skipped
+
+    ----- stderr -----
+    "#);
+
+    // `--keep-going` runs all hooks anyway, and still fails.
+    cmd_snapshot!(context.filters(), context.run().arg("--keep-going"), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    pass.....................................................................Passed
+    fail.....................................................................Failed
+    - hook id: fail
+    - exit code: 1
+    after....................................................................Passed
+
+    ----- stderr -----
+    "#);
+
+    // So does `fail_fast` of the failed hook.
+    context.write_pre_commit_config(&config(false, true));
+    context.git_add(".");
+    cmd_snapshot!(context.filters(), context.run(), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    pass.....................................................................Passed
+    fail.....................................................................Failed
+    - hook id: fail
+    - exit code: 1
+    after.........................................(skipped due to fail_fast)Skipped
+
+    ----- stderr -----
+    "#);
+
+    cmd_snapshot!(context.filters(), context.run().arg("--keep-going"), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    pass.....................................................................Passed
+    fail.....................................................................Failed
+    - hook id: fail
+    - exit code: 1
+    after....................................................................Passed
 
     ----- stderr -----
     "#);
@@ -572,6 +795,37 @@ fn log_file() {
 
     let log = context.read("log.txt");
     assert_eq!(log, "Fixing files");
+
+    // The output of passing hooks is logged too, the terminal only shows the status.
+    context.write_pre_commit_config(indoc::indoc! {r#"
+        repos:
+          - repo: local
+            hooks:
+              - id: chatty
+                name: chatty
+                language: system
+                entry: python3 -c 'print("Lots of output")'
+                always_run: true
+                verbose: true
+                log_file: logs/chatty.log
+    "#});
+    context.git_add(".");
+
+    cmd_snapshot!(context.filters(), context.run(), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    chatty...................................................................Passed
+    - hook id: chatty
+    - duration: [TIME]
+
+    ----- stderr -----
+    "#);
+
+    // The log file is replaced on each run.
+    context.run().assert().success();
+    let log = context.read("logs/chatty.log");
+    assert_eq!(log, "Lots of output");
 }
 
 /// Pass pre-commit environment variables to the hook.
@@ -600,6 +854,7 @@ fn pass_env_vars() {
     Pass environment.........................................................Passed
 
     ----- stderr -----
+    warning: The configuration file `.pre-commit-config.yaml` is not tracked by git
     "#);
 
     let env = context.read("env.txt");
@@ -661,6 +916,61 @@ fn staged_files_only() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn no_restore_on_failure() -> Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+    context.write_pre_commit_config(indoc::indoc! {r#"
+        repos:
+          - repo: local
+            hooks:
+              - id: modify
+                name: modify
+                language: system
+                entry: python3 -c 'open("file.txt", "wt").write("Modified by hook"); exit(1)'
+                pass_filenames: false
+   "#});
+
+    context
+        .workdir()
+        .child("file.txt")
+        .write_str("Hello, world!")?;
+    context.git_add(".");
+
+    context
+        .workdir()
+        .child("file.txt")
+        .write_str("Hello world again!")?;
+
+    let filters: Vec<_> = context
+        .filters()
+        .into_iter()
+        .chain([(r"/\d+-\d+.patch", "/[TIME]-[PID].patch")])
+        .collect();
+
+    // The working tree is left as the hook left it.
+    cmd_snapshot!(filters, context.run().arg("--no-restore-on-failure"), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    modify...................................................................Failed
+    - hook id: modify
+    - exit code: 1
+    - files were modified by this hook
+
+    ----- stderr -----
+    Non-staged changes detected, saving to `[HOME]/[TIME]-[PID].patch`
+
+    Non-staged changes are not restored, they are saved in `[HOME]/[TIME]-[PID].patch`.
+    To restore them, run `git checkout -- .` to discard the hook modifications, then `git apply [HOME]/[TIME]-[PID].patch`
+    "#);
+
+    let content = context.read("file.txt");
+    assert_snapshot!(content, @"Modified by hook");
+
+    Ok(())
+}
+
 #[cfg(unix)]
 #[test]
 fn restore_on_interrupt() -> Result<()> {
@@ -714,3 +1024,2655 @@ fn restore_on_interrupt() -> Result<()> {
 
     Ok(())
 }
+
+/// Hooks without `stages` inherit the top-level `default_stages`.
+#[test]
+fn default_stages() {
+    let context = TestContext::new();
+    context.init_project();
+
+    // `commit` is the deprecated name of `pre-commit`.
+    context.write_pre_commit_config(indoc::indoc! {r#"
+        default_stages: [commit]
+        repos:
+          - repo: local
+            hooks:
+              - id: default-stages
+                name: default-stages
+                language: system
+                entry: python3 -c 'print("default stages")'
+                always_run: true
+                verbose: true
+              - id: pre-push
+                name: pre-push
+                language: system
+                entry: python3 -c 'print("pre-push")'
+                always_run: true
+                verbose: true
+                stages: [pre-push]
+    "#});
+    context.git_add(".");
+
+    cmd_snapshot!(context.filters(), context.run().arg("--hook-stage").arg("pre-push"), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    pre-push.................................................................Passed
+    - hook id: pre-push
+    - duration: [TIME]
+      pre-push
+
+    ----- stderr -----
+    "#);
+
+    cmd_snapshot!(context.filters(), context.run().arg("--hook-stage").arg("commit"), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    default-stages...........................................................Passed
+    - hook id: default-stages
+    - duration: [TIME]
+      default stages
+
+    ----- stderr -----
+    "#);
+}
+
+/// Meta hooks honor `stages` like any other hook.
+#[test]
+fn meta_hooks_stages() {
+    let context = TestContext::new();
+    context.init_project();
+
+    context.write_pre_commit_config(indoc::indoc! {r"
+        repos:
+          - repo: meta
+            hooks:
+              - id: check-hooks-apply
+                stages: [manual]
+              - id: check-useless-excludes
+              - id: identity
+                stages: [manual]
+          - repo: local
+            hooks:
+              - id: nothing
+                name: nothing
+                language: system
+                entry: echo
+                files: \.py$
+                exclude: ^vendor/
+    "});
+    context.git_add(".");
+
+    cmd_snapshot!(context.filters(), context.run(), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    Check for useless excludes...............................................Failed
+    - hook id: check-useless-excludes
+    - exit code: 1
+      The exclude pattern `^vendor/` for nothing does not match any files
+    nothing..............................................(no files to check)Skipped
+
+    ----- stderr -----
+    "#);
+
+    cmd_snapshot!(context.filters(), context.run().arg("--hook-stage").arg("manual"), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    Check hooks apply to the repository......................................Failed
+    - hook id: check-hooks-apply
+    - exit code: 1
+      nothing does not apply to this repository
+    Check for useless excludes...............................................Failed
+    - hook id: check-useless-excludes
+    - exit code: 1
+      The exclude pattern `^vendor/` for nothing does not match any files
+    identity.................................................................Passed
+    - hook id: identity
+    - duration: [TIME]
+      .pre-commit-config.yaml
+    nothing..............................................(no files to check)Skipped
+
+    ----- stderr -----
+    "#);
+}
+
+/// Repos of included configs are merged in, the including config takes precedence.
+#[test]
+fn include_configs() -> Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+
+    context.write_pre_commit_config(indoc::indoc! {r"
+        include: [shared/base.yaml]
+        repos:
+          - repo: local
+            hooks:
+              - id: main
+                name: main
+                language: system
+                entry: 'true'
+                always_run: true
+              - id: override
+                name: override from main
+                language: system
+                entry: 'true'
+                always_run: true
+    "});
+    let cwd = context.workdir();
+    cwd.child("shared/base.yaml").write_str(indoc::indoc! {r"
+        include: [org.yaml]
+        repos:
+          - repo: local
+            hooks:
+              - id: base
+                name: base
+                language: system
+                entry: 'true'
+                always_run: true
+              - id: override
+                name: override from base
+                language: system
+                entry: 'true'
+                always_run: true
+    "})?;
+    cwd.child("shared/org.yaml").write_str(indoc::indoc! {r"
+        repos:
+          - repo: local
+            hooks:
+              - id: org
+                name: org
+                language: system
+                entry: 'true'
+                always_run: true
+    "})?;
+    context.git_add(".");
+
+    cmd_snapshot!(context.filters(), context.run(), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    org......................................................................Passed
+    base.....................................................................Passed
+    main.....................................................................Passed
+    override from main.......................................................Passed
+
+    ----- stderr -----
+    "#);
+
+    // A config can't include itself, directly or not.
+    cwd.child("shared/org.yaml")
+        .write_str("include: [../.pre-commit-config.yaml]\nrepos: []\n")?;
+    context.git_add(".");
+
+    cmd_snapshot!(context.filters(), context.run(), @r#"
+    success: false
+    exit_code: 2
+    ----- stdout -----
+
+    ----- stderr -----
+    error: Configuration files include each other: `.pre-commit-config.yaml` -> `shared/base.yaml` -> `shared/org.yaml` -> `shared/../.pre-commit-config.yaml`
+    "#);
+
+    Ok(())
+}
+
+/// Global `exclude` applies to every hook, in addition to the hook level `files` and `exclude`.
+#[test]
+fn global_exclude() -> Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+
+    let cwd = context.workdir();
+    cwd.child("main.py").write_str("print('main')\n")?;
+    cwd.child("vendor/lib.py").write_str("print('lib')\n")?;
+    cwd.child("vendor/data.json").write_str("{}\n")?;
+
+    context.write_pre_commit_config(indoc::indoc! {r"
+        exclude: ^vendor/
+        repos:
+          - repo: local
+            hooks:
+              - id: python
+                name: python
+                language: system
+                entry: python3 -c 'import sys; print(sorted(sys.argv[1:])); exit(1)'
+                files: \.py$
+              - id: json
+                name: json
+                language: system
+                entry: python3 -c 'import sys; print(sorted(sys.argv[1:])); exit(1)'
+                types: [json]
+              - id: all
+                name: all
+                language: system
+                entry: python3 -c 'import sys; print(sorted(sys.argv[1:])); exit(1)'
+                exclude: \.yaml$
+    "});
+    context.git_add(".");
+
+    cmd_snapshot!(context.filters(), context.run(), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    python...................................................................Failed
+    - hook id: python
+    - exit code: 1
+      ['main.py']
+    json.................................................(no files to check)Skipped
+    all......................................................................Failed
+    - hook id: all
+    - exit code: 1
+      ['main.py']
+
+    ----- stderr -----
+    "#);
+
+    Ok(())
+}
+
+/// `--quiet` only prints failed hooks, `-qq` prints nothing.
+#[test]
+fn quiet() {
+    let context = TestContext::new();
+    context.init_project();
+
+    context.write_pre_commit_config(indoc::indoc! {r#"
+        repos:
+          - repo: local
+            hooks:
+              - id: success
+                name: success
+                language: system
+                entry: python3 -c 'print("success")'
+                always_run: true
+              - id: failure
+                name: failure
+                language: system
+                entry: python3 -c 'print("failure"); exit(1)'
+                always_run: true
+    "#});
+    context.git_add(".");
+
+    cmd_snapshot!(context.filters(), context.run().arg("--quiet"), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    failure..................................................................Failed
+    - hook id: failure
+    - exit code: 1
+      failure
+
+    ----- stderr -----
+    "#);
+
+    cmd_snapshot!(context.filters(), context.run().arg("-qq"), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+
+    ----- stderr -----
+    "#);
+}
+
+/// Warn if the config file is not committed.
+#[test]
+fn config_not_committed() -> Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+
+    context.write_pre_commit_config(indoc::indoc! {r"
+        repos:
+          - repo: local
+            hooks:
+              - id: local
+                name: local
+                language: system
+                entry: python3 -V
+                always_run: true
+    "});
+
+    cmd_snapshot!(context.filters(), context.run(), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    local....................................................................Passed
+
+    ----- stderr -----
+    warning: The configuration file `.pre-commit-config.yaml` is not tracked by git
+    "#);
+
+    context.git_add(".");
+    Command::new("git")
+        .arg("commit")
+        .arg("-m")
+        .arg("Initial commit")
+        .current_dir(context.workdir())
+        .assert()
+        .success();
+
+    cmd_snapshot!(context.filters(), context.run(), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    local....................................................................Passed
+
+    ----- stderr -----
+    "#);
+
+    context
+        .workdir()
+        .child(".pre-commit-config.yaml")
+        .write_str(indoc::indoc! {r"
+        repos:
+          - repo: local
+            hooks:
+              - id: local
+                name: local
+                language: system
+                entry: python3 -V
+                always_run: true
+                pass_filenames: false
+    "})?;
+    context.git_add(".");
+
+    cmd_snapshot!(context.filters(), context.run(), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    local....................................................................Passed
+
+    ----- stderr -----
+    warning: The configuration file `.pre-commit-config.yaml` has uncommitted changes
+    "#);
+
+    Ok(())
+}
+
+/// Run a hook in a subdirectory, filenames are relative to the working directory.
+#[test]
+fn work_dir() -> Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+
+    let cwd = context.workdir();
+    cwd.child("foo/a.txt").write_str("Hello, world!\n")?;
+    cwd.child("b.txt").write_str("Hello, world!\n")?;
+
+    context.write_pre_commit_config(indoc::indoc! {r"
+        repos:
+          - repo: local
+            hooks:
+              - id: work-dir
+                name: work-dir
+                language: system
+                entry: python3 -c 'import os, sys; print(os.path.basename(os.getcwd()), sorted(sys.argv[1:])); exit(1)'
+                files: \.txt$
+                work_dir: foo
+    "});
+    context.git_add(".");
+
+    cmd_snapshot!(context.filters(), context.run(), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    work-dir.................................................................Failed
+    - hook id: work-dir
+    - exit code: 1
+      foo ['../b.txt', 'a.txt']
+
+    ----- stderr -----
+    "#);
+
+    Ok(())
+}
+
+/// Truncate hook output longer than `--max-output-lines`.
+#[test]
+fn max_output_lines() {
+    let context = TestContext::new();
+    context.init_project();
+
+    context.write_pre_commit_config(indoc::indoc! {r#"
+        repos:
+          - repo: local
+            hooks:
+              - id: noisy
+                name: noisy
+                language: system
+                entry: python3 -c 'print("\n".join(map(str, range(10)))); exit(1)'
+                always_run: true
+    "#});
+    context.git_add(".");
+
+    cmd_snapshot!(context.filters(), context.run().arg("--max-output-lines").arg("3"), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    noisy....................................................................Failed
+    - hook id: noisy
+    - exit code: 1
+      0
+      1
+      2
+      ... [7 lines truncated]
+
+    ----- stderr -----
+    "#);
+}
+
+/// Load the config file from a git rev instead of the working tree.
+#[test]
+fn config_rev() -> Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+
+    context.write_pre_commit_config(indoc::indoc! {r#"
+        repos:
+          - repo: local
+            hooks:
+              - id: old
+                name: old
+                language: system
+                entry: python3 -c 'print("old"); exit(1)'
+                always_run: true
+    "#});
+    context.git_add(".");
+    Command::new("git")
+        .arg("commit")
+        .arg("-m")
+        .arg("Initial commit")
+        .current_dir(context.workdir())
+        .assert()
+        .success();
+
+    context
+        .workdir()
+        .child(".pre-commit-config.yaml")
+        .write_str(indoc::indoc! {r#"
+        repos:
+          - repo: local
+            hooks:
+              - id: new
+                name: new
+                language: system
+                entry: python3 -c 'print("new"); exit(1)'
+                always_run: true
+    "#})?;
+
+    cmd_snapshot!(context.filters(), context.run().arg("--all-files").arg("--config-rev").arg("HEAD"), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    old..............................................................(1 file)Failed
+    - hook id: old
+    - exit code: 1
+      old
+
+    ----- stderr -----
+    "#);
+
+    Ok(())
+}
+
+/// Capture the stderr of a failing hook and display it with the hook output.
+#[test]
+fn capture_stderr() {
+    let context = TestContext::new();
+    context.init_project();
+
+    context.write_pre_commit_config(indoc::indoc! {r#"
+        repos:
+          - repo: local
+            hooks:
+              - id: stderr
+                name: stderr
+                language: system
+                entry: python3 -c 'import sys; print("error message", file=sys.stderr); exit(2)'
+                always_run: true
+    "#});
+    context.git_add(".");
+
+    cmd_snapshot!(context.filters(), context.run(), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    stderr...................................................................Failed
+    - hook id: stderr
+    - exit code: 2
+      error message
+
+    ----- stderr -----
+    "#);
+}
+
+/// `--jobs 1` runs all files in a single batch, unless `--batch-size` is smaller.
+#[test]
+fn jobs() -> Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+
+    let cwd = context.workdir();
+    for i in 0..10 {
+        cwd.child(format!("file-{i}.txt"))
+            .write_str("Hello, world!\n")?;
+    }
+
+    context.write_pre_commit_config(indoc::indoc! {r"
+        repos:
+          - repo: local
+            hooks:
+              - id: count
+                name: count
+                language: system
+                entry: python3 -c 'import sys; print(len(sys.argv) - 1); exit(1)'
+                files: \.txt$
+    "});
+    context.git_add(".");
+
+    cmd_snapshot!(context.filters(), context.run().arg("--jobs").arg("1"), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    count....................................................................Failed
+    - hook id: count
+    - exit code: 1
+      10
+
+    ----- stderr -----
+    "#);
+
+    // `--batch-size` caps the files passed to each invocation.
+    cmd_snapshot!(context.filters(), context.run().arg("--jobs").arg("1").arg("--batch-size").arg("4"), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    count....................................................................Failed
+    - hook id: count
+    - exit code: 1
+      4
+      4
+      2
+
+    ----- stderr -----
+    "#);
+
+    cmd_snapshot!(context.filters(), context.run().arg("--parallel-repos").arg("0"), @r#"
+    success: false
+    exit_code: 2
+    ----- stdout -----
+
+    ----- stderr -----
+    error: invalid value '0' for '--parallel-repos <N>': number would be zero for non-zero type
+
+    For more information, try '--help'.
+    "#);
+
+    Ok(())
+}
+
+/// Without `--hook-stage`, the stage is `pre-commit` or implied by the git hook arguments.
+#[test]
+fn detect_stage() -> Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+
+    context
+        .workdir()
+        .child("COMMIT_EDITMSG")
+        .write_str("Commit message\n")?;
+    context.write_pre_commit_config(indoc::indoc! {r#"
+        repos:
+          - repo: local
+            hooks:
+              - id: pre-commit
+                name: pre-commit
+                language: system
+                entry: python3 -c 'print("pre-commit")'
+                always_run: true
+                verbose: true
+                stages: [pre-commit]
+              - id: commit-msg
+                name: commit-msg
+                language: system
+                entry: python3 -c 'import sys; print(open(sys.argv[1]).read())'
+                verbose: true
+                stages: [commit-msg]
+    "#});
+    context.git_add(".");
+
+    cmd_snapshot!(context.filters(), context.run(), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    pre-commit...............................................................Passed
+    - hook id: pre-commit
+    - duration: [TIME]
+      pre-commit
+
+    ----- stderr -----
+    "#);
+
+    cmd_snapshot!(context.filters(), context.run().arg("--commit-msg-filename").arg("COMMIT_EDITMSG"), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    commit-msg...............................................................Passed
+    - hook id: commit-msg
+    - duration: [TIME]
+      Commit message
+
+    ----- stderr -----
+    "#);
+
+    Ok(())
+}
+
+/// A failed environment install is removed, instead of being left behind as installed.
+#[test]
+fn failed_install() -> Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+
+    context.write_pre_commit_config(indoc::indoc! {r"
+        repos:
+          - repo: local
+            hooks:
+              - id: local
+                name: local
+                language: python
+                entry: python -V
+                additional_dependencies: ['./does-not-exist']
+                always_run: true
+    "});
+    context.git_add(".");
+
+    let envs = || -> Result<Vec<String>> {
+        let mut envs = Vec::new();
+        for repo in fs_err::read_dir(context.home_dir().path())? {
+            let repo = repo?.path();
+            if !repo.is_dir() {
+                continue;
+            }
+            for env in fs_err::read_dir(&repo)? {
+                // The lock file of the environment is left next to it.
+                let env = env?.path();
+                let name = env.file_name().unwrap().to_string_lossy().to_string();
+                if env.is_dir() && name.starts_with("py_env") {
+                    envs.push(name);
+                }
+            }
+        }
+        Ok(envs)
+    };
+
+    context.run().assert().failure();
+    assert!(envs()?.is_empty());
+
+    // The failed environment is not treated as installed.
+    context.run().assert().failure();
+    assert!(envs()?.is_empty());
+
+    Ok(())
+}
+
+/// `git commit <paths>` runs the hook with `GIT_INDEX_FILE` pointing to a temporary index.
+#[test]
+fn git_index_file() -> Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+    context.write_pre_commit_config(indoc::indoc! {r"
+        repos:
+          - repo: local
+            hooks:
+              - id: echo
+                name: echo
+                language: system
+                entry: echo
+                files: \.txt$
+                verbose: true
+    "});
+
+    let cwd = context.workdir();
+    cwd.child("a.txt").write_str("Hello, a!")?;
+    cwd.child("b.txt").write_str("Hello, b!")?;
+    cwd.child("sub").create_dir_all()?;
+
+    Command::new("git")
+        .arg("add")
+        .arg(".pre-commit-config.yaml")
+        .arg("a.txt")
+        .current_dir(cwd)
+        .assert()
+        .success();
+    Command::new("git")
+        .arg("add")
+        .arg(".pre-commit-config.yaml")
+        .arg("b.txt")
+        .env("GIT_INDEX_FILE", ".git/tmp_index")
+        .current_dir(cwd)
+        .assert()
+        .success();
+
+    // A relative index path is resolved against the directory pre-commit is run from.
+    let mut run = context.run();
+    run.env("GIT_INDEX_FILE", "../.git/tmp_index")
+        .current_dir(cwd.child("sub"));
+
+    cmd_snapshot!(context.filters(), run, @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    echo.....................................................................Passed
+    - hook id: echo
+    - duration: [TIME]
+      b.txt
+
+    ----- stderr -----
+    "#);
+
+    Ok(())
+}
+
+#[test]
+fn conflicted_files() -> Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+    context.write_pre_commit_config(indoc::indoc! {r"
+        repos:
+          - repo: local
+            hooks:
+              - id: echo
+                name: echo
+                language: system
+                entry: echo
+    "});
+
+    let cwd = context.workdir();
+    let git = |args: &[&str]| {
+        Command::new("git")
+            .args(args)
+            .current_dir(cwd)
+            .assert()
+            .success();
+    };
+
+    cwd.child("a.txt").write_str("base\n")?;
+    cwd.child("b.txt").write_str("base\n")?;
+    git(&["add", "."]);
+    git(&["commit", "-m", "base"]);
+
+    git(&["checkout", "-b", "other"]);
+    cwd.child("a.txt").write_str("other\n")?;
+    cwd.child("b.txt").write_str("other\n")?;
+    git(&["commit", "-am", "other"]);
+
+    git(&["checkout", "-"]);
+    cwd.child("a.txt").write_str("main\n")?;
+    cwd.child("b.txt").write_str("main\n")?;
+    git(&["commit", "-am", "main"]);
+
+    Command::new("git")
+        .arg("merge")
+        .arg("other")
+        .current_dir(cwd)
+        .assert()
+        .failure();
+
+    cmd_snapshot!(context.filters(), context.run(), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+
+    ----- stderr -----
+    You have unmerged paths. Resolve them before running pre-commit.
+      a.txt
+      b.txt
+    "#);
+
+    Ok(())
+}
+
+#[test]
+fn diff_scope() -> Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+    context.write_pre_commit_config(indoc::indoc! {r#"
+        repos:
+          - repo: local
+            hooks:
+              - id: modify
+                name: modify
+                language: system
+                entry: python3 -c 'open("a.txt", "wt").write("modified\n")'
+                files: ^a\.txt$
+                pass_filenames: false
+    "#});
+
+    let cwd = context.workdir();
+    cwd.child("a.txt").write_str("a\n")?;
+    cwd.child("b.txt").write_str("b\n")?;
+    context.git_add(".");
+
+    // An unrelated change, which is not shown.
+    cwd.child("b.txt").write_str("unrelated\n")?;
+
+    cmd_snapshot!(context.filters(), context.run().arg("--all-files").arg("--show-diff-on-failure").arg("--diff-scope").arg("touched"), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    modify...........................................................(1 file)Failed
+    - hook id: modify
+    - files were modified by this hook
+    All changes made by hooks:
+    diff --git a/a.txt b/a.txt
+    index 7898192..2e09960 100644
+    --- a/a.txt
+    +++ b/a.txt
+    @@ -1 +1 @@
+    -a
+    +modified
+
+    ----- stderr -----
+    "#);
+
+    Ok(())
+}
+
+/// With non-staged changes stashed, the diff is against the index before hooks ran.
+#[test]
+fn show_diff_on_failure_stashed() -> Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+    context.write_pre_commit_config(indoc::indoc! {r"
+        repos:
+          - repo: local
+            hooks:
+              - id: modify
+                name: modify
+                language: system
+                entry: sh -c 'echo modified > a.txt && git add a.txt && exit 1'
+                files: ^a\.txt$
+                pass_filenames: false
+    "});
+
+    let cwd = context.workdir();
+    cwd.child("a.txt").write_str("a\n")?;
+    cwd.child("b.txt").write_str("b\n")?;
+    context.git_add(".");
+
+    // A non-staged change, which is stashed and not shown.
+    cwd.child("b.txt").write_str("unstaged\n")?;
+
+    let filters: Vec<_> = context
+        .filters()
+        .into_iter()
+        .chain([(r"/\d+-\d+.patch", "/[TIME]-[PID].patch")])
+        .collect();
+
+    // The change staged by the hook is shown too.
+    cmd_snapshot!(filters, context.run().arg("--show-diff-on-failure"), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    modify...................................................................Failed
+    - hook id: modify
+    - exit code: 1
+    All changes made by hooks:
+    diff --git a/a.txt b/a.txt
+    index 7898192..2e09960 100644
+    --- a/a.txt
+    +++ b/a.txt
+    @@ -1 +1 @@
+    -a
+    +modified
+
+    ----- stderr -----
+    Non-staged changes detected, saving to `[HOME]/[TIME]-[PID].patch`
+
+    Restored working tree changes from `[HOME]/[TIME]-[PID].patch`
+    "#);
+
+    assert_snapshot!(context.read("b.txt"), @"unstaged");
+
+    Ok(())
+}
+
+/// Without stashing, e.g. with `--all-files`, the diff is against the working tree before hooks
+/// ran, so the user's own non-staged changes are not shown as changes made by hooks.
+#[test]
+fn show_diff_on_failure_all_files() -> Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+    context.write_pre_commit_config(indoc::indoc! {r"
+        repos:
+          - repo: local
+            hooks:
+              - id: format
+                name: format
+                language: system
+                entry: python3 -c 'import sys, pathlib; [pathlib.Path(f).write_text(pathlib.Path(f).read_text().upper()) for f in sys.argv[1:]]'
+                files: \.txt$
+    "});
+
+    let cwd = context.workdir();
+    cwd.child("a.txt").write_str("a\n")?;
+    cwd.child("notes.md").write_str("notes\n")?;
+    context.git_add(".");
+
+    // Non-staged changes, to a file the hook formats and to an unrelated file.
+    cwd.child("a.txt").write_str("b\n")?;
+    cwd.child("notes.md").write_str("draft\n")?;
+
+    cmd_snapshot!(context.filters(), context.run().arg("--all-files").arg("--show-diff-on-failure"), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    format...........................................................(1 file)Failed
+    - hook id: format
+    - files were modified by this hook
+    All changes made by hooks:
+    diff --git a/a.txt b/a.txt
+    index 6178079..223b783 100644
+    --- a/a.txt
+    +++ b/a.txt
+    @@ -1 +1 @@
+    -b
+    +B
+
+    ----- stderr -----
+    "#);
+
+    // The index is left as it was.
+    let staged = Command::new("git")
+        .args(["show", ":a.txt"])
+        .current_dir(cwd)
+        .output()?;
+    assert_snapshot!(String::from_utf8_lossy(&staged.stdout), @"a");
+
+    Ok(())
+}
+
+#[test]
+fn duplicate_hook_ids() {
+    let context = TestContext::new();
+    context.init_project();
+    context.write_pre_commit_config(indoc::indoc! {r"
+        repos:
+          - repo: local
+            hooks:
+              - id: echo
+                name: echo
+                language: system
+                entry: echo first
+                pass_filenames: false
+                always_run: true
+                verbose: true
+              - id: other
+                name: other
+                language: system
+                entry: echo other
+                pass_filenames: false
+                always_run: true
+                verbose: true
+              - id: echo
+                name: echo
+                language: system
+                entry: echo second
+                pass_filenames: false
+                always_run: true
+                verbose: true
+    "});
+    context.git_add(".");
+
+    cmd_snapshot!(context.filters(), context.run(), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    echo.....................................................................Passed
+    - hook id: echo (1)
+    - duration: [TIME]
+      first
+    other....................................................................Passed
+    - hook id: other
+    - duration: [TIME]
+      other
+    echo.....................................................................Passed
+    - hook id: echo (2)
+    - duration: [TIME]
+      second
+
+    ----- stderr -----
+    "#);
+}
+
+/// The entry is split with shell-like word splitting, quoted words are kept together.
+#[test]
+fn entry_with_quotes() {
+    let context = TestContext::new();
+    context.init_project();
+    context.write_pre_commit_config(indoc::indoc! {r#"
+        repos:
+          - repo: local
+            hooks:
+              - id: quoted
+                name: quoted
+                language: system
+                entry: python3 -c "import sys; print(sys.argv[1:])" "hello world" 'single quoted'
+                args: [--flag, "with space"]
+                pass_filenames: false
+                always_run: true
+                verbose: true
+    "#});
+    context.git_add(".");
+
+    cmd_snapshot!(context.filters(), context.run(), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    quoted...................................................................Passed
+    - hook id: quoted
+    - duration: [TIME]
+      ['hello world', 'single quoted', '--flag', 'with space']
+
+    ----- stderr -----
+    "#);
+}
+
+#[test]
+fn skip_reasons() {
+    let context = TestContext::new();
+    context.init_project();
+    context.write_pre_commit_config(indoc::indoc! {r"
+        repos:
+          - repo: local
+            hooks:
+              - id: skipped
+                name: skipped by env
+                language: system
+                entry: echo
+              - id: no-files
+                name: no files
+                language: system
+                entry: echo
+                files: \.rs$
+              - id: manual
+                name: manual only
+                language: system
+                entry: echo
+                stages: [manual]
+              - id: echo
+                name: echo
+                language: system
+                entry: echo hello
+                pass_filenames: false
+    "});
+    context.git_add(".");
+
+    cmd_snapshot!(context.filters(), context.run().env("SKIP", "skipped"), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    skipped by env..........................................................Skipped
+    no files.............................................(no files to check)Skipped
+    echo.....................................................................Passed
+
+    ----- stderr -----
+    "#);
+
+    // Verbose mode shows why each hook didn't run.
+    cmd_snapshot!(context.filters(), context.run().arg("-v").env("SKIP", "skipped").env("RUST_LOG", "off"), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    skipped by env................................................(SKIP env)Skipped
+    no files.............................................(no files to check)Skipped
+    manual only...............................................(not in stage)Skipped
+    echo.....................................................................Passed
+    - hook id: echo
+    - duration: [TIME]
+    - command: echo hello
+    - cwd: .
+      hello
+
+    ----- stderr -----
+    "#);
+}
+
+/// Verbose mode shows the command line of each hook, to reproduce it by hand.
+#[test]
+fn verbose_command_line() -> Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+    context.write_pre_commit_config(indoc::indoc! {r"
+        repos:
+          - repo: local
+            hooks:
+              - id: echo
+                name: echo
+                language: system
+                entry: echo
+                args: [--flag]
+                files: \.txt$
+    "});
+
+    let cwd = context.workdir();
+    for idx in 1..=5 {
+        cwd.child(format!("file{idx}.txt"))
+            .write_str("Hello, world!\n")?;
+    }
+    context.git_add(".");
+
+    // Filenames are shuffled.
+    let filters = [(r"file\d\.txt", "[FILE]")]
+        .into_iter()
+        .chain(context.filters())
+        .collect::<Vec<_>>();
+
+    cmd_snapshot!(filters, context.run().arg("-v").env("RUST_LOG", "off"), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    echo.....................................................................Passed
+    - hook id: echo
+    - duration: [TIME]
+    - command: echo --flag [FILE] [FILE] [FILE] …
+    - cwd: .
+      --flag [FILE] [FILE] [FILE] [FILE] [FILE]
+
+    ----- stderr -----
+    "#);
+
+    Ok(())
+}
+
+#[test]
+fn from_ref_aliases() -> Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+    context.write_pre_commit_config(indoc::indoc! {r"
+        repos:
+          - repo: local
+            hooks:
+              - id: echo
+                name: echo
+                language: system
+                entry: echo
+                files: \.txt$
+                verbose: true
+    "});
+
+    let cwd = context.workdir();
+    let git = |args: &[&str]| {
+        Command::new("git")
+            .args(args)
+            .current_dir(cwd)
+            .assert()
+            .success();
+    };
+
+    cwd.child("a.txt").write_str("Hello, a!")?;
+    git(&["add", "."]);
+    git(&["commit", "-m", "first"]);
+    cwd.child("b.txt").write_str("Hello, b!")?;
+    git(&["add", "b.txt"]);
+    git(&["commit", "-m", "second"]);
+
+    cmd_snapshot!(context.filters(), context.run().args(["--from-ref", "HEAD~1", "--to-ref", "HEAD"]), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    echo.....................................................................Passed
+    - hook id: echo
+    - duration: [TIME]
+      b.txt
+
+    ----- stderr -----
+    "#);
+
+    // The deprecated `--source`/`--origin` spellings select the same files.
+    cmd_snapshot!(context.filters(), context.run().args(["--source", "HEAD~1", "--origin", "HEAD"]), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    echo.....................................................................Passed
+    - hook id: echo
+    - duration: [TIME]
+      b.txt
+
+    ----- stderr -----
+    "#);
+
+    Ok(())
+}
+
+/// A rename at the boundary of git's similarity threshold maps to the new path, whether it is
+/// detected as a rename or not.
+#[test]
+fn from_ref_near_rename() -> Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+    context.write_pre_commit_config(indoc::indoc! {r"
+        repos:
+          - repo: local
+            hooks:
+              - id: echo
+                name: echo
+                language: system
+                entry: echo
+                files: \.txt$
+                verbose: true
+    "});
+
+    let cwd = context.workdir();
+    let git = |args: &[&str]| {
+        Command::new("git")
+            .args(args)
+            .current_dir(cwd)
+            .assert()
+            .success();
+    };
+
+    let lines: Vec<_> = (0..10).map(|i| format!("line {i}\n")).collect();
+    cwd.child("old.txt").write_str(&lines.concat())?;
+    git(&["add", "."]);
+    git(&["commit", "-m", "first"]);
+
+    // Half of the lines change, right at git's default 50% threshold.
+    git(&["mv", "old.txt", "new.txt"]);
+    let changed: String = lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            if i % 2 == 0 {
+                format!("changed {i}\n")
+            } else {
+                line.clone()
+            }
+        })
+        .collect();
+    cwd.child("new.txt").write_str(&changed)?;
+    git(&["add", "."]);
+    git(&["commit", "-m", "rename"]);
+
+    cmd_snapshot!(context.filters(), context.run().args(["--from-ref", "HEAD~1", "--to-ref", "HEAD"]), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    echo.....................................................................Passed
+    - hook id: echo
+    - duration: [TIME]
+      new.txt
+
+    ----- stderr -----
+    "#);
+
+    Ok(())
+}
+
+/// `--from-ref` runs on the changes since the branches diverged, unless `--ref-range two-dot`.
+#[test]
+fn from_ref_diverged_branch() -> Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+    context.write_pre_commit_config(indoc::indoc! {r"
+        repos:
+          - repo: local
+            hooks:
+              - id: files
+                name: files
+                language: system
+                entry: python3 -c 'import sys; print(sorted(sys.argv[1:]))'
+                files: \.txt$
+                verbose: true
+    "});
+
+    let cwd = context.workdir();
+    let git = |args: &[&str]| {
+        Command::new("git")
+            .args(args)
+            .current_dir(cwd)
+            .assert()
+            .success();
+    };
+
+    cwd.child("shared.txt").write_str("Hello, world!\n")?;
+    git(&["add", "."]);
+    git(&["commit", "-m", "base"]);
+
+    // The base branch moves on after the feature branch diverged.
+    git(&["checkout", "-b", "base"]);
+    cwd.child("shared.txt").write_str("Hello, base!\n")?;
+    git(&["commit", "-am", "change on base"]);
+
+    git(&["checkout", "-b", "feature", "HEAD~1"]);
+    cwd.child("feature.txt").write_str("Hello, feature!\n")?;
+    git(&["add", "."]);
+    git(&["commit", "-m", "change on feature"]);
+
+    cmd_snapshot!(context.filters(), context.run().args(["--from-ref", "base", "--to-ref", "HEAD"]), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    files....................................................................Passed
+    - hook id: files
+    - duration: [TIME]
+      ['feature.txt']
+
+    ----- stderr -----
+    "#);
+
+    cmd_snapshot!(context.filters(), context.run().args(["--from-ref", "base", "--to-ref", "HEAD", "--ref-range", "two-dot"]), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    files....................................................................Passed
+    - hook id: files
+    - duration: [TIME]
+      ['feature.txt', 'shared.txt']
+
+    ----- stderr -----
+    "#);
+
+    Ok(())
+}
+
+#[test]
+fn hook_verbose() {
+    let context = TestContext::new();
+    context.init_project();
+    context.write_pre_commit_config(indoc::indoc! {r"
+        repos:
+          - repo: local
+            hooks:
+              - id: quiet
+                name: quiet
+                language: system
+                entry: echo quiet
+                pass_filenames: false
+              - id: noisy
+                name: noisy
+                language: system
+                entry: echo noisy
+                pass_filenames: false
+                verbose: true
+              - id: skipped
+                name: skipped
+                language: system
+                entry: echo skipped
+                pass_filenames: false
+              - id: skipped-noisy
+                name: skipped noisy
+                language: system
+                entry: echo skipped
+                pass_filenames: false
+                verbose: true
+    "});
+    context.git_add(".");
+
+    // Only the hook with `verbose: true` shows its output and skip reason.
+    cmd_snapshot!(context.filters(), context.run().env("SKIP", "skipped,skipped-noisy"), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    quiet....................................................................Passed
+    noisy....................................................................Passed
+    - hook id: noisy
+    - duration: [TIME]
+      noisy
+    skipped.................................................................Skipped
+    skipped noisy.................................................(SKIP env)Skipped
+
+    ----- stderr -----
+    "#);
+}
+
+#[test]
+fn files_with_directories() -> Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+    context.write_pre_commit_config(indoc::indoc! {r"
+        repos:
+          - repo: local
+            hooks:
+              - id: echo
+                name: echo
+                language: system
+                entry: echo
+                verbose: true
+    "});
+
+    let cwd = context.workdir();
+    cwd.child("a.txt").write_str("Hello, a!")?;
+    cwd.child("dir/b.txt").write_str("Hello, b!")?;
+    cwd.child("dir/sub/c.txt").write_str("Hello, c!")?;
+    cwd.child("other/d.txt").write_str("Hello, d!")?;
+    context.git_add(".");
+    // Untracked files under the directory are not selected.
+    cwd.child("dir/untracked.txt")
+        .write_str("Hello, untracked!")?;
+
+    cmd_snapshot!(context.filters(), context.run().args(["--files", "a.txt", "--files", "dir"]), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    echo.....................................................................Passed
+    - hook id: echo
+    - duration: [TIME]
+      a.txt dir/b.txt dir/sub/c.txt
+
+    ----- stderr -----
+    "#);
+
+    Ok(())
+}
+
+#[test]
+fn files_after_separator() -> Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+    context.write_pre_commit_config(indoc::indoc! {r"
+        repos:
+          - repo: local
+            hooks:
+              - id: echo
+                name: echo
+                language: system
+                entry: echo
+                verbose: true
+              - id: lint
+                name: lint
+                language: system
+                entry: echo linting
+                verbose: true
+    "});
+
+    // A file named the same as a hook id.
+    let cwd = context.workdir();
+    cwd.child("lint").write_str("Hello, lint!")?;
+    cwd.child("a.txt").write_str("Hello, a!")?;
+    context.git_add(".");
+
+    // `lint` after `--` is a file, not a hook id.
+    cmd_snapshot!(context.filters(), context.run().args(["echo", "--", "lint"]), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    echo.....................................................................Passed
+    - hook id: echo
+    - duration: [TIME]
+      lint
+
+    ----- stderr -----
+    "#);
+
+    // Without a hook id, all hooks run on the files.
+    cmd_snapshot!(context.filters(), context.run().args(["--", "lint", "a.txt"]), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    echo.....................................................................Passed
+    - hook id: echo
+    - duration: [TIME]
+      a.txt lint
+    lint.....................................................................Passed
+    - hook id: lint
+    - duration: [TIME]
+      linting a.txt lint
+
+    ----- stderr -----
+    "#);
+
+    Ok(())
+}
+
+#[test]
+fn submodules() -> Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+
+    let submodule = context.home_dir().child("submodule");
+    submodule.create_dir_all()?;
+    make_hooks_repo(&submodule, &[("README.md", "# submodule\n")]);
+
+    let cwd = context.workdir();
+    Command::new("git")
+        .args(["-c", "protocol.file.allow=always", "submodule", "add"])
+        .arg(&*submodule)
+        .arg("sub")
+        .current_dir(cwd)
+        .assert()
+        .success();
+
+    context.write_pre_commit_config(indoc::indoc! {r"
+        repos:
+          - repo: local
+            hooks:
+              - id: all-types
+                name: all types
+                language: system
+                entry: echo
+                types: []
+                verbose: true
+              - id: directories
+                name: directories
+                language: system
+                entry: echo
+                types: [directory]
+                verbose: true
+    "});
+    cwd.child("a.txt").write_str("Hello, a!")?;
+    context.git_add(".");
+
+    // The submodule is only passed to the hook selecting directories.
+    cmd_snapshot!(context.filters(), context.run().arg("--all-files"), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    all types.......................................................(3 files)Passed
+    - hook id: all-types
+    - duration: [TIME]
+      .gitmodules .pre-commit-config.yaml a.txt
+    directories......................................................(1 file)Passed
+    - hook id: directories
+    - duration: [TIME]
+      sub
+
+    ----- stderr -----
+    "#);
+
+    Ok(())
+}
+
+#[test]
+fn color_env() {
+    let context = TestContext::new();
+    context.init_project();
+    context.write_pre_commit_config(indoc::indoc! {r#"
+        repos:
+          - repo: local
+            hooks:
+              - id: env
+                name: env
+                language: system
+                entry: sh -c 'env | grep -E "^(FORCE_COLOR|CLICOLOR_FORCE|PY_COLORS|NO_COLOR)=" | sort > env.txt'
+                pass_filenames: false
+                always_run: true
+    "#});
+    context.git_add(".");
+
+    context
+        .run()
+        .args(["--all-files", "--color", "always"])
+        .env_remove("NO_COLOR")
+        .assert()
+        .success();
+    assert_snapshot!(context.read("env.txt"), @r#"
+    CLICOLOR_FORCE=1
+    FORCE_COLOR=1
+    PY_COLORS=1
+    "#);
+
+    context
+        .run()
+        .args(["--all-files", "--color", "never"])
+        .env_remove("FORCE_COLOR")
+        .assert()
+        .success();
+    assert_snapshot!(context.read("env.txt"), @"NO_COLOR=1");
+}
+
+/// `NO_COLOR` and `--no-color` remove every escape code, an explicit `--color` still wins.
+#[test]
+fn no_color() -> Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+    context.write_pre_commit_config(indoc::indoc! {r#"
+        repos:
+          - repo: local
+            hooks:
+              - id: fail
+                name: fail
+                language: system
+                entry: sh -c 'env | grep -E "^(FORCE_COLOR|NO_COLOR)=" | sort > env.txt; exit 1'
+                pass_filenames: false
+                always_run: true
+    "#});
+    context.git_add(".");
+
+    let has_escape = |output: &std::process::Output| {
+        output.stdout.contains(&b'\x1b') || output.stderr.contains(&b'\x1b')
+    };
+
+    let output = context.run().env("NO_COLOR", "1").output()?;
+    assert!(!output.status.success());
+    assert!(!has_escape(&output), "{output:?}");
+    assert_snapshot!(context.read("env.txt"), @"NO_COLOR=1");
+
+    let output = context
+        .run()
+        .arg("--no-color")
+        .env("PRE_COMMIT_COLOR", "always")
+        .env_remove("NO_COLOR")
+        .output()?;
+    assert!(!has_escape(&output), "{output:?}");
+    assert_snapshot!(context.read("env.txt"), @"NO_COLOR=1");
+
+    let output = context
+        .run()
+        .args(["--color", "always"])
+        .env("NO_COLOR", "1")
+        .output()?;
+    assert!(has_escape(&output), "{output:?}");
+
+    Ok(())
+}
+
+#[test]
+fn multiple_hook_stages() {
+    let context = TestContext::new();
+    context.init_project();
+    context.write_pre_commit_config(indoc::indoc! {r"
+        repos:
+          - repo: local
+            hooks:
+              - id: commit-only
+                name: commit only
+                language: system
+                entry: echo commit
+                pass_filenames: false
+                always_run: true
+                verbose: true
+                stages: [pre-commit]
+              - id: both
+                name: both
+                language: system
+                entry: echo both
+                pass_filenames: false
+                always_run: true
+                verbose: true
+                stages: [pre-commit, pre-push]
+              - id: push-only
+                name: push only
+                language: system
+                entry: echo push
+                pass_filenames: false
+                always_run: true
+                verbose: true
+                stages: [pre-push]
+              - id: manual
+                name: manual
+                language: system
+                entry: echo manual
+                pass_filenames: false
+                always_run: true
+                stages: [manual]
+    "});
+    context.git_add(".");
+
+    // Hooks in both stages run only once.
+    cmd_snapshot!(context.filters(), context.run().args(["--hook-stage", "pre-commit", "--hook-stage", "pre-push"]), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    commit only..............................................................Passed
+    - hook id: commit-only
+    - duration: [TIME]
+      commit
+    both.....................................................................Passed
+    - hook id: both
+    - duration: [TIME]
+      both
+    push only................................................................Passed
+    - hook id: push-only
+    - duration: [TIME]
+      push
+
+    ----- stderr -----
+    "#);
+
+    cmd_snapshot!(context.filters(), context.run().args(["manual", "--hook-stage", "pre-commit", "--hook-stage", "pre-push"]), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+
+    ----- stderr -----
+    No hook found for id `manual` and stage `pre-commit`, `pre-push`
+    "#);
+}
+
+#[test]
+fn ci_skip() {
+    let context = TestContext::new();
+    context.init_project();
+    context.write_pre_commit_config(indoc::indoc! {r"
+        repos:
+          - repo: local
+            hooks:
+              - id: echo
+                name: echo
+                language: system
+                entry: echo
+                pass_filenames: false
+                always_run: true
+              - id: lint
+                name: lint
+                language: system
+                entry: echo lint
+                pass_filenames: false
+                always_run: true
+        ci:
+          skip: [lint]
+          autofix_prs: false
+          autoupdate_schedule: monthly
+    "});
+    context.git_add(".");
+
+    cmd_snapshot!(context.filters(), context.run().env_remove("PRE_COMMIT_CI"), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    echo.....................................................................Passed
+    lint.....................................................................Passed
+
+    ----- stderr -----
+    "#);
+
+    // `ci.skip` hooks are skipped in CI.
+    cmd_snapshot!(context.filters(), context.run().arg("--ci"), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    echo.....................................................................Passed
+    lint....................................................................Skipped
+
+    ----- stderr -----
+    "#);
+
+    cmd_snapshot!(context.filters(), context.run().env("PRE_COMMIT_CI", "1"), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    echo.....................................................................Passed
+    lint....................................................................Skipped
+
+    ----- stderr -----
+    "#);
+}
+
+#[test]
+fn show_diff_on_failure_in_ci() -> Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+    context.write_pre_commit_config(indoc::indoc! {r#"
+        repos:
+          - repo: local
+            hooks:
+              - id: modify
+                name: modify
+                language: system
+                entry: python3 -c 'open("a.txt", "wt").write("modified\n")'
+                pass_filenames: false
+                always_run: true
+    "#});
+
+    let cwd = context.workdir();
+    cwd.child("a.txt").write_str("a\n")?;
+    context.git_add(".");
+
+    // The diff is shown by default in CI.
+    cmd_snapshot!(context.filters(), context.run().arg("--all-files").env("CI", "true"), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    modify..........................................................(2 files)Failed
+    - hook id: modify
+    - files were modified by this hook
+    All changes made by hooks:
+    diff --git a/a.txt b/a.txt
+    index 7898192..2e09960 100644
+    --- a/a.txt
+    +++ b/a.txt
+    @@ -1 +1 @@
+    -a
+    +modified
+
+    ----- stderr -----
+    "#);
+
+    // Unless disabled explicitly.
+    cwd.child("a.txt").write_str("a\n")?;
+    cmd_snapshot!(context.filters(), context.run().arg("--all-files").arg("--no-show-diff-on-failure").env("CI", "true"), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    modify..........................................................(2 files)Failed
+    - hook id: modify
+    - files were modified by this hook
+
+    ----- stderr -----
+    "#);
+
+    // Not shown outside of CI.
+    cwd.child("a.txt").write_str("a\n")?;
+    cmd_snapshot!(context.filters(), context.run().arg("--all-files").env("CI", "false"), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    modify..........................................................(2 files)Failed
+    - hook id: modify
+    - files were modified by this hook
+
+    ----- stderr -----
+    "#);
+
+    Ok(())
+}
+
+#[test]
+fn github_annotations() -> Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+    context.write_pre_commit_config(indoc::indoc! {r"
+        repos:
+          - repo: local
+            hooks:
+              - id: no-todo
+                name: no todo
+                entry: 'TODO'
+                language: pygrep
+                files: \.py$
+              - id: no-b
+                name: no b
+                entry: b.py is not allowed
+                language: fail
+                files: ^b\.py$
+    "});
+
+    let cwd = context.workdir();
+    cwd.child("a.py").write_str("import os\n# TODO: remove\n")?;
+    cwd.child("b.py").write_str("print('b')\n")?;
+    context.git_add(".");
+
+    cmd_snapshot!(context.filters(), context.run().arg("--output-format").arg("github"), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    no todo..................................................................Failed
+    - hook id: no-todo
+    - exit code: 1
+      a.py:2:# TODO: remove
+    ::error file=a.py,line=2,title=no todo::# TODO: remove
+    no b.....................................................................Failed
+    - hook id: no-b
+    - exit code: 1
+      b.py is not allowed
+
+      b.py
+    ::error file=b.py,title=no b::b.py is not allowed
+
+    ----- stderr -----
+    "#);
+
+    // Selected automatically in GitHub Actions.
+    cmd_snapshot!(context.filters(), context.run().arg("no-todo").arg("--no-show-diff-on-failure").env("GITHUB_ACTIONS", "true"), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    no todo..................................................................Failed
+    - hook id: no-todo
+    - exit code: 1
+      a.py:2:# TODO: remove
+    ::error file=a.py,line=2,title=no todo::# TODO: remove
+
+    ----- stderr -----
+    "#);
+
+    cmd_snapshot!(context.filters(), context.run().arg("no-todo").arg("--no-show-diff-on-failure").arg("--output-format").arg("text").env("GITHUB_ACTIONS", "true"), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    no todo..................................................................Failed
+    - hook id: no-todo
+    - exit code: 1
+      a.py:2:# TODO: remove
+
+    ----- stderr -----
+    "#);
+
+    Ok(())
+}
+
+#[test]
+fn files_dedup_and_order() -> Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+    context.write_pre_commit_config(indoc::indoc! {r#"
+        repos:
+          - repo: local
+            hooks:
+              - id: echo
+                name: echo
+                language: system
+                entry: sh -c 'echo "$@"; exit 1' --
+    "#});
+
+    let cwd = context.workdir();
+    cwd.child("a.txt").write_str("Hello, a!")?;
+    cwd.child("b.txt").write_str("Hello, b!")?;
+    cwd.child("c.txt").write_str("Hello, c!")?;
+    context.git_add(".");
+
+    let run = |files: &[&str]| {
+        let mut cmd = context.run();
+        for file in files {
+            cmd.arg("--files").arg(file);
+        }
+        let output = cmd.output().expect("Failed to run pre-commit");
+        String::from_utf8(output.stdout).expect("Invalid UTF-8")
+    };
+
+    let canonical = run(&["a.txt", "b.txt", "c.txt"]);
+    let shuffled = run(&["c.txt", "b.txt", "a.txt", "b.txt", "c.txt"]);
+    assert_eq!(canonical, shuffled);
+    for file in ["a.txt", "b.txt", "c.txt"] {
+        assert_eq!(shuffled.matches(file).count(), 1, "{shuffled}");
+    }
+
+    Ok(())
+}
+
+/// `--jobs` is taken from the CLI, then `PRE_COMMIT_JOBS`, then the config, and clamped to at least 1.
+#[test]
+fn jobs_resolution() {
+    let context = TestContext::new();
+    context.init_project();
+    context.write_pre_commit_config(indoc::indoc! {r"
+        jobs: 100000
+        repos:
+          - repo: local
+            hooks:
+              - id: local
+                name: local
+                language: system
+                entry: echo Hello, world!
+                always_run: true
+    "});
+    context.git_add(".");
+
+    let filters: Vec<_> = [(r"on \d+ CPU\(s\)", "on [N] CPU(s)")]
+        .into_iter()
+        .chain(context.filters())
+        .collect();
+
+    cmd_snapshot!(filters.clone(), context.run(), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    local....................................................................Passed
+
+    ----- stderr -----
+    warning: Running 100000 jobs on [N] CPU(s), hooks will compete for resources; consider lowering `--jobs`
+    "#);
+
+    // The env var overrides the config.
+    cmd_snapshot!(filters.clone(), context.run().env("PRE_COMMIT_JOBS", "0"), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    local....................................................................Passed
+
+    ----- stderr -----
+    "#);
+
+    cmd_snapshot!(filters.clone(), context.run().env("PRE_COMMIT_JOBS", "many"), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    local....................................................................Passed
+
+    ----- stderr -----
+    warning: Ignoring invalid `PRE_COMMIT_JOBS`: `many`
+    warning: Running 100000 jobs on [N] CPU(s), hooks will compete for resources; consider lowering `--jobs`
+    "#);
+
+    // `--jobs` overrides the env var, 0 runs hooks one at a time.
+    cmd_snapshot!(filters.clone(), context.run().arg("--jobs").arg("0").env("PRE_COMMIT_JOBS", "100000"), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    local....................................................................Passed
+
+    ----- stderr -----
+    "#);
+}
+
+/// `--print-env-path` fails for unknown hooks and hooks without an environment.
+#[test]
+fn print_env_path() {
+    let context = TestContext::new();
+    context.init_project();
+    context.write_pre_commit_config(indoc::indoc! {r"
+        repos:
+          - repo: local
+            hooks:
+              - id: local
+                name: local
+                language: system
+                entry: echo Hello, world!
+    "});
+    context.git_add(".");
+
+    cmd_snapshot!(context.filters(), context.run().arg("--print-env-path").arg("missing"), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+
+    ----- stderr -----
+    No hook found for id `missing`
+    "#);
+
+    cmd_snapshot!(context.filters(), context.run().arg("--print-env-path").arg("local"), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+
+    ----- stderr -----
+    Hook `local` uses language `system`, which has no environment
+    "#);
+}
+
+/// `--list-stages` groups the hooks by stage, hooks without `stages` follow `default_stages`.
+#[test]
+fn list_stages() {
+    let context = TestContext::new();
+    context.init_project();
+    context.write_pre_commit_config(indoc::indoc! {r"
+        default_stages: [pre-commit, pre-push]
+        repos:
+          - repo: local
+            hooks:
+              - id: default
+                name: default
+                language: system
+                entry: echo
+              - id: push
+                name: push
+                language: system
+                entry: echo
+                stages: [pre-push]
+              - id: commit
+                name: commit
+                language: system
+                entry: echo
+                stages: [pre-commit, commit-msg]
+              - id: manual
+                name: manual
+                language: system
+                entry: echo
+                stages: [manual]
+    "});
+    context.git_add(".");
+
+    cmd_snapshot!(context.filters(), context.run().arg("--list-stages"), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    manual:
+      manual
+    commit-msg:
+      commit
+    pre-commit:
+      default
+      commit
+    pre-push:
+      default
+      push
+
+    ----- stderr -----
+    "#);
+}
+
+/// `--include-untracked` adds untracked files to `--all-files`, but never ignored ones.
+#[test]
+fn all_files_include_untracked() -> Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+    context.write_pre_commit_config(indoc::indoc! {r"
+        repos:
+          - repo: local
+            hooks:
+              - id: echo
+                name: echo
+                language: system
+                entry: echo
+                files: \.txt$
+                verbose: true
+    "});
+
+    let cwd = context.workdir();
+    cwd.child("tracked.txt").write_str("Hello, tracked!")?;
+    cwd.child(".gitignore").write_str("ignored.txt\n")?;
+    context.git_add(".");
+    cwd.child("untracked.txt").write_str("Hello, untracked!")?;
+    cwd.child("ignored.txt").write_str("Hello, ignored!")?;
+
+    cmd_snapshot!(context.filters(), context.run().arg("--all-files"), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    echo.............................................................(1 file)Passed
+    - hook id: echo
+    - duration: [TIME]
+      tracked.txt
+
+    ----- stderr -----
+    "#);
+
+    cmd_snapshot!(context.filters(), context.run().arg("--all-files").arg("--include-untracked"), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    echo............................................................(2 files)Passed
+    - hook id: echo
+    - duration: [TIME]
+      tracked.txt untracked.txt
+
+    ----- stderr -----
+    "#);
+
+    Ok(())
+}
+
+/// A completely cloned repo is reused without network access, an incomplete one is cloned again.
+#[test]
+fn reuse_cached_repo() -> Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+
+    let hooks_repo = tempfile::tempdir()?;
+    make_hooks_repo(
+        hooks_repo.path(),
+        &[(
+            ".pre-commit-hooks.yaml",
+            indoc::indoc! {r"
+                - id: echo
+                  name: echo
+                  entry: echo
+                  language: system
+                  always_run: true
+            "},
+        )],
+    );
+    let url = url::Url::from_directory_path(hooks_repo.path()).unwrap();
+
+    context.write_pre_commit_config(&indoc::formatdoc! {r"
+        repos:
+          - repo: {url}
+            rev: v1
+            hooks:
+              - id: echo
+    "});
+    context.git_add(".");
+
+    let filters = context
+        .filters()
+        .into_iter()
+        .chain([(r"file://\S+@v1", "[HOOKS_REPO]@v1")])
+        .collect::<Vec<_>>();
+
+    cmd_snapshot!(filters.clone(), context.run(), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    Cloning [HOOKS_REPO]@v1
+    echo.....................................................................Passed
+
+    ----- stderr -----
+    "#);
+
+    // Without the completion marker, e.g. after an interrupted clone, the repo is cloned again.
+    for entry in fs_err::read_dir(context.home_dir().path())? {
+        let marker = entry?.path().join(".git").join("pre-commit-clone-complete");
+        if marker.exists() {
+            fs_err::remove_file(marker)?;
+        }
+    }
+    cmd_snapshot!(filters.clone(), context.run(), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    Cloning [HOOKS_REPO]@v1
+    echo.....................................................................Passed
+
+    ----- stderr -----
+    "#);
+
+    // A cache hit doesn't fetch, so it works with the remote gone.
+    drop(hooks_repo);
+    cmd_snapshot!(filters.clone(), context.run(), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    echo.....................................................................Passed
+
+    ----- stderr -----
+    "#);
+
+    Ok(())
+}
+
+/// Hooks using a language that isn't supported yet fail early with the supported languages.
+#[test]
+fn unsupported_language() {
+    let context = TestContext::new();
+    context.init_project();
+    context.write_pre_commit_config(indoc::indoc! {r"
+        repos:
+          - repo: local
+            hooks:
+              - id: perl-hook
+                name: perl-hook
+                language: perl
+                entry: perl -e 'print 1'
+    "});
+    context.git_add(".");
+
+    cmd_snapshot!(context.filters(), context.run(), @r#"
+    success: false
+    exit_code: 2
+    ----- stdout -----
+
+    ----- stderr -----
+    error: Hook `perl-hook` uses language `perl`, which is not supported yet (supported languages: docker, fail, golang, pygrep, python, ruby, rust, script, system)
+    "#);
+}
+
+/// Languages without an environment have nowhere to install `additional_dependencies`.
+#[test]
+fn additional_dependencies_not_supported() {
+    let context = TestContext::new();
+    context.init_project();
+    context.write_pre_commit_config(indoc::indoc! {r"
+        repos:
+          - repo: local
+            hooks:
+              - id: system-hook
+                name: system-hook
+                language: system
+                entry: echo
+                additional_dependencies: [requests]
+    "});
+    context.git_add(".");
+
+    cmd_snapshot!(context.filters(), context.run(), @r#"
+    success: false
+    exit_code: 2
+    ----- stdout -----
+
+    ----- stderr -----
+    error: Hook `system-hook` specifies `additional_dependencies`, but language `system` does not install an environment to add them to
+    "#);
+}
+
+/// A hook that passed on the same files is not run again, until a file changes.
+#[test]
+fn cached_hook_results() -> Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+
+    let hooks_repo = tempfile::tempdir()?;
+    make_hooks_repo(
+        hooks_repo.path(),
+        &[(
+            ".pre-commit-hooks.yaml",
+            indoc::indoc! {r"
+                - id: echo
+                  name: echo
+                  entry: echo
+                  language: system
+                  files: \.txt$
+            "},
+        )],
+    );
+    let url = url::Url::from_directory_path(hooks_repo.path()).unwrap();
+
+    context.write_pre_commit_config(&indoc::formatdoc! {r"
+        repos:
+          - repo: {url}
+            rev: v1
+            hooks:
+              - id: echo
+    "});
+
+    let cwd = context.workdir();
+    cwd.child("file.txt").write_str("Hello, world!\n")?;
+    context.git_add(".");
+
+    let filters = context
+        .filters()
+        .into_iter()
+        .chain([(r"file://\S+@v1", "[HOOKS_REPO]@v1")])
+        .collect::<Vec<_>>();
+
+    cmd_snapshot!(filters.clone(), context.run(), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    Cloning [HOOKS_REPO]@v1
+    echo.....................................................................Passed
+
+    ----- stderr -----
+    "#);
+
+    cmd_snapshot!(filters.clone(), context.run(), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    echo.............................................................(cached)Passed
+
+    ----- stderr -----
+    "#);
+
+    cmd_snapshot!(filters.clone(), context.run().arg("--no-cache"), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    echo.....................................................................Passed
+
+    ----- stderr -----
+    "#);
+
+    // A change to the content invalidates the result.
+    cwd.child("file.txt").write_str("Hello, changed!\n")?;
+    context.git_add(".");
+
+    cmd_snapshot!(filters.clone(), context.run(), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    echo.....................................................................Passed
+
+    ----- stderr -----
+    "#);
+
+    Ok(())
+}
+
+/// Local hooks are never cached, their scripts can change without any file they check changing.
+#[cfg(unix)]
+#[test]
+fn local_hook_results_not_cached() -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let context = TestContext::new();
+    context.init_project();
+    context.write_pre_commit_config(indoc::indoc! {r"
+        repos:
+          - repo: local
+            hooks:
+              - id: check
+                name: check
+                language: script
+                entry: ./check.sh
+                files: \.txt$
+    "});
+
+    let cwd = context.workdir();
+    cwd.child("file.txt").write_str("Hello, world!\n")?;
+    cwd.child("check.sh").write_str("#!/bin/sh\nexit 0\n")?;
+    fs_err::set_permissions(
+        cwd.child("check.sh").path(),
+        std::fs::Permissions::from_mode(0o755),
+    )?;
+    context.git_add(".");
+
+    cmd_snapshot!(context.filters(), context.run(), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    check....................................................................Passed
+
+    ----- stderr -----
+    "#);
+
+    // The script now fails, the earlier pass is not reused.
+    cwd.child("check.sh").write_str("#!/bin/sh\nexit 1\n")?;
+    context.git_add(".");
+
+    cmd_snapshot!(context.filters(), context.run(), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    check....................................................................Failed
+    - hook id: check
+    - exit code: 1
+
+    ----- stderr -----
+    "#);
+
+    Ok(())
+}
+
+/// Cached passes are keyed to the hook repo rev, so an updated hook runs again.
+#[test]
+fn cached_hook_results_rev() -> Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+
+    let hooks_repo = tempfile::tempdir()?;
+    make_hooks_repo(
+        hooks_repo.path(),
+        &[(
+            ".pre-commit-hooks.yaml",
+            indoc::indoc! {r"
+                - id: echo
+                  name: echo
+                  entry: echo
+                  language: system
+                  files: \.txt$
+            "},
+        )],
+    );
+    let url = url::Url::from_directory_path(hooks_repo.path()).unwrap();
+
+    let config = |rev: &str| {
+        indoc::formatdoc! {r"
+            repos:
+              - repo: {url}
+                rev: {rev}
+                hooks:
+                  - id: echo
+        "}
+    };
+    context.write_pre_commit_config(&config("v1"));
+    let cwd = context.workdir();
+    cwd.child("file.txt").write_str("Hello, world!\n")?;
+    context.git_add(".");
+
+    let filters = context
+        .filters()
+        .into_iter()
+        .chain([(r"file://\S+@(v\d)", "[HOOKS_REPO]@$1")])
+        .collect::<Vec<_>>();
+
+    cmd_snapshot!(filters.clone(), context.run(), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    Cloning [HOOKS_REPO]@v1
+    echo.....................................................................Passed
+
+    ----- stderr -----
+    "#);
+
+    cmd_snapshot!(filters.clone(), context.run(), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    echo.............................................................(cached)Passed
+
+    ----- stderr -----
+    "#);
+
+    // A new rev of the hook, e.g. after `autoupdate`, runs again on the same files.
+    fs_err::write(hooks_repo.path().join("README.md"), "# hooks\n")?;
+    tag_hooks_repo(hooks_repo.path(), "v2");
+    context.write_pre_commit_config(&config("v2"));
+    context.git_add(".");
+
+    cmd_snapshot!(filters.clone(), context.run(), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    Cloning [HOOKS_REPO]@v2
+    echo.....................................................................Passed
+
+    ----- stderr -----
+    "#);
+
+    Ok(())
+}
+
+/// `--all-files` shows the number of files each hook runs on, after filtering.
+#[test]
+fn all_files_count() -> Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+    context.write_pre_commit_config(indoc::indoc! {r"
+        repos:
+          - repo: local
+            hooks:
+              - id: txt
+                name: txt
+                language: system
+                entry: python3 -c 'import sys; print(len(sys.argv) - 1)'
+                files: \.txt$
+                verbose: true
+              - id: python
+                name: python
+                language: system
+                entry: python3 -c 'import sys; print(len(sys.argv) - 1)'
+                types: [python]
+                always_run: true
+                verbose: true
+    "});
+
+    let cwd = context.workdir();
+    for name in ["a.txt", "b.txt", "c.txt", "d.json"] {
+        cwd.child(name).write_str("{}\n")?;
+    }
+    context.git_add(".");
+
+    cmd_snapshot!(context.filters(), context.run().arg("--all-files"), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    txt.............................................................(3 files)Passed
+    - hook id: txt
+    - duration: [TIME]
+      3
+    python..........................................................(0 files)Passed
+    - hook id: python
+    - duration: [TIME]
+      0
+
+    ----- stderr -----
+    "#);
+
+    Ok(())
+}
+
+/// `--files @<path>` reads the filenames from a file.
+#[test]
+fn files_from_list() -> Result<()> {
+    let context = TestContext::new();
+    context.init_project();
+    context.write_pre_commit_config(indoc::indoc! {r"
+        repos:
+          - repo: local
+            hooks:
+              - id: files
+                name: files
+                language: system
+                entry: python3 -c 'import sys; print(sorted(sys.argv[1:]))'
+                verbose: true
+    "});
+
+    let cwd = context.workdir();
+    cwd.child("a.txt").write_str("a")?;
+    cwd.child("b.txt").write_str("b")?;
+    cwd.child("sub/c.txt").write_str("c")?;
+    cwd.child("d.txt").write_str("d")?;
+    cwd.child("list.txt")
+        .write_str("a.txt\r\nsub/c.txt\n\nb.txt\n")?;
+    context.git_add(".");
+
+    cmd_snapshot!(context.filters(), context.run().arg("--files").arg("@list.txt").arg("--files").arg("d.txt"), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    files....................................................................Passed
+    - hook id: files
+    - duration: [TIME]
+      ['a.txt', 'b.txt', 'd.txt', 'sub/c.txt']
+
+    ----- stderr -----
+    "#);
+
+    // A missing list is an error, rather than running on nothing.
+    context
+        .run()
+        .arg("--files")
+        .arg("@missing.txt")
+        .assert()
+        .code(2);
+
+    Ok(())
+}
+
+/// A missing `entry` program fails the hook, with the searched PATH, and other hooks still run.
+#[test]
+fn entry_not_found() {
+    let context = TestContext::new();
+    context.init_project();
+    context.write_pre_commit_config(indoc::indoc! {r"
+        repos:
+          - repo: local
+            hooks:
+              - id: missing
+                name: missing
+                language: system
+                entry: pre-commit-missing-tool --check
+                always_run: true
+              - id: echo
+                name: echo
+                language: system
+                entry: echo
+                always_run: true
+                pass_filenames: false
+    "});
+    context.git_add(".");
+
+    let filters = [(r"PATH: .*", "PATH: [PATH]")]
+        .into_iter()
+        .chain(context.filters())
+        .collect::<Vec<_>>();
+
+    cmd_snapshot!(filters, context.run(), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    missing..................................................................Failed
+    - hook id: missing
+    - exit code: 1
+      Executable `pre-commit-missing-tool` not found in PATH
+      PATH: [PATH]
+      hint: Install `pre-commit-missing-tool` so it's on PATH, or use the language of the tool, e.g. `python`, and add it to `additional_dependencies`
+    echo.....................................................................Passed
+
+    ----- stderr -----
+    "#);
+}
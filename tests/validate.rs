@@ -1,6 +1,6 @@
 use assert_fs::fixture::{FileWriteStr, PathChild};
 
-use crate::common::{cmd_snapshot, TestContext};
+use crate::common::{cmd_snapshot, make_hooks_repo, TestContext};
 
 mod common;
 
@@ -51,7 +51,94 @@ fn validate_config() -> anyhow::Result<()> {
 
     ----- stderr -----
     error: Failed to parse `config-1.yaml`
-      caused by: repos: Invalid remote repo: missing field `rev` at line 2 column 3
+      caused by: repos: Invalid remote repo: `repo: https://github.com/pre-commit/pre-commit-hooks` must set `rev` at line 2 column 3
+    "#);
+
+    context
+        .workdir()
+        .child("config-2.yaml")
+        .write_str(indoc::indoc! {r"
+            repos:
+              - repo: local
+                rev: v1.0.0
+                hooks:
+                  - id: echo
+                    name: echo
+                    language: system
+                    entry: echo
+        "})?;
+
+    cmd_snapshot!(context.filters(), context.validate_config().arg("config-2.yaml"), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+
+    ----- stderr -----
+    error: Failed to parse `config-2.yaml`
+      caused by: repos: Invalid local repo: `repo: local` must not set `rev` at line 2 column 3
+    "#);
+
+    Ok(())
+}
+
+/// Check the revs of remote repos, reporting all bad references at once.
+#[test]
+fn validate_config_check_revs() -> anyhow::Result<()> {
+    let context = TestContext::new();
+
+    let hooks_repo = tempfile::tempdir()?;
+    let other_repo = tempfile::tempdir()?;
+    for repo in [&hooks_repo, &other_repo] {
+        make_hooks_repo(
+            repo.path(),
+            &[(
+                ".pre-commit-hooks.yaml",
+                indoc::indoc! {r"
+                    - id: echo
+                      name: echo
+                      entry: echo
+                      language: system
+                "},
+            )],
+        );
+    }
+    let hooks_url = url::Url::from_directory_path(hooks_repo.path()).unwrap();
+    let other_url = url::Url::from_directory_path(other_repo.path()).unwrap();
+
+    context.write_pre_commit_config(&indoc::formatdoc! {r"
+            repos:
+              - repo: {hooks_url}
+                rev: v1
+                hooks:
+                  - id: echo
+              - repo: {hooks_url}
+                rev: v999.0.0
+                hooks:
+                  - id: echo
+              - repo: {other_url}
+                rev: v999.0.0
+                hooks:
+                  - id: echo
+        "});
+
+    let hooks_escaped = regex::escape(hooks_url.as_str());
+    let other_escaped = regex::escape(other_url.as_str());
+    let filters: Vec<_> = [
+        (hooks_escaped.as_str(), "[HOOKS_REPO]"),
+        (other_escaped.as_str(), "[OTHER_REPO]"),
+    ]
+    .into_iter()
+    .chain(context.filters())
+    .collect();
+
+    cmd_snapshot!(filters, context.validate_config().arg("--check-revs").arg(".pre-commit-config.yaml"), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+
+    ----- stderr -----
+    error: Rev `v999.0.0` not found in `[HOOKS_REPO]`
+    error: Rev `v999.0.0` not found in `[OTHER_REPO]`
     "#);
 
     Ok(())
@@ -116,3 +203,73 @@ fn validate_manifest() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn validate_config_string_args() {
+    let context = TestContext::new();
+
+    context.write_pre_commit_config(indoc::indoc! {r#"
+        repos:
+          - repo: https://github.com/psf/black
+            rev: 24.10.0
+            hooks:
+              - id: black
+                args: "--line-length 88"
+    "#});
+
+    cmd_snapshot!(context.filters(), context.validate_config().arg(".pre-commit-config.yaml"), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+
+    ----- stderr -----
+    error: Failed to parse `.pre-commit-config.yaml`
+      caused by: repos: Invalid remote repo: hook `black`: expected a list of strings for `args`, found a string; wrap each arg separately, e.g. `args: ["--line-length", "88"]` at line 2 column 3
+    "#);
+}
+
+/// A hook id listed twice in a repo is a warning, or an error with `--strict`.
+#[test]
+fn validate_config_duplicate_hook_ids() {
+    let context = TestContext::new();
+
+    context.write_pre_commit_config(indoc::indoc! {r"
+            repos:
+              - repo: https://github.com/pre-commit/pre-commit-hooks
+                rev: v5.0.0
+                hooks:
+                  - id: trailing-whitespace
+                  - id: end-of-file-fixer
+                  - id: trailing-whitespace
+              - repo: local
+                hooks:
+                  - id: echo
+                    name: echo
+                    language: system
+                    entry: echo
+              - repo: local
+                hooks:
+                  - id: echo
+                    name: echo
+                    language: system
+                    entry: echo
+        "});
+
+    cmd_snapshot!(context.filters(), context.validate_config().arg(".pre-commit-config.yaml"), @r#"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+
+    ----- stderr -----
+    warning: Hook `trailing-whitespace` is listed more than once in repo `https://github.com/pre-commit/pre-commit-hooks` of `.pre-commit-config.yaml`
+    "#);
+
+    cmd_snapshot!(context.filters(), context.validate_config().arg("--strict").arg(".pre-commit-config.yaml"), @r#"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+
+    ----- stderr -----
+    error: Hook `trailing-whitespace` is listed more than once in repo `https://github.com/pre-commit/pre-commit-hooks` of `.pre-commit-config.yaml`
+    "#);
+}